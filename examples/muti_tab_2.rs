@@ -5,6 +5,7 @@ use eframe::egui::{FontDefinitions, FontFamily};
 
 #[derive(Debug, Clone)]
 struct Tab {
+    #[allow(dead_code)]
     id: usize,
     title: String,
     content: String,
@@ -10,6 +10,7 @@ use std::path::PathBuf;
 use std::sync::Arc;
 //use editor_rs::editor_app::EditorApp;
 
+#[derive(Default)]
 struct MyApp {
     file_content: String,
     current_file: Option<PathBuf>, // 新增：记录当前文件路径
@@ -26,15 +27,6 @@ impl MyApp {
     }
 }
 
-impl Default for MyApp {
-    fn default() -> Self {
-        Self {
-            file_content: String::new(),
-            current_file: None,
-        }
-    }
-}
-
 impl eframe::App for MyApp {
 
     fn update(&mut self, ctx: &egui::Context, _frame:  &mut eframe::Frame) {
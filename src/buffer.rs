@@ -0,0 +1,456 @@
+use ropey::{Rope, RopeSlice};
+use std::collections::VecDeque;
+use std::ops::Range;
+
+// 最多记住最近多少次修改的位置
+const EDIT_LOG_LEN: usize = 256;
+
+/// 基于 rope 的文本缓冲区，负责行索引、坐标换算以及增量编辑。
+///
+/// 所有位置默认使用字符（char）下标；需要字节或行列坐标时通过对应的换算函数转换。
+#[derive(Clone, Default)]
+pub struct TextBuffer {
+    rope: Rope,
+    // 每次修改后递增，用于让缓存（布局、高亮等）判断是否过期
+    version: u64,
+    // 最近的修改：(修改后的版本号, 受影响的行)
+    edit_log: VecDeque<(u64, LineChange)>,
+    // 还没有同步到其他窗格的修改
+    splices: Vec<Splice>,
+}
+
+/// 一次或几次修改涉及的行：修改前的 `start..old_end` 行变成了 `start..new_end` 行，
+/// 之前和之后的行内容不变（之后的行号可能整体移动）。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineChange {
+    pub start: usize,
+    pub old_end: usize,
+    pub new_end: usize,
+}
+
+impl LineChange {
+    /// 在这段修改之后又发生了 `next`（行号以这段修改之后为准），合并成一段
+    fn then(self, next: LineChange) -> LineChange {
+        // 两段中间没有改过的行也算在里面
+        let end = self.new_end.max(next.old_end);
+        LineChange {
+            start: self.start.min(next.start),
+            old_end: end - self.new_end + self.old_end,
+            new_end: end - next.old_end + next.new_end,
+        }
+    }
+}
+
+/// 一处修改在字符下标上的效果：`start` 处的 `removed` 个字符换成了 `inserted` 个字符。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Splice {
+    pub start: usize,
+    pub removed: usize,
+    pub inserted: usize,
+}
+
+/// 行列坐标，均从 0 开始，列以字符计。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl TextBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_text(text: &str) -> Self {
+        Self {
+            rope: Rope::from_str(text),
+            ..Self::default()
+        }
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// 自版本 `version` 之后被修改过的第一行；没有修改时返回 `None`。
+    /// 记录已经被淘汰时保守地返回第 0 行，让缓存整体失效。
+    pub fn first_changed_line_since(&self, version: u64) -> Option<usize> {
+        if version >= self.version {
+            return None;
+        }
+        let oldest = self.edit_log.front().map_or(self.version, |(v, _)| *v);
+        if oldest > version + 1 {
+            return Some(0);
+        }
+        self.edit_log
+            .iter()
+            .filter(|(v, _)| *v > version)
+            .map(|(_, change)| change.start)
+            .min()
+    }
+
+    /// 自版本 `version` 之后的修改合并成的一段行；没有修改时返回 `None`。
+    /// 记录已经被淘汰时返回整个缓冲区，`old_lines` 是版本 `version` 时的行数。
+    pub fn changed_lines_since(&self, version: u64, old_lines: usize) -> Option<LineChange> {
+        if version >= self.version {
+            return None;
+        }
+        let oldest = self.edit_log.front().map_or(self.version, |(v, _)| *v);
+        if oldest > version + 1 {
+            return Some(LineChange {
+                start: 0,
+                old_end: old_lines,
+                new_end: self.len_lines(),
+            });
+        }
+        self.edit_log
+            .iter()
+            .filter(|(v, _)| *v > version)
+            .map(|(_, change)| *change)
+            .reduce(LineChange::then)
+    }
+
+    /// 取走上次调用以来的所有修改，用来移动其他窗格中的光标
+    pub fn take_splices(&mut self) -> Vec<Splice> {
+        std::mem::take(&mut self.splices)
+    }
+
+    pub fn rope(&self) -> &Rope {
+        &self.rope
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rope.len_chars() == 0
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    /// 行数，末尾的换行符之后还算一行（与大多数编辑器一致）。
+    pub fn len_lines(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    // ---- 坐标换算 ----
+
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        self.rope.char_to_byte(char_idx.min(self.len_chars()))
+    }
+
+    pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+        self.rope.byte_to_char(byte_idx.min(self.len_bytes()))
+    }
+
+    pub fn char_to_line(&self, char_idx: usize) -> usize {
+        self.rope.char_to_line(char_idx.min(self.len_chars()))
+    }
+
+    pub fn line_to_char(&self, line_idx: usize) -> usize {
+        self.rope.line_to_char(line_idx.min(self.len_lines()))
+    }
+
+    pub fn line_to_byte(&self, line_idx: usize) -> usize {
+        self.rope.line_to_byte(line_idx.min(self.len_lines()))
+    }
+
+    pub fn byte_to_line(&self, byte_idx: usize) -> usize {
+        self.rope.byte_to_line(byte_idx.min(self.len_bytes()))
+    }
+
+    pub fn char_to_line_col(&self, char_idx: usize) -> LineCol {
+        let char_idx = char_idx.min(self.len_chars());
+        let line = self.rope.char_to_line(char_idx);
+        LineCol {
+            line,
+            col: char_idx - self.rope.line_to_char(line),
+        }
+    }
+
+    /// 行列转字符下标；列超出行尾时停在行尾（换行符之前）。
+    pub fn line_col_to_char(&self, pos: LineCol) -> usize {
+        if pos.line >= self.len_lines() {
+            return self.len_chars();
+        }
+        self.line_to_char(pos.line) + pos.col.min(self.line_len_chars(pos.line))
+    }
+
+    // ---- 行访问 ----
+
+    pub fn line(&self, line_idx: usize) -> RopeSlice<'_> {
+        self.rope.line(line_idx)
+    }
+
+    /// 行内容（不含换行符）的字符数。
+    pub fn line_len_chars(&self, line_idx: usize) -> usize {
+        let line = self.rope.line(line_idx);
+        let mut len = line.len_chars();
+        if len > 0 && line.char(len - 1) == '\n' {
+            len -= 1;
+            if len > 0 && line.char(len - 1) == '\r' {
+                len -= 1;
+            }
+        }
+        len
+    }
+
+    /// 行内容（不含换行符）。
+    pub fn line_text(&self, line_idx: usize) -> String {
+        let start = self.line_to_char(line_idx);
+        self.slice_to_string(start..start + self.line_len_chars(line_idx))
+    }
+
+    /// 从 `lines.start` 行开头到 `lines.end` 行开头之间的字符范围。
+    pub fn lines_char_range(&self, lines: Range<usize>) -> Range<usize> {
+        self.line_to_char(lines.start)..self.line_to_char(lines.end)
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> RopeSlice<'_> {
+        let range = self.clamp_range(range);
+        self.rope.slice(range)
+    }
+
+    pub fn slice_to_string(&self, range: Range<usize>) -> String {
+        self.slice(range).to_string()
+    }
+
+    pub fn char_at(&self, char_idx: usize) -> Option<char> {
+        (char_idx < self.len_chars()).then(|| self.rope.char(char_idx))
+    }
+
+    // ---- 增量编辑 ----
+
+    pub fn insert(&mut self, char_idx: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let char_idx = char_idx.min(self.len_chars());
+        let start = self.rope.char_to_line(char_idx);
+        self.rope.insert(char_idx, text);
+        let inserted = text.chars().count();
+        let end = self.rope.char_to_line(char_idx + inserted);
+        self.bump_version(LineChange {
+            start,
+            old_end: start + 1,
+            new_end: end + 1,
+        });
+        self.splices.push(Splice {
+            start: char_idx,
+            removed: 0,
+            inserted,
+        });
+    }
+
+    pub fn remove(&mut self, range: Range<usize>) {
+        let range = self.clamp_range(range);
+        if range.is_empty() {
+            return;
+        }
+        let start = self.rope.char_to_line(range.start);
+        let end = self.rope.char_to_line(range.end);
+        self.rope.remove(range.clone());
+        self.bump_version(LineChange {
+            start,
+            old_end: end + 1,
+            new_end: start + 1,
+        });
+        self.splices.push(Splice {
+            start: range.start,
+            removed: range.len(),
+            inserted: 0,
+        });
+    }
+
+    /// 用 `text` 替换 `range`，返回插入后文本的字符范围。
+    pub fn replace(&mut self, range: Range<usize>, text: &str) -> Range<usize> {
+        let range = self.clamp_range(range);
+        let pending = self.splices.len();
+        self.remove(range.clone());
+        self.insert(range.start, text);
+        // 删除和插入合成一处，落在被替换范围内的位置移到替换文本的开头
+        let inserted = text.chars().count();
+        self.splices.truncate(pending);
+        if !range.is_empty() || inserted > 0 {
+            self.splices.push(Splice {
+                start: range.start,
+                removed: range.len(),
+                inserted,
+            });
+        }
+        range.start..range.start + inserted
+    }
+
+    /// 整体替换内容（例如重新加载文件）。
+    pub fn set_text(&mut self, text: &str) {
+        let old_end = self.len_lines();
+        let removed = self.len_chars();
+        self.rope = Rope::from_str(text);
+        self.bump_version(LineChange {
+            start: 0,
+            old_end,
+            new_end: self.len_lines(),
+        });
+        self.splices.push(Splice {
+            start: 0,
+            removed,
+            inserted: self.len_chars(),
+        });
+    }
+
+    fn bump_version(&mut self, change: LineChange) {
+        self.version += 1;
+        self.edit_log.push_back((self.version, change));
+        if self.edit_log.len() > EDIT_LOG_LEN {
+            self.edit_log.pop_front();
+        }
+    }
+
+    fn clamp_range(&self, range: Range<usize>) -> Range<usize> {
+        let len = self.len_chars();
+        let end = range.end.min(len);
+        range.start.min(end)..end
+    }
+}
+
+impl std::fmt::Display for TextBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for chunk in self.rope.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+/// 比较编辑前后的文本，得到最小替换：`(旧文本中被替换的字符范围, 新插入的文本)`。
+///
+/// 用于把 `TextEdit` 对可见窗口的修改映射回整个缓冲区。
+pub fn diff_edit(old: &str, new: &str) -> Option<(Range<usize>, String)> {
+    if old == new {
+        return None;
+    }
+    let prefix = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_len = old.chars().count();
+    let new_len = new.chars().count();
+    let max_suffix = old_len.min(new_len) - prefix;
+    let suffix = old
+        .chars()
+        .rev()
+        .zip(new.chars().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let inserted: String = new
+        .chars()
+        .skip(prefix)
+        .take(new_len - prefix - suffix)
+        .collect();
+    Some((prefix..old_len - suffix, inserted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(start: usize, old_end: usize, new_end: usize) -> LineChange {
+        LineChange {
+            start,
+            old_end,
+            new_end,
+        }
+    }
+
+    #[test]
+    fn changed_lines_of_single_edits() {
+        let mut buffer = TextBuffer::from_text("a\nb\nc\nd");
+        let version = buffer.version();
+        assert_eq!(buffer.changed_lines_since(version, 4), None);
+
+        // 在第 1 行插入两个换行：第 1 行变成 3 行
+        buffer.insert(3, "x\ny\n");
+        assert_eq!(
+            buffer.changed_lines_since(version, 4),
+            Some(change(1, 2, 4))
+        );
+
+        // 删除第 1、2 行
+        let version = buffer.version();
+        let start = buffer.line_to_char(1);
+        let end = buffer.line_to_char(3);
+        buffer.remove(start..end);
+        assert_eq!(buffer.to_string(), "a\n\nc\nd");
+        assert_eq!(
+            buffer.changed_lines_since(version, 6),
+            Some(change(1, 4, 2))
+        );
+    }
+
+    #[test]
+    fn changed_lines_merge_several_edits() {
+        let mut buffer = TextBuffer::from_text("0\n1\n2\n3\n4\n5");
+        let version = buffer.version();
+        buffer.insert(buffer.line_to_char(4), "new\n");
+        buffer.remove(buffer.line_to_char(1)..buffer.line_to_char(2));
+        // 旧的第 1..5 行变成了新的第 1..5 行，两处修改之间的行也算在里面
+        assert_eq!(
+            buffer.changed_lines_since(version, 6),
+            Some(change(1, 5, 5))
+        );
+        assert_eq!(buffer.to_string(), "0\n2\n3\nnew\n4\n5");
+    }
+
+    #[test]
+    fn changed_lines_after_log_eviction_covers_everything() {
+        let mut buffer = TextBuffer::from_text("a\nb");
+        let version = buffer.version();
+        for _ in 0..EDIT_LOG_LEN + 1 {
+            buffer.insert(0, "x");
+        }
+        assert_eq!(
+            buffer.changed_lines_since(version, 2),
+            Some(change(0, 2, 2))
+        );
+        assert_eq!(buffer.first_changed_line_since(version), Some(0));
+    }
+
+    #[test]
+    fn take_splices_reports_each_edit_once() {
+        let mut buffer = TextBuffer::from_text("hello world");
+        buffer.insert(0, ">");
+        buffer.replace(1..6, "hi");
+        buffer.replace(3..3, "");
+        assert_eq!(
+            buffer.take_splices(),
+            vec![
+                Splice {
+                    start: 0,
+                    removed: 0,
+                    inserted: 1,
+                },
+                Splice {
+                    start: 1,
+                    removed: 5,
+                    inserted: 2,
+                },
+            ]
+        );
+        assert!(buffer.take_splices().is_empty());
+
+        buffer.set_text("abc");
+        assert_eq!(
+            buffer.take_splices(),
+            vec![Splice {
+                start: 0,
+                removed: 9,
+                inserted: 3,
+            }]
+        );
+    }
+}
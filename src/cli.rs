@@ -0,0 +1,238 @@
+use crate::buffer::LineCol;
+use std::ffi::OsString;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+用法: editor-rs [选项] [路径...]
+
+路径:
+  <文件>[:行[:列]]   打开文件并跳到指定位置（行列从 1 开始）；文件不存在时新建
+  <文件夹>           作为工作区打开
+  -                  从标准输入读取内容
+
+选项:
+  -n, --new-window   在新窗口中打开，而不是交给已经运行的编辑器
+  -w, --wait         等到打开的文件被关闭后再返回（可用作 $EDITOR / GIT_EDITOR）
+  -h, --help         显示帮助
+  -V, --version      显示版本
+";
+
+/// 要打开的文件以及光标位置
+#[derive(Clone, Debug)]
+pub struct FileTarget {
+    pub path: PathBuf,
+    /// 从 0 开始的行列
+    pub position: Option<LineCol>,
+}
+
+/// 解析后的命令行参数
+#[derive(Clone, Debug, Default)]
+pub struct CliArgs {
+    pub files: Vec<FileTarget>,
+    pub folders: Vec<PathBuf>,
+    /// `-` 从标准输入读到的内容
+    pub stdin: Option<Vec<u8>>,
+    pub new_window: bool,
+    pub wait: bool,
+}
+
+pub enum Command {
+    Run(CliArgs),
+    Help,
+    Version,
+}
+
+impl CliArgs {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.folders.is_empty() && self.stdin.is_none()
+    }
+}
+
+/// 解析命令行参数（不含程序名）。相对路径按当前目录转换成绝对路径，
+/// 这样交给另一个编辑器进程时也能找到文件。文件名不必是有效的 UTF-8。
+pub fn parse_args(args: impl IntoIterator<Item = OsString>) -> Result<Command, String> {
+    let mut parsed = CliArgs::default();
+    let mut only_paths = false;
+    for arg in args {
+        if !only_paths {
+            match arg.to_str().unwrap_or_default() {
+                "-h" | "--help" => return Ok(Command::Help),
+                "-V" | "--version" => return Ok(Command::Version),
+                "-n" | "--new-window" => {
+                    parsed.new_window = true;
+                    continue;
+                }
+                "-w" | "--wait" => {
+                    parsed.wait = true;
+                    continue;
+                }
+                "--" => {
+                    only_paths = true;
+                    continue;
+                }
+                "-" => {
+                    let mut bytes = Vec::new();
+                    io::stdin()
+                        .read_to_end(&mut bytes)
+                        .map_err(|err| format!("读取标准输入失败：{}", err))?;
+                    parsed.stdin = Some(bytes);
+                    continue;
+                }
+                _ if arg.as_encoded_bytes().starts_with(b"-") => {
+                    return Err(format!("未知选项：{}", arg.to_string_lossy()));
+                }
+                _ => {}
+            }
+        }
+        // 不是 UTF-8 的参数只能是路径，不带行列
+        let target = match arg.to_str() {
+            Some(arg) => parse_target(arg),
+            None => FileTarget {
+                path: PathBuf::from(&arg),
+                position: None,
+            },
+        };
+        let path = std::path::absolute(&target.path)
+            .map_err(|err| format!("无效的路径 {}：{}", arg.to_string_lossy(), err))?;
+        if path.is_dir() {
+            parsed.folders.push(path);
+        } else {
+            parsed.files.push(FileTarget { path, ..target });
+        }
+    }
+    Ok(Command::Run(parsed))
+}
+
+/// 拆开 `file:line:col` / `file:line`。路径本身存在时原样使用，
+/// 因此文件名里带冒号（或者 Windows 盘符）也不会被误拆。
+fn parse_target(arg: &str) -> FileTarget {
+    let whole = FileTarget {
+        path: PathBuf::from(arg),
+        position: None,
+    };
+    if Path::new(arg).exists() {
+        return whole;
+    }
+    let mut parts = arg.rsplitn(3, ':');
+    let last = parts.next().and_then(|s| s.parse::<usize>().ok());
+    let middle = parts.next();
+    let rest = parts.next();
+    match (last, middle, rest) {
+        // file:line:col
+        (Some(col), Some(line), Some(path))
+            if line.parse::<usize>().is_ok() && !path.is_empty() =>
+        {
+            let line: usize = line.parse().unwrap_or(1);
+            FileTarget {
+                path: PathBuf::from(path),
+                position: Some(LineCol {
+                    line: line.saturating_sub(1),
+                    col: col.saturating_sub(1),
+                }),
+            }
+        }
+        // file:line（路径中可能还有别的冒号）
+        (Some(line), Some(_), _) => {
+            let path = &arg[..arg.rfind(':').unwrap_or(arg.len())];
+            if path.is_empty() {
+                return whole;
+            }
+            FileTarget {
+                path: PathBuf::from(path),
+                position: Some(LineCol {
+                    line: line.saturating_sub(1),
+                    col: 0,
+                }),
+            }
+        }
+        _ => whole,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> CliArgs {
+        match parse_args(args.iter().map(OsString::from)) {
+            Ok(Command::Run(args)) => args,
+            _ => panic!("解析失败：{:?}", args),
+        }
+    }
+
+    #[test]
+    fn target_with_line_and_column() {
+        let target = parse_target("no-such-dir/main.rs:12:3");
+        assert_eq!(target.path, PathBuf::from("no-such-dir/main.rs"));
+        assert_eq!(target.position, Some(LineCol { line: 11, col: 2 }));
+    }
+
+    #[test]
+    fn target_with_line_only() {
+        let target = parse_target("no-such-dir/main.rs:7");
+        assert_eq!(target.path, PathBuf::from("no-such-dir/main.rs"));
+        assert_eq!(target.position, Some(LineCol { line: 6, col: 0 }));
+
+        // 路径中的其他冒号保留
+        let target = parse_target("no-such-dir/a:b:3");
+        assert_eq!(target.path, PathBuf::from("no-such-dir/a:b"));
+        assert_eq!(target.position, Some(LineCol { line: 2, col: 0 }));
+    }
+
+    #[test]
+    fn target_without_position() {
+        for arg in [
+            "no-such-dir/main.rs",
+            "no-such-dir/main.rs:x",
+            ":5",
+            "Cargo.toml",
+        ] {
+            let target = parse_target(arg);
+            assert_eq!(target.path, PathBuf::from(arg));
+            assert_eq!(target.position, None);
+        }
+    }
+
+    #[test]
+    fn args_options_and_paths() {
+        let args = run(&["-w", "-n", "no-such-dir/main.rs:3", "src"]);
+        assert!(args.wait);
+        assert!(args.new_window);
+        assert_eq!(args.files.len(), 1);
+        assert!(args.files[0].path.is_absolute());
+        assert!(args.files[0].path.ends_with("no-such-dir/main.rs"));
+        assert_eq!(args.files[0].position, Some(LineCol { line: 2, col: 0 }));
+        assert_eq!(args.folders.len(), 1);
+        assert!(args.folders[0].ends_with("src"));
+    }
+
+    #[test]
+    fn args_after_double_dash_are_paths() {
+        let args = run(&["--", "-n"]);
+        assert!(!args.new_window);
+        assert!(args.files[0].path.ends_with("-n"));
+    }
+
+    #[test]
+    fn args_help_and_unknown_option() {
+        let parse = |args: &[&str]| parse_args(args.iter().map(OsString::from));
+        assert!(matches!(parse(&["a.rs", "--help"]), Ok(Command::Help)));
+        assert!(matches!(parse(&["-V"]), Ok(Command::Version)));
+        assert!(parse(&["--bogus"]).is_err());
+        assert!(run(&[]).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn args_accept_paths_that_are_not_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+        let name = OsString::from_vec(b"no-such-dir/\xff.txt:3".to_vec());
+        let Ok(Command::Run(args)) = parse_args([name]) else {
+            panic!("解析失败");
+        };
+        let path = args.files[0].path.as_os_str().as_encoded_bytes();
+        assert!(path.ends_with(b"no-such-dir/\xff.txt:3"));
+        assert_eq!(args.files[0].position, None);
+    }
+}
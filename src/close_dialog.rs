@@ -0,0 +1,86 @@
+use crate::tabs::Tabs;
+use eframe::egui;
+
+/// 等待用户确认的关闭请求：关闭若干标签，或者退出整个应用。
+pub struct PendingClose {
+    /// 要关闭的所有标签
+    pub ids: Vec<usize>,
+    /// 关闭完成后是否退出应用
+    pub quit: bool,
+}
+
+impl PendingClose {
+    /// 其中还没有保存的标签
+    pub fn dirty_ids(&self, tabs: &Tabs) -> Vec<usize> {
+        self.ids
+            .iter()
+            .copied()
+            .filter(|&id| tabs.get(id).is_some_and(|doc| doc.is_dirty()))
+            .collect()
+    }
+}
+
+pub enum CloseChoice {
+    /// 保存单个文件
+    Save(usize),
+    SaveAll,
+    DontSave,
+    Cancel,
+}
+
+/// 列出未保存的文件，提供 Save / Don't Save / Cancel 以及 Save All。
+pub fn show_close_dialog(
+    ctx: &egui::Context,
+    pending: &PendingClose,
+    tabs: &Tabs,
+) -> Option<CloseChoice> {
+    let dirty = pending.dirty_ids(tabs);
+    let mut choice = None;
+    let title = if pending.quit {
+        "退出前保存修改？"
+    } else {
+        "关闭前保存修改？"
+    };
+    egui::Window::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            ui.label(format!("以下 {} 个文件有未保存的修改：", dirty.len()));
+            ui.add_space(4.0);
+            egui::Grid::new("unsaved_files")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    for id in &dirty {
+                        let Some(document) = tabs.get(*id) else {
+                            continue;
+                        };
+                        let label = ui.label(document.title());
+                        if let Some(path) = &document.path {
+                            label.on_hover_text(path.display().to_string());
+                        }
+                        if ui.button("Save").clicked() {
+                            choice = Some(CloseChoice::Save(*id));
+                        }
+                        ui.end_row();
+                    }
+                });
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if ui.button("Save All").clicked() {
+                    choice = Some(CloseChoice::SaveAll);
+                }
+                if ui.button("Don't Save").clicked() {
+                    choice = Some(CloseChoice::DontSave);
+                }
+                if ui.button("Cancel").clicked() {
+                    choice = Some(CloseChoice::Cancel);
+                }
+            });
+        });
+    if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        choice = Some(CloseChoice::Cancel);
+    }
+    choice
+}
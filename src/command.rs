@@ -0,0 +1,237 @@
+/// 编辑器中所有可以绑定快捷键、出现在菜单里的操作。
+///
+/// `id` 是快捷键文件里使用的名字，`title` 是菜单中显示的文字。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Command {
+    NewFile,
+    Open,
+    OpenFolder,
+    Save,
+    SaveAs,
+    SaveAll,
+    Close,
+    Settings,
+    Quit,
+    Undo,
+    Redo,
+    Find,
+    Replace,
+    FindInFiles,
+    FindNext,
+    FindPrevious,
+    CloseFindBar,
+    AddNextOccurrence,
+    AddCursorAbove,
+    AddCursorBelow,
+    SingleCursor,
+    NextTab,
+    PreviousTab,
+    SplitRight,
+    SplitDown,
+    ClosePane,
+    FocusLeftPane,
+    FocusRightPane,
+    FocusPaneAbove,
+    FocusPaneBelow,
+    Fold,
+    Unfold,
+    UnfoldRecursively,
+    FoldAll,
+    UnfoldAll,
+    FoldLevel1,
+    FoldLevel2,
+    FoldLevel3,
+    FoldLevel4,
+    FoldLevel5,
+    ToggleExplorer,
+    ToggleWordWrap,
+    ToggleMinimap,
+    CommandPalette,
+    GoToFile,
+    GoToLine,
+    About,
+}
+
+impl Command {
+    pub const ALL: [Command; 47] = [
+        Command::NewFile,
+        Command::Open,
+        Command::OpenFolder,
+        Command::Save,
+        Command::SaveAs,
+        Command::SaveAll,
+        Command::Close,
+        Command::Settings,
+        Command::Quit,
+        Command::Undo,
+        Command::Redo,
+        Command::Find,
+        Command::Replace,
+        Command::FindInFiles,
+        Command::FindNext,
+        Command::FindPrevious,
+        Command::CloseFindBar,
+        Command::AddNextOccurrence,
+        Command::AddCursorAbove,
+        Command::AddCursorBelow,
+        Command::SingleCursor,
+        Command::NextTab,
+        Command::PreviousTab,
+        Command::SplitRight,
+        Command::SplitDown,
+        Command::ClosePane,
+        Command::FocusLeftPane,
+        Command::FocusRightPane,
+        Command::FocusPaneAbove,
+        Command::FocusPaneBelow,
+        Command::Fold,
+        Command::Unfold,
+        Command::UnfoldRecursively,
+        Command::FoldAll,
+        Command::UnfoldAll,
+        Command::FoldLevel1,
+        Command::FoldLevel2,
+        Command::FoldLevel3,
+        Command::FoldLevel4,
+        Command::FoldLevel5,
+        Command::ToggleExplorer,
+        Command::ToggleWordWrap,
+        Command::ToggleMinimap,
+        Command::CommandPalette,
+        Command::GoToFile,
+        Command::GoToLine,
+        Command::About,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Command::NewFile => "file.new",
+            Command::Open => "file.open",
+            Command::OpenFolder => "file.openFolder",
+            Command::Save => "file.save",
+            Command::SaveAs => "file.saveAs",
+            Command::SaveAll => "file.saveAll",
+            Command::Close => "file.close",
+            Command::Settings => "file.settings",
+            Command::Quit => "app.quit",
+            Command::Undo => "edit.undo",
+            Command::Redo => "edit.redo",
+            Command::Find => "edit.find",
+            Command::Replace => "edit.replace",
+            Command::FindInFiles => "edit.findInFiles",
+            Command::FindNext => "edit.findNext",
+            Command::FindPrevious => "edit.findPrevious",
+            Command::CloseFindBar => "edit.closeFindBar",
+            Command::AddNextOccurrence => "edit.addNextOccurrence",
+            Command::AddCursorAbove => "edit.addCursorAbove",
+            Command::AddCursorBelow => "edit.addCursorBelow",
+            Command::SingleCursor => "edit.singleCursor",
+            Command::NextTab => "view.nextTab",
+            Command::PreviousTab => "view.previousTab",
+            Command::SplitRight => "view.splitRight",
+            Command::SplitDown => "view.splitDown",
+            Command::ClosePane => "view.closePane",
+            Command::FocusLeftPane => "view.focusLeftPane",
+            Command::FocusRightPane => "view.focusRightPane",
+            Command::FocusPaneAbove => "view.focusPaneAbove",
+            Command::FocusPaneBelow => "view.focusPaneBelow",
+            Command::Fold => "view.fold",
+            Command::Unfold => "view.unfold",
+            Command::UnfoldRecursively => "view.unfoldRecursively",
+            Command::FoldAll => "view.foldAll",
+            Command::UnfoldAll => "view.unfoldAll",
+            Command::FoldLevel1 => "view.foldLevel1",
+            Command::FoldLevel2 => "view.foldLevel2",
+            Command::FoldLevel3 => "view.foldLevel3",
+            Command::FoldLevel4 => "view.foldLevel4",
+            Command::FoldLevel5 => "view.foldLevel5",
+            Command::ToggleExplorer => "view.toggleExplorer",
+            Command::ToggleWordWrap => "view.toggleWordWrap",
+            Command::ToggleMinimap => "view.toggleMinimap",
+            Command::CommandPalette => "view.commandPalette",
+            Command::GoToFile => "go.file",
+            Command::GoToLine => "go.line",
+            Command::About => "help.about",
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Command::NewFile => "New",
+            Command::Open => "Open…",
+            Command::OpenFolder => "Open Folder…",
+            Command::Save => "Save",
+            Command::SaveAs => "Save As…",
+            Command::SaveAll => "Save All",
+            Command::Close => "Close",
+            Command::Settings => "Settings…",
+            Command::Quit => "Quit",
+            Command::Undo => "Undo",
+            Command::Redo => "Redo",
+            Command::Find => "Find…",
+            Command::Replace => "Replace…",
+            Command::FindInFiles => "Find in Files…",
+            Command::FindNext => "Find Next",
+            Command::FindPrevious => "Find Previous",
+            Command::CloseFindBar => "Close Find Bar",
+            Command::AddNextOccurrence => "Add Next Occurrence",
+            Command::AddCursorAbove => "Add Cursor Above",
+            Command::AddCursorBelow => "Add Cursor Below",
+            Command::SingleCursor => "Single Cursor",
+            Command::NextTab => "Next Tab",
+            Command::PreviousTab => "Previous Tab",
+            Command::SplitRight => "Split Right",
+            Command::SplitDown => "Split Down",
+            Command::ClosePane => "Close Pane",
+            Command::FocusLeftPane => "Focus Left Pane",
+            Command::FocusRightPane => "Focus Right Pane",
+            Command::FocusPaneAbove => "Focus Pane Above",
+            Command::FocusPaneBelow => "Focus Pane Below",
+            Command::Fold => "Fold",
+            Command::Unfold => "Unfold",
+            Command::UnfoldRecursively => "Unfold Recursively",
+            Command::FoldAll => "Fold All",
+            Command::UnfoldAll => "Unfold All",
+            Command::FoldLevel1 => "Fold Level 1",
+            Command::FoldLevel2 => "Fold Level 2",
+            Command::FoldLevel3 => "Fold Level 3",
+            Command::FoldLevel4 => "Fold Level 4",
+            Command::FoldLevel5 => "Fold Level 5",
+            Command::ToggleExplorer => "Explorer",
+            Command::ToggleWordWrap => "Word Wrap",
+            Command::ToggleMinimap => "Minimap",
+            Command::CommandPalette => "Command Palette…",
+            Command::GoToFile => "Go to File…",
+            Command::GoToLine => "Go to Line…",
+            Command::About => "About…",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|command| command.id() == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn all_lists_every_command_once() {
+        // 变体没有附带数据，判别值从 0 开始连续编号；About 是最后一个
+        let discriminants: HashSet<usize> = Command::ALL.iter().map(|&c| c as usize).collect();
+        assert_eq!(discriminants.len(), Command::ALL.len());
+        assert_eq!(Command::About as usize + 1, Command::ALL.len());
+    }
+
+    #[test]
+    fn ids_round_trip() {
+        let ids: HashSet<&str> = Command::ALL.iter().map(|c| c.id()).collect();
+        assert_eq!(ids.len(), Command::ALL.len());
+        for command in Command::ALL {
+            assert_eq!(Command::from_id(command.id()), Some(command));
+        }
+        assert_eq!(Command::from_id("no.such.command"), None);
+    }
+}
@@ -0,0 +1,321 @@
+use eframe::egui;
+use std::ops::Range;
+
+// 编辑距离超过这个数时不再细算，把中间整段当作一处修改
+const MAX_EDITS: isize = 4_000;
+/// 差异视图最多显示的行数
+pub const MAX_DIFF_LINES: usize = 500;
+
+/// 一处差异：旧内容的 `old` 行被替换成新内容的 `new` 行。
+/// `old` 为空表示插入，`new` 为空表示删除。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// 按行比较，返回按位置排列的差异（Myers 算法，线性空间）
+pub fn diff_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk> {
+    // 先去掉相同的开头和结尾，通常只剩很少的几行需要比较
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+    if a.is_empty() && b.is_empty() {
+        return Vec::new();
+    }
+    let matches = myers(a, b).unwrap_or_default();
+
+    let mut hunks = Vec::new();
+    let (mut x, mut y) = (0, 0);
+    for (mx, my) in matches.into_iter().chain([(a.len(), b.len())]) {
+        if mx > x || my > y {
+            hunks.push(Hunk {
+                old: prefix + x..prefix + mx,
+                new: prefix + y..prefix + my,
+            });
+        }
+        x = mx + 1;
+        y = my + 1;
+    }
+    hunks
+}
+
+/// 返回两边相同的行 `(旧行, 新行)`；差异太多时返回 `None`
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Option<Vec<(usize, usize)>> {
+    let mut matches = Vec::new();
+    // 整体的编辑距离不超过 MAX_EDITS 时，拆开后的各段也不会超过
+    let (x, y, u, v, d) = middle_snake(a, b, (MAX_EDITS + 1) / 2)?;
+    split(a, b, (x, y, u, v, d), 0, 0, &mut matches);
+    Some(matches)
+}
+
+/// 以中间的一段相同的行为界，把两边分成前后两半分别比较
+fn split<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    snake: (usize, usize, usize, usize, isize),
+    a_offset: usize,
+    b_offset: usize,
+    matches: &mut Vec<(usize, usize)>,
+) {
+    let (x, y, u, v, d) = snake;
+    if d <= 1 {
+        // 最多差一行：较长的一边比较短的一边多出一行，依次对齐即可
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if a[i] == b[j] {
+                matches.push((a_offset + i, b_offset + j));
+                i += 1;
+                j += 1;
+            } else if a.len() > b.len() {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        return;
+    }
+    diff_range(&a[..x], &b[..y], a_offset, b_offset, matches);
+    matches.extend((0..u - x).map(|i| (a_offset + x + i, b_offset + y + i)));
+    diff_range(&a[u..], &b[v..], a_offset + u, b_offset + v, matches);
+}
+
+fn diff_range<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    a_offset: usize,
+    b_offset: usize,
+    matches: &mut Vec<(usize, usize)>,
+) {
+    if a.is_empty() || b.is_empty() {
+        return;
+    }
+    if let Some(snake) = middle_snake(a, b, isize::MAX) {
+        split(a, b, snake, a_offset, b_offset, matches);
+    }
+}
+
+/// 从两头同时搜索最短编辑路径，返回两边相遇处的一段相同的行
+/// `(x, y, u, v, d)`：旧内容的 `x..u` 对应新内容的 `y..v`，`d` 是整体的编辑距离。
+/// 只保存当前一轮的 `v`，内存与行数成正比；`d` 超过 `2 * max_d` 时返回 `None`
+fn middle_snake<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    max_d: isize,
+) -> Option<(usize, usize, usize, usize, isize)> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    let at = |k: isize| (offset + k) as usize;
+    // forward[k]：从开头出发，对角线 k 上走得最远的 x；
+    // backward[k]：从末尾出发（两边都倒过来看），对角线 k 上走得最远的 x
+    let mut forward = vec![0isize; (2 * offset + 1) as usize];
+    let mut backward = vec![0isize; (2 * offset + 1) as usize];
+    for d in 0..=max.min(max_d) {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            let back_k = delta - k;
+            if odd && (-(d - 1)..=d - 1).contains(&back_k) && x + backward[at(back_k)] >= n {
+                return Some((x0 as usize, y0 as usize, x as usize, y as usize, 2 * d - 1));
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+            let forward_k = delta - k;
+            if !odd && (-d..=d).contains(&forward_k) && x + forward[at(forward_k)] >= n {
+                return Some((
+                    (n - x) as usize,
+                    (m - y) as usize,
+                    (n - x0) as usize,
+                    (m - y0) as usize,
+                    2 * d,
+                ));
+            }
+        }
+    }
+    None
+}
+
+/// 差异中的一行
+pub enum DiffLine {
+    /// 一处差异的开头，显示从 1 开始的行号
+    Header(usize, usize),
+    Removed(String),
+    Added(String),
+}
+
+/// `old` → `new` 的逐行差异，最多 MAX_DIFF_LINES 行
+pub fn diff_text(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut lines = Vec::new();
+    for hunk in diff_lines(&old, &new) {
+        if lines.len() >= MAX_DIFF_LINES {
+            break;
+        }
+        lines.push(DiffLine::Header(hunk.old.start + 1, hunk.new.start + 1));
+        lines.extend(
+            old[hunk.old]
+                .iter()
+                .map(|l| DiffLine::Removed((*l).to_owned())),
+        );
+        lines.extend(
+            new[hunk.new]
+                .iter()
+                .map(|l| DiffLine::Added((*l).to_owned())),
+        );
+    }
+    lines.truncate(MAX_DIFF_LINES);
+    lines
+}
+
+/// 用红色 / 绿色显示删除和增加的行
+pub fn diff_view(ui: &mut egui::Ui, diff: &[DiffLine]) {
+    let (removed, added) = if ui.visuals().dark_mode {
+        (
+            egui::Color32::from_rgb(240, 110, 110),
+            egui::Color32::from_rgb(110, 200, 110),
+        )
+    } else {
+        (
+            egui::Color32::from_rgb(180, 30, 30),
+            egui::Color32::from_rgb(20, 130, 20),
+        )
+    };
+    for line in diff {
+        let text = match line {
+            DiffLine::Header(old, new) => {
+                egui::RichText::new(format!("@@ 原第 {} 行 → 第 {} 行 @@", old, new)).weak()
+            }
+            DiffLine::Removed(text) => egui::RichText::new(format!("- {}", text)).color(removed),
+            DiffLine::Added(text) => egui::RichText::new(format!("+ {}", text)).color(added),
+        };
+        ui.label(text.monospace());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(old: Range<usize>, new: Range<usize>) -> Hunk {
+        Hunk { old, new }
+    }
+
+    /// 按差异把 `old` 改成 `new`，检查差异是否完整
+    fn apply(old: &[u32], new: &[u32], hunks: &[Hunk]) -> Vec<u32> {
+        let mut out = Vec::new();
+        let mut x = 0;
+        for h in hunks {
+            out.extend_from_slice(&old[x..h.old.start]);
+            out.extend_from_slice(&new[h.new.clone()]);
+            x = h.old.end;
+        }
+        out.extend_from_slice(&old[x..]);
+        out
+    }
+
+    fn lcs_len(a: &[u32], b: &[u32]) -> usize {
+        let mut row = vec![0; b.len() + 1];
+        for x in a {
+            let mut diagonal = 0;
+            for (j, y) in b.iter().enumerate() {
+                let up = row[j + 1];
+                row[j + 1] = if x == y { diagonal + 1 } else { up.max(row[j]) };
+                diagonal = up;
+            }
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn diff_lines_reports_inserts_deletes_and_changes() {
+        let old = ["a", "b", "c", "d", "e"];
+        let new = ["a", "x", "c", "e", "f"];
+        assert_eq!(
+            diff_lines(&old, &new),
+            vec![hunk(1..2, 1..2), hunk(3..4, 3..3), hunk(5..5, 4..5)]
+        );
+        assert!(diff_lines(&old, &old).is_empty());
+        assert_eq!(diff_lines(&old, &[]), vec![hunk(0..5, 0..0)]);
+    }
+
+    #[test]
+    fn diff_lines_keeps_a_longest_common_subsequence() {
+        // 简单的线性同余随机数，字母表很小，重复的行很多
+        let mut seed = 0x2545_f491_u32;
+        let mut next = |limit: u32| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) % limit
+        };
+        for _ in 0..500 {
+            let old: Vec<u32> = (0..next(30)).map(|_| next(4)).collect();
+            let new: Vec<u32> = (0..next(30)).map(|_| next(4)).collect();
+            let hunks = diff_lines(&old, &new);
+            assert_eq!(apply(&old, &new, &hunks), new);
+            let changed: usize = hunks.iter().map(|h| h.old.len()).sum();
+            assert_eq!(old.len() - changed, lcs_len(&old, &new), "{old:?} {new:?}");
+        }
+    }
+
+    #[test]
+    fn diff_lines_falls_back_to_one_hunk_when_too_different() {
+        // 开头和结尾相同，中间完全不同，编辑距离超过 MAX_EDITS
+        let n = MAX_EDITS as u32;
+        let old: Vec<u32> = [0].into_iter().chain(1..=n).chain([u32::MAX]).collect();
+        let new: Vec<u32> = [0]
+            .into_iter()
+            .chain(n + 1..=2 * n)
+            .chain([u32::MAX])
+            .collect();
+        let len = n as usize + 2;
+        assert_eq!(diff_lines(&old, &new), vec![hunk(1..len - 1, 1..len - 1)]);
+
+        // 编辑距离不超过 MAX_EDITS 时仍然逐行比较
+        let half = n / 2;
+        let new: Vec<u32> = old
+            .iter()
+            .map(|&x| {
+                if x % 2 == 1 && x <= half {
+                    x + 2 * n
+                } else {
+                    x
+                }
+            })
+            .collect();
+        let hunks = diff_lines(&old, &new);
+        assert_eq!(apply(&old, &new, &hunks), new);
+        assert_eq!(hunks.len(), (half as usize).div_ceil(2));
+    }
+}
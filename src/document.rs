@@ -0,0 +1,495 @@
+use crate::buffer::{LineCol, TextBuffer, diff_edit};
+use crate::encoding::{TextEncoding, looks_utf16};
+use crate::highlight::{Highlighter, Language};
+use crate::editor_view::WrapLayout;
+use crate::folding::Folds;
+use crate::gutter::Decorations;
+use crate::history::{EditKind, EditOp, History};
+use crate::indent::Indent;
+use crate::line_ending::{self, LineEnding, LineEndingStats};
+use eframe::egui::text::{CCursor, CCursorRange};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 一个打开的文档：文本缓冲区 + 对应的磁盘路径 + 保存状态。
+#[derive(Default)]
+pub struct Document {
+    pub buffer: TextBuffer,
+    pub cursor: Option<CCursorRange>, // 光标（缓冲区内的绝对字符坐标）
+    /// 主光标之外的其余光标（多光标编辑）
+    pub extra_cursors: Vec<CCursorRange>,
+    /// 下一帧需要滚动到的位置（字符下标）
+    pub scroll_request: Option<usize>,
+    /// 下一帧需要滚动到视口顶部的行（恢复会话时使用）
+    pub scroll_top_request: Option<usize>,
+    /// 上一帧视口顶部的行
+    pub top_line: usize,
+    pub path: Option<PathBuf>,
+    /// 没有路径时显示的名字（例如拖入的只有内容、没有路径的文件）
+    pub name: Option<String>,
+    pub history: History,
+    pub highlighter: Highlighter,
+    /// 保存时使用的编码，打开时自动检测
+    pub encoding: TextEncoding,
+    /// 保存时使用的换行符；缓冲区内部总是 `\n`
+    pub line_ending: LineEnding,
+    /// 打开时文件混用了多种换行符，保存后统一为 `line_ending`
+    pub mixed_line_endings: bool,
+    /// 按 Tab 键时插入的缩进，打开时根据内容推测
+    pub indent: Indent,
+    /// 自动换行时的布局缓存
+    pub wrap_layout: WrapLayout,
+    /// 折叠区域和折叠状态
+    pub folds: Folds,
+    /// 行号栏上的标记（git 修改、诊断等），按来源登记
+    pub decorations: Decorations,
+    /// 磁盘上的文件被其他程序修改或删除了，等待用户处理
+    pub disk_change: Option<DiskChange>,
+    // 上次读取或保存时磁盘文件的修改时间和大小
+    disk_stamp: Option<DiskStamp>,
+    // 缩进是否是从文件内容推测出来的，不是时使用设置中的缩进
+    indent_detected: bool,
+    // 编码是用户用「Reopen with Encoding」选择的，重新读取时不再自动检测
+    encoding_chosen: bool,
+    // 上次保存（或打开）时撤销历史所处的状态、编码和换行符
+    saved_state: u64,
+    saved_encoding: TextEncoding,
+    saved_line_ending: LineEnding,
+}
+
+/// 磁盘上的文件发生的变化
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiskChange {
+    Modified,
+    Deleted,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct DiskStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl DiskStamp {
+    fn read(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let mut document = Self::from_bytes(&bytes)?;
+        document.set_path(path);
+        document.disk_stamp = DiskStamp::read(path).ok();
+        Ok(document)
+    }
+
+    /// 恢复没来得及保存的内容：文件还在时先打开它，再把内容作为一次编辑放进去，
+    /// 这样文档显示为未保存，撤销可以回到磁盘上的内容
+    pub fn with_unsaved_text(path: Option<&Path>, text: &str) -> io::Result<Self> {
+        let mut document = match path {
+            Some(path) if path.exists() => Self::open(path)?,
+            Some(path) => {
+                let mut document = Self::new();
+                document.set_path(path);
+                document
+            }
+            None => Self::new(),
+        };
+        document.replace_text(text);
+        Ok(document)
+    }
+
+    /// 从内存中的内容创建文档，二进制或无法解码的内容返回 `InvalidData` 错误
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if looks_binary(bytes) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "二进制文件，无法作为文本打开",
+            ));
+        }
+        let encoding = TextEncoding::detect(bytes);
+        let text = decode(bytes, encoding)?;
+        let mut document = Self {
+            encoding,
+            saved_encoding: encoding,
+            ..Self::default()
+        };
+        document.load_text(&text);
+        Ok(document)
+    }
+
+    /// 检测换行符，统一成 `\n` 后放进缓冲区
+    fn load_text(&mut self, text: &str) {
+        let stats = LineEndingStats::count(text);
+        self.line_ending = stats.dominant().unwrap_or_default();
+        self.saved_line_ending = self.line_ending;
+        self.mixed_line_endings = stats.is_mixed();
+        let detected = Indent::detect(text);
+        self.indent_detected = detected.is_some();
+        self.indent = detected.unwrap_or_default();
+        self.buffer.set_text(&line_ending::normalize(text));
+    }
+
+    /// 用指定的编码重新读取文件，放弃未保存的修改
+    pub fn reopen_with_encoding(&mut self, encoding: TextEncoding) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "文件还没有保存过"));
+        };
+        let bytes = fs::read(path)?;
+        let stamp = DiskStamp::read(path).ok();
+        let text = decode(&bytes, encoding)?;
+        // 保留文件本来的 BOM 状态
+        let encoding = TextEncoding {
+            bom: encoding.has_bom(&bytes),
+            ..encoding
+        };
+        self.load_text(&text);
+        self.cursor = None;
+        self.extra_cursors.clear();
+        self.history.clear();
+        self.saved_state = self.history.state_id();
+        self.encoding = encoding;
+        self.saved_encoding = encoding;
+        self.encoding_chosen = true;
+        self.disk_stamp = stamp;
+        self.disk_change = None;
+        Ok(())
+    }
+
+    /// 重新读取磁盘上的文件，放弃未保存的修改。作为一次编辑记录，
+    /// 撤销可以回到重新读取之前的内容
+    pub fn reload(&mut self) -> io::Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "文件还没有保存过"));
+        };
+        let bytes = fs::read(&path)?;
+        if looks_binary(&bytes) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "二进制文件，无法作为文本打开",
+            ));
+        }
+        // 用户选择的编码解码失败时（文件可能被换成了别的编码）才重新检测
+        let chosen = self
+            .encoding_chosen
+            .then(|| Some((self.encoding, self.encoding.decode(&bytes)?)))
+            .flatten();
+        let (encoding, text) = match chosen {
+            Some((encoding, text)) => {
+                let encoding = TextEncoding {
+                    bom: encoding.has_bom(&bytes),
+                    ..encoding
+                };
+                (encoding, text)
+            }
+            None => {
+                let encoding = TextEncoding::detect(&bytes);
+                self.encoding_chosen = false;
+                (encoding, decode(&bytes, encoding)?)
+            }
+        };
+        let stats = LineEndingStats::count(&text);
+        self.replace_text(&line_ending::normalize(&text));
+        let len = self.buffer.len_chars();
+        if let Some(cursor) = &mut self.cursor {
+            cursor.primary.index = cursor.primary.index.min(len);
+            cursor.secondary.index = cursor.secondary.index.min(len);
+        }
+        self.extra_cursors.clear();
+        self.encoding = encoding;
+        self.line_ending = stats.dominant().unwrap_or(self.line_ending);
+        self.mixed_line_endings = stats.is_mixed();
+        self.mark_saved();
+        self.disk_stamp = DiskStamp::read(&path).ok();
+        self.disk_change = None;
+        Ok(())
+    }
+
+    /// 和上次读取或保存时相比，磁盘上的文件有没有变化
+    pub fn check_disk(&self) -> Option<DiskChange> {
+        let path = self.path.as_ref()?;
+        match DiskStamp::read(path) {
+            Ok(stamp) if Some(stamp) != self.disk_stamp => Some(DiskChange::Modified),
+            Ok(_) => None,
+            // 从来没有保存过的新文件不算被删除
+            Err(_) if self.disk_stamp.is_some() => Some(DiskChange::Deleted),
+            Err(_) => None,
+        }
+    }
+
+    /// 保留编辑器中的内容：以后保存时不再提示磁盘上的文件比较新
+    pub fn keep_mine(&mut self) {
+        self.disk_stamp = self.path.as_deref().and_then(|p| DiskStamp::read(p).ok());
+        self.disk_change = None;
+    }
+
+    /// 没能从内容推测出缩进时使用 `indent`
+    pub fn apply_default_indent(&mut self, indent: Indent) {
+        if !self.indent_detected {
+            self.indent = indent;
+        }
+    }
+
+    /// 设置路径，同时按扩展名切换高亮语言
+    pub fn set_path(&mut self, path: &Path) {
+        self.path = Some(path.to_path_buf());
+        self.highlighter.set_language(Language::from_path(path));
+    }
+
+    /// 标签页、标题栏上显示的名字
+    pub fn title(&self) -> String {
+        self.path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .or_else(|| self.name.clone())
+            .unwrap_or_else(|| "未命名".to_owned())
+    }
+
+    /// 未命名、没有内容也没有编辑过的文档，打开文件时可以直接被替换
+    pub fn is_pristine(&self) -> bool {
+        self.path.is_none()
+            && self.name.is_none()
+            && self.buffer.is_empty()
+            && !self.history.can_undo()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.history.state_id() != self.saved_state
+            || self.encoding != self.saved_encoding
+            || self.line_ending != self.saved_line_ending
+    }
+
+    /// 记录编辑器视图已经写入缓冲区的修改
+    pub fn record_edit(&mut self, op: EditOp, cursor_before: Option<CCursorRange>) {
+        self.history.record(op, cursor_before, self.cursor);
+    }
+
+    /// 记录编辑器视图在一帧内写入缓冲区的所有修改；多处修改（多个光标）作为一个撤销步骤，
+    /// 连续输入时和单个光标一样合并
+    pub fn record_edits(&mut self, ops: Vec<EditOp>, cursor_before: Option<CCursorRange>) {
+        self.history.record_all(ops, cursor_before, self.cursor);
+    }
+
+    /// 依次应用多处修改，并作为一个撤销步骤记录。
+    /// `ops` 中的 `start` 以应用前一项修改后的缓冲区为准，`removed` 会按实际内容重新填写。
+    pub fn apply_edits(&mut self, kind: EditKind, ops: Vec<EditOp>) {
+        let cursor_before = self.cursor;
+        let mut applied = Vec::with_capacity(ops.len());
+        for mut op in ops {
+            let end = op.start + op.removed.chars().count();
+            op.removed = self.buffer.slice_to_string(op.start..end);
+            self.buffer.replace(op.start..end, &op.inserted);
+            applied.push(op);
+        }
+        self.history
+            .record_group(kind, applied, cursor_before, self.cursor);
+    }
+
+    /// 用 `text` 替换全部内容，作为一个撤销步骤记录
+    pub fn replace_text(&mut self, text: &str) {
+        let current = self.buffer.to_string();
+        if let Some((range, inserted)) = diff_edit(&current, text) {
+            let op = EditOp {
+                start: range.start,
+                removed: self.buffer.slice_to_string(range),
+                inserted,
+            };
+            self.apply_edits(EditKind::Other, vec![op]);
+        }
+    }
+
+    /// 把光标移到指定行列并滚动过去
+    pub fn go_to(&mut self, position: LineCol) {
+        let char_idx = self.buffer.line_col_to_char(position);
+        self.cursor = Some(CCursorRange::one(CCursor::new(char_idx)));
+        self.extra_cursors.clear();
+        self.scroll_request = Some(char_idx);
+        self.history.break_coalescing();
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(cursor) = self.history.undo(&mut self.buffer) {
+            self.cursor = cursor;
+            self.extra_cursors.clear();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(cursor) = self.history.redo(&mut self.buffer) {
+            self.cursor = cursor;
+            self.extra_cursors.clear();
+        }
+    }
+
+    /// 保存到当前路径；没有路径时返回 `None`，调用方应改走「另存为」。
+    pub fn save(&mut self) -> Option<io::Result<()>> {
+        let path = self.path.clone()?;
+        Some(self.save_as(&path))
+    }
+
+    pub fn save_as(&mut self, path: &Path) -> io::Result<()> {
+        let text = self.buffer.to_string();
+        let bytes = self
+            .encoding
+            .encode(&line_ending::convert(&text, self.line_ending))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        write_atomic(path, &bytes)?;
+        self.set_path(path);
+        self.mark_saved();
+        self.mixed_line_endings = false;
+        self.disk_stamp = DiskStamp::read(path).ok();
+        self.disk_change = None;
+        Ok(())
+    }
+
+    fn mark_saved(&mut self) {
+        self.saved_state = self.history.state_id();
+        self.saved_encoding = self.encoding;
+        self.saved_line_ending = self.line_ending;
+    }
+}
+
+fn decode(bytes: &[u8], encoding: TextEncoding) -> io::Result<String> {
+    encoding.decode(bytes).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("不是有效的 {} 文本", encoding.encoding.name()),
+        )
+    })
+}
+
+/// 含有 NUL 字节的内容视为二进制（UTF-16 文本除外）
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(8 * 1024).any(|&b| b == 0) && !looks_utf16(bytes)
+}
+
+/// 先写入同目录下的临时文件再重命名覆盖目标文件，
+/// 保证写到一半崩溃或磁盘写满时原文件不会被截断。
+/// `path` 是符号链接时写入链接指向的文件，链接本身保留。
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    // 文件还不存在时 canonicalize 会失败，直接用原路径
+    let path = &fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "无效的文件名"))?;
+    let tmp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    // 保留原文件的权限（例如可执行位）
+    let permissions = fs::metadata(path)
+        .ok()
+        .map(|metadata| metadata.permissions());
+
+    let result = (|| {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // 临时文件一创建就带着原文件的权限，写入期间其他用户也读不到私有文件
+        #[cfg(unix)]
+        if let Some(permissions) = &permissions {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(permissions.mode() & 0o7777);
+        }
+        let mut file = options.open(&tmp_path)?;
+        // 创建时的权限会被 umask 去掉一些位，写入之前补上
+        #[cfg(unix)]
+        if let Some(permissions) = &permissions {
+            file.set_permissions(permissions.clone())?;
+        }
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        // Windows 上只读属性要等写完再设置
+        #[cfg(not(unix))]
+        if let Some(permissions) = permissions {
+            fs::set_permissions(&tmp_path, permissions)?;
+        }
+        fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_keeps_the_chosen_encoding() {
+        use encoding_rs::{GBK, UTF_8};
+        let dir = std::env::temp_dir().join(format!("editor-rs-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.txt");
+        fs::write(&path, "hello").unwrap();
+        let mut document = Document::open(&path).unwrap();
+        assert_eq!(document.encoding.encoding, UTF_8);
+        document
+            .reopen_with_encoding(TextEncoding::new(GBK))
+            .unwrap();
+
+        // 检测会得到 UTF-8，但用户选择了 GBK
+        let (gbk, _, _) = GBK.encode("你好");
+        fs::write(&path, &gbk).unwrap();
+        document.reload().unwrap();
+        assert_eq!(document.encoding.encoding, GBK);
+        assert_eq!(document.buffer.to_string(), "你好");
+        assert!(!document.is_dirty());
+
+        // 用选择的编码无法解码时重新检测
+        fs::write(&path, "\u{FEFF}你好").unwrap();
+        document.reload().unwrap();
+        assert_eq!(document.encoding.encoding, UTF_8);
+        assert_eq!(document.buffer.to_string(), "你好");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_keeps_symlinks_and_permissions() {
+        use std::os::unix::fs::{PermissionsExt, symlink};
+        let dir = std::env::temp_dir().join(format!("editor-rs-write-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("target.sh");
+        let link = dir.join("link.sh");
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o750)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"new").unwrap();
+        assert!(
+            fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o750);
+
+        // 新文件
+        let created = dir.join("created.txt");
+        write_atomic(&created, b"text").unwrap();
+        assert_eq!(fs::read_to_string(&created).unwrap(), "text");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
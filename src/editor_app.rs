@@ -1,64 +1,184 @@
+use crate::buffer::{LineCol, TextBuffer};
+use crate::cli::CliArgs;
+use crate::command::Command;
+use crate::close_dialog::{CloseChoice, PendingClose, show_close_dialog};
+use crate::document::{DiskChange, Document};
+use crate::editor_view::{self, TextView, WrapLayout};
+use crate::encoding::EncodingAction;
+use crate::explorer::{Explorer, ExplorerAction};
+use crate::find::FindBar;
+use crate::find_in_files::FindInFiles;
+use crate::folding::Folds;
+use crate::file_drop::{DropPreview, MAX_FOLDER_FILES, expand_path, preview_files_being_dropped};
+use crate::file_watch::{
+    Comparison, ComparisonAction, DiskAction, FileWatcher, OverwriteChoice, comparison_window,
+    disk_change_banner, overwrite_dialog,
+};
+use crate::git_diff::GitMarkers;
+use crate::gutter::Gutter;
+use crate::highlight::Language;
+use crate::keymap::{KeyContext, Keymap};
+use crate::multi_cursor;
+use crate::history::EditKind;
+use crate::indent::convert_indentation;
+use crate::palette::{CommandPalette, PaletteAction, PaletteMode};
+use crate::panes::{self, Direction, SplitDir};
+use crate::process_lock::ProcessLock;
+use crate::recovery::{RecoveredFile, Recovery, RecoveryAction, recovery_dialog};
+use crate::session::{Session, WindowGeometry};
+use crate::settings::{AutoSave, BUILTIN_FONT, EGUI_FONT, SettingsStore, Theme, settings_window};
+use crate::status_bar::{StatusAction, StatusBar};
+use crate::instance::{InstanceServer, PendingWait, Waiter};
 use crate::menu::build_menu_bar;
+use crate::minimap::Minimap;
+use crate::tabs::{TabAction, Tabs};
 use crate::title_bar::TitleBarPanel;
 use eframe::egui;
-use eframe::egui::{FontDefinitions, FontFamily, ScrollArea, ViewportCommand};
+use eframe::egui::text::CCursorRange;
+use eframe::egui::{FontDefinitions, FontFamily, ViewportCommand};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// 文件对话框中使用的过滤器
+const TEXT_FILE_EXTENSIONS: &[&str] = &["txt", "rs", "md", "toml", "json", "sh"];
 
-#[derive(Default)]
 pub struct EditorApp {
-    pub show_confirmation_dialog: bool,
+    pub pending_close: Option<PendingClose>, // 等待确认保存的关闭请求
     pub allowed_to_close: bool,
-    pub dropped_files: Vec<egui::DroppedFile>,
-    pub picked_path: Option<String>,
-    pub file_content: String, // 文件内容
+    pub drop_preview: DropPreview, // 拖拽悬停时的预览
+    pub tabs: Tabs, // 打开的文档
+    pub find_bar: FindBar, // 查找 / 替换栏
+    pub find_in_files: FindInFiles, // 在文件夹中查找
+    pub status_bar: StatusBar, // 底部状态栏
+    pub explorer: Explorer, // 左侧的文件资源管理器
+    pub error_message: Option<String>, // 打开、保存失败时弹窗提示
     pub title_bar: TitleBarPanel,
+    pub settings: SettingsStore, // 用户设置，文件被修改时自动重新读取
+    pub settings_open: bool, // 是否显示设置页面
+    pub keymap: Keymap, // 快捷键，文件被修改时自动重新读取
+    pub about_open: bool, // 是否显示 About 窗口
+    pub palette: CommandPalette, // 命令面板：命令、打开文件、跳转到行
+    applied_font: Option<String>, // 当前已经加载的字体，字体没变时不重新加载
+    session_lock: Option<ProcessLock>, // 拥有会话时在退出时保存（--new-window 或者后打开的窗口不保存）
+    window: Option<WindowGeometry>, // 当前的窗口位置和大小，退出时写进会话
+    recovery: Recovery, // 定期把未保存的内容写到恢复目录，崩溃后可以找回
+    recovered: Vec<RecoveredFile>, // 上次崩溃留下、等待用户决定的快照
+    autosave_timers: HashMap<usize, (u64, Option<Instant>)>, // 延迟自动保存：文档版本和开始计时的时间
+    was_focused: bool, // 上一帧窗口是否有焦点
+    last_active: Option<usize>, // 上一帧的活动标签
+    file_watcher: FileWatcher, // 发现打开的文件被其他程序修改
+    git_markers: GitMarkers, // 行号栏上和 git HEAD 比较的修改标记
+    comparison: Option<Comparison>, // 磁盘上的文件和编辑器内容的对比窗口
+    pending_overwrite: Option<usize>, // 保存时磁盘上的文件更新，等待确认是否覆盖
+    instance: Option<InstanceServer>, // 接收后启动的进程交过来的文件
+    waits: Vec<PendingWait>, // --wait：等这些文档关闭后通知对方
 }
 
 impl eframe::App for EditorApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.handle_shortcuts(ctx);
+        self.handle_instance_requests(ctx);
+        self.handle_settings(ctx);
+        self.track_window_geometry(ctx);
+        // 要在 autosave 更新 was_focused 之前检查
+        self.check_disk_changes(ctx);
+        self.autosave(ctx);
+        self.recovery.update(ctx, &self.tabs);
+        self.git_markers.update(ctx, &mut self.tabs);
         // 构建title bar
         self.title_bar.title_bar(ctx);
         // 构建 menu bar
         build_menu_bar(self, ctx);
+        // 查找栏停靠在中央内容区上方
+        self.build_find_bar(ctx);
+        // 底部状态栏
+        self.build_status_bar(ctx);
+        // 左侧资源管理器
+        for action in self.explorer.side_panel(ctx) {
+            self.handle_explorer_action(action);
+        }
+        // 在文件夹中查找的结果面板
+        if let Some(location) = self.find_in_files.side_panel(ctx) {
+            self.open_at(&location.path, location.line, location.col);
+        }
         // 构建中央内容区
         self.build_central_panel(ctx);
         // 预览拖拽的文件
-        preview_files_being_dropped(ctx);
+        preview_files_being_dropped(ctx, &mut self.drop_preview);
 
         if ctx.input(|i| i.viewport().close_requested()) {
             if self.allowed_to_close {
                 // do nothing - we will close
             } else {
-                ctx.send_viewport_cmd(ViewportCommand::CancelClose);
-                self.show_confirmation_dialog = true;
-            }
-        }
-        if self.show_confirmation_dialog {
-            egui::Window::new("Do you want to quit?")
-                .collapsible(false)
-                .current_pos(ctx.screen_rect().center())
-                .resizable(false)
-                .show(ctx, |ui| {
-                    ui.horizontal(|ui| {
-                        if ui.button("No").clicked() {
-                            self.show_confirmation_dialog = false;
-                            self.allowed_to_close = false;
+                // 没有未保存的修改时直接退出，否则先询问
+                let ids = self.tabs.ids();
+                let dirty = ids.iter().any(|&id| self.tabs.get(id).is_some_and(|doc| doc.is_dirty()));
+                if dirty && self.hot_exit_enabled() {
+                    // 热退出：未保存的内容写进会话，下次启动时恢复
+                    match self.save_session() {
+                        Ok(()) => self.allowed_to_close = true,
+                        Err(err) => {
+                            self.error_message = Some(format!("无法保存会话：{}", err));
+                            ctx.send_viewport_cmd(ViewportCommand::CancelClose);
                         }
-
-                        if ui.button("Yes").clicked() {
-                            self.show_confirmation_dialog = false;
-                            self.allowed_to_close = true;
-                            ui.ctx().send_viewport_cmd(ViewportCommand::Close);
+                    }
+                } else if dirty {
+                    ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+                    self.pending_close = Some(PendingClose { ids, quit: true });
+                } else {
+                    self.allowed_to_close = true;
+                }
+            }
+        }
+        self.show_close_dialog(ctx);
+        self.show_error_dialog(ctx);
+        self.show_overwrite_dialog(ctx);
+        if let Some(comparison) = &mut self.comparison {
+            match comparison_window(ctx, comparison) {
+                Some(ComparisonAction::Close) => self.comparison = None,
+                Some(ComparisonAction::Merge) => {
+                    if let Some(document) = self.tabs.get_mut(comparison.doc) {
+                        if let Err(err) = comparison.merge(document) {
+                            self.error_message = Some(format!("无法合并：{}", err));
                         }
-                    });
-                });
+                    }
+                    self.comparison = None;
+                }
+                None => {}
+            }
+        }
+        if let Some(action) = recovery_dialog(ctx, &self.recovered) {
+            self.handle_recovery_action(action);
         }
-        // Collect dropped files:
-        ctx.input(|i| {
-            if !i.raw.dropped_files.is_empty() {
-                self.dropped_files.clone_from(&i.raw.dropped_files);
+        if settings_window(ctx, &mut self.settings_open, &mut self.settings) {
+            self.apply_settings(ctx);
+            self.save_settings();
+        }
+        about_window(ctx, &mut self.about_open);
+        if let Some(action) = self.palette.ui(ctx, &self.keymap, &self.tabs) {
+            self.handle_palette_action(ctx, action);
+        }
+        // Open dropped files:
+        let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
+        if !dropped_files.is_empty() {
+            self.open_dropped_files(&dropped_files);
+        }
+        // 菜单、查找栏、重新加载等在编辑器视图之外的修改，其他窗格中的光标也跟着移动
+        self.tabs.sync_edits();
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if self.session_lock.is_some() {
+            if let Err(err) = self.save_session() {
+                log::warn!("无法保存会话：{}", err);
             }
-        });
+        }
+        // 正常退出：未保存的内容要么已经询问过，要么在会话里
+        self.recovery.finish();
     }
 
     fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
@@ -93,80 +213,1228 @@ fn set_chinese_font(ctx: &egui::Context) {
     ctx.set_fonts(fonts);
 }
 
-impl EditorApp {
-    
-    pub fn default() -> Self {
+/// 按设置加载字体：内置宋体、egui 自带字体，或者字体文件
+fn set_font(ctx: &egui::Context, family: &str) -> Result<(), String> {
+    match family {
+        BUILTIN_FONT => set_chinese_font(ctx),
+        EGUI_FONT => ctx.set_fonts(FontDefinitions::default()),
+        path => {
+            let bytes = fs::read(path).map_err(|err| format!("无法加载字体：{}\n{}", path, err))?;
+            // egui 遇到无法解析的字体会直接 panic，先检查文件头
+            if !looks_like_font(&bytes) {
+                return Err(format!("不是有效的字体文件：{}", path));
+            }
+            let mut fonts = FontDefinitions::default();
+            fonts
+                .font_data
+                .insert("user_font".to_owned(), Arc::new(egui::FontData::from_owned(bytes)));
+            for family in [FontFamily::Proportional, FontFamily::Monospace] {
+                if let Some(list) = fonts.families.get_mut(&family) {
+                    list.insert(0, "user_font".to_owned());
+                }
+            }
+            ctx.set_fonts(fonts);
+        }
+    }
+    Ok(())
+}
+
+/// TrueType / OpenType / 字体集合的文件头
+fn looks_like_font(bytes: &[u8]) -> bool {
+    matches!(
+        bytes.get(..4),
+        Some([0, 1, 0, 0] | b"true" | b"OTTO" | b"ttcf")
+    )
+}
+
+impl Default for EditorApp {
+    fn default() -> Self {
         Self {
-            show_confirmation_dialog: false,
+            pending_close: None,
             allowed_to_close: false,
-            dropped_files: Vec::new(),
-            picked_path: None,
-            file_content: String::new(), // 初始化文件内容为空
+            drop_preview: DropPreview::default(),
+            tabs: Tabs::default(), // 初始化为一个空文档
+            find_bar: FindBar::default(),
+            find_in_files: FindInFiles::default(),
+            status_bar: StatusBar::default(),
+            explorer: Explorer::default(),
+            error_message: None,
             title_bar: TitleBarPanel::new("Editor-rs"),
+            settings: SettingsStore::default(),
+            settings_open: false,
+            keymap: Keymap::default(),
+            about_open: false,
+            palette: CommandPalette::default(),
+            applied_font: None,
+            session_lock: None,
+            window: None,
+            recovery: Recovery::default(),
+            recovered: Vec::new(),
+            autosave_timers: HashMap::new(),
+            was_focused: true,
+            last_active: None,
+            file_watcher: FileWatcher::default(),
+            git_markers: GitMarkers::default(),
+            comparison: None,
+            pending_overwrite: None,
+            instance: None,
+            waits: Vec::new(),
         }
     }
-    
+}
+
+impl EditorApp {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         set_chinese_font(&cc.egui_ctx);
-        Self::default()
+        Self {
+            keymap: Keymap::load(),
+            ..Self::default()
+        }
+    }
+
+    /// 按命令行参数和设置启动：先恢复上次的会话，再打开参数中的文件和文件夹，
+    /// 并接收之后启动的进程交过来的文件
+    pub fn with_args(
+        cc: &eframe::CreationContext<'_>,
+        args: CliArgs,
+        settings: SettingsStore,
+        session: Option<Session>,
+        session_lock: Option<ProcessLock>,
+    ) -> Self {
+        let keymap = Keymap::load();
+        let error_message = match (&settings.load_error, &keymap.load_error) {
+            (Some(a), Some(b)) => Some(format!("{}\n\n{}", a, b)),
+            (a, b) => a.clone().or_else(|| b.clone()),
+        };
+        let mut app = Self {
+            error_message,
+            settings,
+            keymap,
+            session_lock,
+            ..Self::default()
+        };
+        app.settings.watch(&cc.egui_ctx);
+        app.keymap.watch(&cc.egui_ctx);
+        app.apply_settings(&cc.egui_ctx);
+        if let Some(session) = session {
+            app.restore_session(&cc.egui_ctx, session);
+        }
+        app.recovery = Recovery::start();
+        app.recovered = app.recovery.find_leftovers();
+        match InstanceServer::start(&cc.egui_ctx) {
+            Ok(server) => app.instance = Some(server),
+            Err(err) => log::warn!("无法接收其他进程的打开请求：{}", err),
+        }
+        let wait = args.wait;
+        let ids = app.open_args(&cc.egui_ctx, args);
+        // 没有打开任何文档（只有文件夹或者都打开失败）时不等待，否则第一帧就会退出
+        if wait && !ids.is_empty() {
+            app.waits.push(PendingWait {
+                ids,
+                waiter: Waiter::Exit,
+            });
+        }
+        app
+    }
+
+    /// 打开命令行参数中的文件夹、文件和标准输入，返回打开的文档
+    pub fn open_args(&mut self, ctx: &egui::Context, args: CliArgs) -> Vec<usize> {
+        for folder in args.folders {
+            self.open_folder(ctx, folder);
+        }
+        let mut ids = Vec::new();
+        for target in args.files {
+            if !target.path.exists() {
+                // 不存在的文件先作为空文档打开，保存时创建
+                let mut document = Document::new();
+                document.set_path(&target.path);
+                ids.push(self.open_document(document));
+                continue;
+            }
+            if let Err(err) = self.try_open_path(&target.path) {
+                self.error_message = Some(format!("读取失败：{}\n{}", target.path.display(), err));
+                continue;
+            }
+            if let Some(document) = self.tabs.active_mut() {
+                if let Some(position) = target.position {
+                    document.go_to(position);
+                }
+            }
+            ids.extend(self.tabs.active_id());
+        }
+        if let Some(bytes) = args.stdin {
+            match Document::from_bytes(&bytes) {
+                Ok(mut document) => {
+                    document.name = Some("stdin".to_owned());
+                    ids.push(self.open_document(document));
+                }
+                Err(err) => self.error_message = Some(format!("无法读取标准输入：{}", err)),
+            }
+        }
+        ids
+    }
+
+    /// 恢复或丢弃上次崩溃留下的快照
+    fn handle_recovery_action(&mut self, action: RecoveryAction) {
+        let (indices, recover): (Vec<usize>, bool) = match action {
+            RecoveryAction::Recover(index) => (vec![index], true),
+            RecoveryAction::Discard(index) => (vec![index], false),
+            RecoveryAction::RecoverAll => ((0..self.recovered.len()).collect(), true),
+            RecoveryAction::DiscardAll => ((0..self.recovered.len()).collect(), false),
+        };
+        // 从后往前取，下标不受影响
+        for index in indices.into_iter().rev() {
+            let file = self.recovered.remove(index);
+            if recover {
+                self.recover_file(&file);
+            }
+            file.discard();
+        }
+    }
+
+    /// 把快照的内容放回编辑器：文件已经打开时替换它的内容，否则新开一个标签
+    fn recover_file(&mut self, file: &RecoveredFile) {
+        let open = file.path.as_deref().and_then(|path| self.tabs.find_by_path(path));
+        if let Some(id) = open {
+            if let Some(document) = self.tabs.get_mut(id) {
+                document.replace_text(&file.text);
+            }
+            self.tabs.set_active(id);
+            return;
+        }
+        match Document::with_unsaved_text(file.path.as_deref(), &file.text) {
+            Ok(mut document) => {
+                document.name = file.name.clone();
+                self.open_document(document);
+            }
+            Err(err) => self.error_message = Some(format!("恢复失败：{}\n{}", file.title(), err)),
+        }
+    }
+
+    /// 处理被其他程序修改的文件：没有未保存修改的直接重新读取，否则显示提示条
+    fn check_disk_changes(&mut self, ctx: &egui::Context) {
+        let paths: Vec<PathBuf> = self
+            .tabs
+            .iter()
+            .filter_map(|(_, document)| document.path.clone())
+            .collect();
+        self.file_watcher.sync(ctx, paths.iter().map(PathBuf::as_path));
+        let changed = self.file_watcher.poll();
+        // 切回窗口时全部检查一遍，监听可能漏掉变化（例如网络磁盘）
+        let focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
+        let regained_focus = focused && !self.was_focused;
+        if regained_focus {
+            // 可能在外面提交或切换了分支
+            self.git_markers.refresh();
+        }
+        if changed.is_empty() && !regained_focus {
+            return;
+        }
+        let mut errors = Vec::new();
+        for (_, document) in self.tabs.iter_mut() {
+            let Some(path) = document.path.clone() else {
+                continue;
+            };
+            if !regained_focus && !changed.contains(&path) {
+                continue;
+            }
+            match document.check_disk() {
+                None => {}
+                Some(DiskChange::Modified) if !document.is_dirty() => {
+                    if let Err(err) = document.reload() {
+                        errors.push(format!("重新读取失败：{}\n{}", path.display(), err));
+                    }
+                }
+                Some(change) => document.disk_change = Some(change),
+            }
+        }
+        if !errors.is_empty() {
+            self.error_message = Some(errors.join("\n"));
+        }
+    }
+
+    fn show_overwrite_dialog(&mut self, ctx: &egui::Context) {
+        let Some(id) = self.pending_overwrite else {
+            return;
+        };
+        let Some(document) = self.tabs.get(id) else {
+            self.pending_overwrite = None;
+            return;
+        };
+        match overwrite_dialog(ctx, &document.title()) {
+            Some(OverwriteChoice::Overwrite) => {
+                self.pending_overwrite = None;
+                if let Some(document) = self.tabs.get_mut(id) {
+                    document.keep_mine();
+                }
+                self.save_document(id);
+            }
+            Some(OverwriteChoice::Compare) => match Comparison::with_disk(id, document) {
+                Ok(comparison) => self.comparison = Some(comparison),
+                Err(err) => self.error_message = Some(format!("无法比较：{}", err)),
+            },
+            Some(OverwriteChoice::Cancel) => self.pending_overwrite = None,
+            None => {}
+        }
+    }
+
+    /// 按设置自动保存：停止输入一段时间后，或者窗口失去焦点、切换标签时。
+    /// 只保存已经有路径的文档
+    fn autosave(&mut self, ctx: &egui::Context) {
+        let focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
+        let lost_focus = self.was_focused && !focused;
+        self.was_focused = focused;
+        let active = self.tabs.active_id();
+        let previous = std::mem::replace(&mut self.last_active, active);
+
+        let settings = &self.settings.settings;
+        let mut due = Vec::new();
+        match settings.autosave {
+            AutoSave::Off => {}
+            AutoSave::AfterDelay => {
+                let delay = Duration::from_millis(settings.autosave_delay_ms);
+                let now = Instant::now();
+                self.autosave_timers.retain(|id, _| self.tabs.get(*id).is_some());
+                for (id, document) in self.tabs.iter() {
+                    if !document.is_dirty() || document.path.is_none() {
+                        self.autosave_timers.remove(&id);
+                        continue;
+                    }
+                    let version = document.buffer.version();
+                    match self.autosave_timers.get(&id) {
+                        // 这个版本已经保存过（或者保存失败了），等下次修改
+                        Some(&(saved, None)) if saved == version => {}
+                        Some(&(saved, Some(since))) if saved == version => {
+                            let elapsed = now - since;
+                            if elapsed >= delay {
+                                due.push(id);
+                            } else {
+                                ctx.request_repaint_after(delay - elapsed);
+                            }
+                        }
+                        _ => {
+                            self.autosave_timers.insert(id, (version, Some(now)));
+                            ctx.request_repaint_after(delay);
+                        }
+                    }
+                }
+            }
+            AutoSave::OnFocusChange => {
+                if lost_focus {
+                    due = self.tabs.ids();
+                } else if previous != active {
+                    due.extend(previous);
+                }
+            }
+        }
+        for id in due {
+            let Some(document) = self.tabs.get(id) else {
+                continue;
+            };
+            if !document.is_dirty() || document.path.is_none() {
+                continue;
+            }
+            let version = document.buffer.version();
+            self.save_document(id);
+            self.autosave_timers.insert(id, (version, None));
+        }
+    }
+
+    /// 重新打开上次的标签、工作区和侧边栏
+    fn restore_session(&mut self, ctx: &egui::Context, session: Session) {
+        if let Some(dir) = session.workspace.filter(|dir| dir.is_dir()) {
+            self.open_folder(ctx, dir);
+        }
+        self.explorer.open = session.explorer_open;
+        self.find_in_files.open = session.find_in_files_open;
+        let recent = session
+            .recent_commands
+            .iter()
+            .filter_map(|id| Command::from_id(id))
+            .collect();
+        self.palette.set_recent_commands(recent);
+        let mut active = None;
+        for (index, tab) in session.tabs.iter().enumerate() {
+            let Some(document) = tab.restore() else {
+                continue;
+            };
+            let id = self.open_document(document);
+            if session.active == Some(index) {
+                active = Some(id);
+            }
+        }
+        if let Some(id) = active {
+            self.tabs.set_active(id);
+        }
+    }
+
+    /// 把打开的标签、侧边栏和窗口位置写进会话文件
+    pub fn save_session(&self) -> io::Result<()> {
+        let session = Session {
+            window: self.window,
+            explorer_open: self.explorer.open,
+            workspace: self.explorer.root().map(Path::to_path_buf),
+            find_in_files_open: self.find_in_files.open,
+            recent_commands: self
+                .palette
+                .recent_commands()
+                .iter()
+                .map(|command| command.id().to_owned())
+                .collect(),
+            ..Session::default()
+        };
+        let active = self.tabs.active_id();
+        let documents = self.tabs.iter().map(|(id, doc)| (Some(id) == active, doc));
+        session.save(documents, self.hot_exit_enabled())
+    }
+
+    /// 只有下次启动会恢复会话时才能热退出，否则未保存的内容就丢了
+    fn hot_exit_enabled(&self) -> bool {
+        let settings = &self.settings.settings;
+        self.session_lock.is_some() && settings.restore_session && settings.hot_exit
+    }
+
+    /// 记下窗口的位置和大小，退出时已经拿不到了
+    fn track_window_geometry(&mut self, ctx: &egui::Context) {
+        let geometry = ctx.input(|i| {
+            let viewport = i.viewport();
+            let inner = viewport.inner_rect?;
+            let outer = viewport.outer_rect;
+            Some(WindowGeometry {
+                x: outer.map(|r| r.min.x),
+                y: outer.map(|r| r.min.y),
+                width: inner.width(),
+                height: inner.height(),
+                maximized: viewport.maximized.unwrap_or(false),
+            })
+        });
+        if let Some(geometry) = geometry {
+            // 最大化时保留原来的大小，取消最大化后回到这个大小
+            match &mut self.window {
+                Some(window) if geometry.maximized => window.maximized = true,
+                window => *window = Some(geometry),
+            }
+        }
+    }
+
+    /// 处理其他进程的打开请求，并通知等待的文档已经关闭
+    fn handle_instance_requests(&mut self, ctx: &egui::Context) {
+        let requests = self.instance.as_ref().map(InstanceServer::poll).unwrap_or_default();
+        for request in requests {
+            let ids = self.open_args(ctx, request.args);
+            if let Some(stream) = request.wait {
+                if ids.is_empty() {
+                    // 没有要等待关闭的文档，让对方马上返回
+                    Waiter::Remote(stream).notify(ctx);
+                } else {
+                    self.waits.push(PendingWait {
+                        ids,
+                        waiter: Waiter::Remote(stream),
+                    });
+                }
+            }
+            ctx.send_viewport_cmd(ViewportCommand::Focus);
+        }
+
+        let (done, pending) = std::mem::take(&mut self.waits)
+            .into_iter()
+            .partition(|wait| wait.ids.iter().all(|&id| self.tabs.get(id).is_none()));
+        self.waits = pending;
+        for wait in done {
+            wait.waiter.notify(ctx);
+        }
+    }
+
+    /// 设置文件被外部修改时重新应用；用菜单栏的开关切换主题时写回设置
+    fn handle_settings(&mut self, ctx: &egui::Context) {
+        match self.settings.poll() {
+            Ok(true) => self.apply_settings(ctx),
+            Ok(false) => {}
+            Err(err) => self.error_message = Some(err),
+        }
+        let theme = Theme::from_preference(ctx.options(|o| o.theme_preference));
+        if theme != self.settings.settings.theme {
+            self.settings.settings.theme = theme;
+            self.save_settings();
+        }
+    }
+
+    /// 把当前设置应用到界面和已经打开的文档
+    pub fn apply_settings(&mut self, ctx: &egui::Context) {
+        let settings = &self.settings.settings;
+        if self.applied_font.as_deref() != Some(settings.font_family.as_str()) {
+            match set_font(ctx, &settings.font_family) {
+                Ok(()) => self.applied_font = Some(settings.font_family.clone()),
+                Err(err) => {
+                    // 字体加载失败时至少保证能显示中文
+                    if self.applied_font.is_none() {
+                        set_chinese_font(ctx);
+                        self.applied_font = Some(BUILTIN_FONT.to_owned());
+                    }
+                    self.error_message = Some(err);
+                }
+            }
+            // 字体变了，字宽也变了
+            for (_, document) in self.tabs.iter_mut() {
+                document.wrap_layout = WrapLayout::default();
+            }
+        }
+        let font_size = settings.font_size.clamp(6.0, 72.0);
+        ctx.all_styles_mut(|style| {
+            if let Some(font) = style.text_styles.get_mut(&egui::TextStyle::Monospace) {
+                font.size = font_size;
+            }
+        });
+        ctx.set_theme(settings.theme.preference());
+        self.title_bar.set_height(settings.title_bar_height.clamp(16.0, 96.0));
+        let indent = settings.default_indent();
+        for (_, document) in self.tabs.iter_mut() {
+            document.apply_default_indent(indent);
+        }
+    }
+
+    pub fn save_settings(&mut self) {
+        if let Err(err) = self.settings.save() {
+            self.error_message = Some(err);
+        }
     }
 
     pub fn build_central_panel(&mut self, ctx: &egui::Context) {
         let _panel_frame = custom_central_panel_frame(ctx);
         egui::CentralPanel::default().frame(_panel_frame).show(ctx, |ui| {
-            ui.label("Drag-and-drop files onto the window!");
-            if let Some(picked_path) = &self.picked_path {
-                ui.horizontal(|ui| {
-                    ui.label("Picked file:");
-                    ui.monospace(picked_path);
-                });
+            // 每个窗格有自己的标签栏和编辑视图
+            let rect = ui.available_rect_before_wrap();
+            let panes = self.tabs.layout(ui, rect);
+            ui.allocate_rect(rect, egui::Sense::hover());
+            let split = panes.len() > 1;
+            for (pane, rect) in panes {
+                let mut ui = ui.new_child(egui::UiBuilder::new().max_rect(rect).id_salt(("pane", pane)));
+                ui.shrink_clip_rect(rect);
+                self.pane_ui(&mut ui, pane);
+                if split && pane == self.tabs.focused_pane() {
+                    panes::paint_focus(&ui, rect);
+                }
             }
-            ScrollArea::vertical()
-                .auto_shrink([false; 2])
-                .show(ui, |ui| {
-                    ui.add(
-                        egui::TextEdit::multiline(&mut self.file_content)
-                            .font(egui::TextStyle::Monospace) // 等宽字体
-                            .desired_rows(20)
-                            .lock_focus(true)
-                            .desired_width(f32::INFINITY),
-                    );
-                });
+        });
+    }
 
-            // Show dropped files (if any):
-            if !self.dropped_files.is_empty() {
-                ui.group(|ui| {
-                    ui.label("Dropped files:");
-
-                    for file in &self.dropped_files {
-                        let mut info = if let Some(path) = &file.path {
-                            path.display().to_string()
-                        } else if !file.name.is_empty() {
-                            file.name.clone()
-                        } else {
-                            "???".to_owned()
-                        };
-
-                        let mut additional_info = vec![];
-                        if !file.mime.is_empty() {
-                            additional_info.push(format!("type: {}", file.mime));
-                        }
-                        if let Some(bytes) = &file.bytes {
-                            additional_info.push(format!("{} bytes", bytes.len()));
-                        }
-                        if !additional_info.is_empty() {
-                            info += &format!(" ({})", additional_info.join(", "));
-                        }
+    fn pane_ui(&mut self, ui: &mut egui::Ui, pane: usize) {
+        let actions = self.tabs.tab_bar(ui, pane);
+        for action in actions {
+            // 点击其他窗格的标签栏时焦点先移过去
+            self.tabs.focus_pane(pane);
+            self.handle_tab_action(action);
+        }
+        ui.separator();
 
-                        ui.label(info);
+        let Some(id) = self.tabs.pane_active(pane) else {
+            ui.centered_and_justified(|ui| {
+                ui.label("无标签页打开，拖入文件或使用 File → Open…");
+            });
+            return;
+        };
+        let document = self.tabs.get_mut(id).expect("active tab exists");
+        if let Some(change) = document.disk_change {
+            match disk_change_banner(ui, change) {
+                Some(DiskAction::Reload) => {
+                    if let Err(err) = document.reload() {
+                        self.error_message = Some(format!("重新读取失败：{}", err));
                     }
+                }
+                Some(DiskAction::KeepMine) => document.keep_mine(),
+                Some(DiskAction::Compare) => match Comparison::with_disk(id, document) {
+                    Ok(comparison) => self.comparison = Some(comparison),
+                    Err(err) => self.error_message = Some(format!("无法比较：{}", err)),
+                },
+                None => {}
+            }
+        }
+        // 查找结果只属于活动文档
+        let (matches, current) = if Some(id) == self.tabs.active_id() {
+            (self.find_bar.matches(), self.find_bar.current_match())
+        } else {
+            (&[][..], None)
+        };
+        let word_wrap = self.settings.settings.word_wrap;
+        let show_minimap = self.settings.settings.minimap;
+        let line_numbers = self.settings.settings.line_numbers;
+        let editor_id = editor_id(pane, id);
+        let pane_focused = self.tabs.focused_pane() == pane;
+        // 只渲染可见行，大文件也能流畅编辑；每个窗格的每个标签使用独立的 id，保留各自的滚动位置
+        let focused = self.tabs.show_in_pane(pane, id, |document| {
+            let cursor_before = document.cursor;
+            document.folds.update_debounced(
+                ui.ctx(),
+                &document.buffer,
+                document.highlighter.language(),
+            );
+            // 光标或者要滚动到的位置在折叠起来的行里时展开。折叠状态由各窗格共用，
+            // 只看有焦点的窗格，否则在一个窗格里折叠会被另一个窗格的光标展开
+            if pane_focused {
+                let len = document.buffer.len_chars();
+                let targets = document
+                    .cursor
+                    .iter()
+                    .chain(&document.extra_cursors)
+                    .map(|c| c.primary.index)
+                    .chain(document.scroll_request);
+                for index in targets {
+                    let line = document.buffer.char_to_line(index.min(len));
+                    document.folds.reveal(line);
+                }
+            }
+            let gutter = Gutter {
+                line_numbers,
+                decorations: &document.decorations,
+                folds: document.folds.markers(),
+            };
+            let mut view = TextView::new(editor_id, &mut document.buffer, &mut document.cursor)
+                .gutter(gutter)
+                .extra_cursors(&mut document.extra_cursors)
+                .highlighter(&mut document.highlighter)
+                .highlights(matches, current)
+                .folded(document.folds.hidden())
+                .scroll_to(document.scroll_request.take())
+                .scroll_top_to_line(document.scroll_top_request.take())
+                .indent(document.indent);
+            if word_wrap {
+                view = view.word_wrap(&mut document.wrap_layout);
+            }
+            if show_minimap {
+                view = view.minimap(Minimap {
+                    decorations: &document.decorations,
                 });
             }
+            let output = view.show(ui);
+            if let Some(line) = output.toggle_fold {
+                document.folds.toggle(line);
+            }
+            document.top_line = output.top_line;
+            document.record_edits(output.edits, cursor_before);
+            output.response.has_focus()
         });
+        // 在窗格中点击或输入后它成为有焦点的窗格
+        if focused == Some(true) {
+            self.tabs.focus_pane(pane);
+        }
+    }
+
+    fn build_status_bar(&mut self, ctx: &egui::Context) {
+        let Some(id) = self.tabs.active_id() else {
+            return;
+        };
+        let Some(document) = self.tabs.get(id) else {
+            return;
+        };
+        self.status_bar.pending_keys = self.keymap.pending_text();
+        let Some(action) = self.status_bar.show(ctx, document) else {
+            return;
+        };
+        let Some(document) = self.tabs.get_mut(id) else {
+            return;
+        };
+        match action {
+            StatusAction::GoTo(position) => document.go_to(position),
+            StatusAction::Encoding(EncodingAction::Reopen(encoding)) => {
+                if let Err(err) = document.reopen_with_encoding(encoding) {
+                    self.error_message = Some(format!("无法用 {} 重新打开：\n{}", encoding, err));
+                }
+            }
+            StatusAction::Encoding(EncodingAction::SaveWith(encoding)) => {
+                // 保存失败（例如有字符无法用新编码表示）时恢复原来的编码
+                let previous = std::mem::replace(&mut document.encoding, encoding);
+                if !self.save_document(id) {
+                    if let Some(document) = self.tabs.get_mut(id) {
+                        document.encoding = previous;
+                    }
+                }
+            }
+            StatusAction::LineEnding(ending) => {
+                // 保存时按新的换行符写回
+                document.line_ending = ending;
+                document.mixed_line_endings = false;
+            }
+            StatusAction::Indent(indent) => document.indent = indent,
+            StatusAction::ConvertIndent(indent) => {
+                let ops = convert_indentation(&document.buffer, indent, document.indent.width());
+                if !ops.is_empty() {
+                    document.apply_edits(EditKind::Other, ops);
+                }
+                document.indent = indent;
+            }
+            StatusAction::Language(language) => document.highlighter.set_language(language),
+            StatusAction::Save => {
+                self.save_document(id);
+            }
+        }
+    }
+
+    fn build_find_bar(&mut self, ctx: &egui::Context) {
+        if !self.find_bar.open {
+            return;
+        }
+        let Some(id) = self.tabs.active_id() else {
+            return;
+        };
+        egui::TopBottomPanel::top("find_bar").show(ctx, |ui| {
+            if let Some(document) = self.tabs.get_mut(id) {
+                self.find_bar.ui(ui, id, document);
+            }
+        });
+    }
+
+    pub fn open_find_in_files(&mut self) {
+        // 默认在工作区文件夹中搜索，没有时用当前文件所在的文件夹
+        let dir = self.explorer.root().map(Path::to_path_buf).or_else(|| {
+            self.tabs
+                .active()
+                .and_then(|doc| doc.path.as_deref())
+                .and_then(Path::parent)
+                .map(Path::to_path_buf)
+        });
+        self.find_in_files.show(dir.as_deref());
+    }
+
+    pub fn open_folder_dialog(&mut self, ctx: &egui::Context) {
+        if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+            self.open_folder(ctx, dir);
+        }
+    }
+
+    /// 把文件夹作为工作区打开，显示在资源管理器中
+    pub fn open_folder(&mut self, ctx: &egui::Context, dir: PathBuf) {
+        self.explorer.set_root(ctx, dir);
+        if let Some(root) = self.explorer.root() {
+            self.find_in_files.root = Some(root.to_path_buf());
+        }
+    }
+
+    fn handle_explorer_action(&mut self, action: ExplorerAction) {
+        match action {
+            ExplorerAction::Open(path) => self.open_path(&path),
+            ExplorerAction::Moved { from, to } => self.tabs.rename_path(&from, &to),
+            ExplorerAction::Error(message) => self.error_message = Some(message),
+        }
+    }
+
+    pub fn open_find(&mut self, replace: bool) {
+        self.find_bar.open(self.tabs.active(), replace);
+    }
+
+    pub fn find_next(&mut self, forward: bool) {
+        let Some(id) = self.tabs.active_id() else {
+            return;
+        };
+        if let Some(document) = self.tabs.get_mut(id) {
+            self.find_bar.update_matches(id, document);
+            self.find_bar.step(document, forward);
+        }
+    }
+
+    /// 把本帧按下的快捷键交给快捷键表，执行对应的命令。
+    ///
+    /// 必须在编辑视图之前消费掉：Ctrl+Alt+Up 之类的组合键
+    /// 否则会被编辑视图当成普通的方向键处理
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if let Err(err) = self.keymap.poll() {
+            self.error_message = Some(err);
+        }
+        // 命令面板打开时按键都交给它
+        if self.palette.open {
+            return;
+        }
+        let editor_focus = self.tabs.active_id().is_some_and(|id| {
+            editor_view::has_focus(ctx, editor_id(self.tabs.focused_pane(), id))
+        });
+        let context = KeyContext {
+            editor_focus,
+            find_bar_open: self.find_bar.open,
+        };
+        if let Some(command) = self.keymap.dispatch(ctx, &context) {
+            self.run_command(ctx, command);
+        }
+    }
+
+    /// 执行菜单项或快捷键对应的命令
+    pub fn run_command(&mut self, ctx: &egui::Context, command: Command) {
+        self.palette.record(command);
+        match command {
+            Command::NewFile => self.new_document(),
+            Command::Open => self.open_file_dialog(),
+            Command::OpenFolder => self.open_folder_dialog(ctx),
+            Command::Save => {
+                self.save();
+            }
+            Command::SaveAs => {
+                self.save_as();
+            }
+            Command::SaveAll => {
+                self.save_all();
+            }
+            Command::Close => {
+                if let Some(id) = self.tabs.active_id() {
+                    self.handle_tab_action(TabAction::Close(id));
+                }
+            }
+            Command::Settings => self.settings_open = !self.settings_open,
+            Command::Quit => ctx.send_viewport_cmd(ViewportCommand::Close),
+            Command::Undo => self.undo(),
+            Command::Redo => self.redo(),
+            Command::Find => self.open_find(false),
+            Command::Replace => self.open_find(true),
+            Command::FindInFiles => self.open_find_in_files(),
+            Command::FindNext => self.find_next(true),
+            Command::FindPrevious => self.find_next(false),
+            Command::CloseFindBar => self.find_bar.close(),
+            Command::AddNextOccurrence => self.edit_cursors(multi_cursor::add_next_occurrence),
+            Command::AddCursorAbove => {
+                self.edit_cursors(|buffer, cursors| multi_cursor::add_cursor_vertical(buffer, cursors, true))
+            }
+            Command::AddCursorBelow => {
+                self.edit_cursors(|buffer, cursors| multi_cursor::add_cursor_vertical(buffer, cursors, false))
+            }
+            Command::SingleCursor => {
+                if let Some(document) = self.tabs.active_mut() {
+                    document.extra_cursors.clear();
+                }
+            }
+            Command::NextTab => self.tabs.next_tab(true),
+            Command::PreviousTab => self.tabs.next_tab(false),
+            Command::SplitRight => self.tabs.split(SplitDir::Horizontal),
+            Command::SplitDown => self.tabs.split(SplitDir::Vertical),
+            Command::ClosePane => {
+                if self.tabs.pane_count() > 1 {
+                    let ids = self.tabs.pane_tabs();
+                    self.close_tabs(&ids);
+                }
+            }
+            Command::FocusLeftPane => self.focus_pane(ctx, Direction::Left),
+            Command::FocusRightPane => self.focus_pane(ctx, Direction::Right),
+            Command::FocusPaneAbove => self.focus_pane(ctx, Direction::Up),
+            Command::FocusPaneBelow => self.focus_pane(ctx, Direction::Down),
+            Command::Fold => self.edit_folds(|folds, line| {
+                folds.fold_at(line);
+            }),
+            Command::Unfold => self.edit_folds(|folds, line| {
+                folds.unfold_at(line, false);
+            }),
+            Command::UnfoldRecursively => self.edit_folds(|folds, line| {
+                folds.unfold_at(line, true);
+            }),
+            Command::FoldAll => self.edit_folds(|folds, _| folds.fold_all()),
+            Command::UnfoldAll => self.edit_folds(|folds, _| folds.unfold_all()),
+            Command::FoldLevel1 => self.edit_folds(|folds, line| folds.fold_level(1, Some(line))),
+            Command::FoldLevel2 => self.edit_folds(|folds, line| folds.fold_level(2, Some(line))),
+            Command::FoldLevel3 => self.edit_folds(|folds, line| folds.fold_level(3, Some(line))),
+            Command::FoldLevel4 => self.edit_folds(|folds, line| folds.fold_level(4, Some(line))),
+            Command::FoldLevel5 => self.edit_folds(|folds, line| folds.fold_level(5, Some(line))),
+            Command::ToggleExplorer => self.explorer.open = !self.explorer.open,
+            Command::ToggleWordWrap => {
+                self.settings.settings.word_wrap = !self.settings.settings.word_wrap;
+                self.save_settings();
+            }
+            Command::ToggleMinimap => {
+                self.settings.settings.minimap = !self.settings.settings.minimap;
+                self.save_settings();
+            }
+            Command::CommandPalette => self.show_palette(ctx, PaletteMode::Commands),
+            Command::GoToFile => self.show_palette(ctx, PaletteMode::Files),
+            Command::GoToLine => self.show_palette(ctx, PaletteMode::Line),
+            Command::About => self.about_open = true,
+        }
+    }
+
+    /// 键盘焦点移到相邻的窗格
+    fn focus_pane(&mut self, ctx: &egui::Context, direction: Direction) {
+        if !self.tabs.focus_direction(direction) {
+            return;
+        }
+        if let Some(id) = self.tabs.active_id() {
+            editor_view::request_focus(ctx, editor_id(self.tabs.focused_pane(), id));
+        }
+    }
+
+    /// 在当前文档的全部光标上执行 `f`，滚动到它返回的位置
+    fn edit_cursors(
+        &mut self,
+        f: impl FnOnce(&TextBuffer, &mut Vec<CCursorRange>) -> Option<usize>,
+    ) {
+        let Some(document) = self.tabs.active_mut() else {
+            return;
+        };
+        let mut cursors: Vec<CCursorRange> = document
+            .cursor
+            .iter()
+            .chain(&document.extra_cursors)
+            .copied()
+            .collect();
+        if cursors.is_empty() {
+            cursors.push(multi_cursor::caret(0));
+        }
+        if let Some(index) = f(&document.buffer, &mut cursors) {
+            document.scroll_request = Some(index);
+            document.history.break_coalescing();
+        }
+        document.cursor = cursors.first().copied();
+        document.extra_cursors = cursors.split_off(1);
+    }
+
+    /// 对活动文档的折叠状态执行 `f`，参数是主光标所在的行
+    fn edit_folds(&mut self, f: impl FnOnce(&mut Folds, usize)) {
+        let Some(document) = self.tabs.active_mut() else {
+            return;
+        };
+        let index = document
+            .cursor
+            .map_or(0, |c| c.primary.index.min(document.buffer.len_chars()));
+        let line = document.buffer.char_to_line(index);
+        document
+            .folds
+            .update(&document.buffer, document.highlighter.language());
+        f(&mut document.folds, line);
+        // 折叠之后光标可能落在隐藏的行里：主光标移到标题行末尾，其余的去掉
+        let folds = &document.folds;
+        if folds.is_hidden(line) {
+            let header = folds.header_of(line);
+            let buffer = &document.buffer;
+            let index = buffer.line_to_char(header) + buffer.line_len_chars(header);
+            document.cursor = Some(multi_cursor::caret(index));
+        }
+        let buffer = &document.buffer;
+        document.extra_cursors.retain(|c| {
+            !folds.is_hidden(buffer.char_to_line(c.primary.index.min(buffer.len_chars())))
+        });
+    }
+
+    fn show_palette(&mut self, ctx: &egui::Context, mode: PaletteMode) {
+        let workspace = self.explorer.root().map(Path::to_path_buf);
+        self.palette.show(ctx, mode, workspace.as_deref());
+    }
+
+    fn handle_palette_action(&mut self, ctx: &egui::Context, action: PaletteAction) {
+        match action {
+            PaletteAction::Run(command) => self.run_command(ctx, command),
+            PaletteAction::SwitchTab(id) => self.tabs.set_active(id),
+            PaletteAction::OpenFile(path) => self.open_path(&path),
+            PaletteAction::GoToLine(position) => {
+                if let Some(document) = self.tabs.active_mut() {
+                    document.go_to(position);
+                }
+            }
+        }
+    }
+
+    pub fn handle_tab_action(&mut self, action: TabAction) {
+        match action {
+            TabAction::Activate(id) => self.tabs.set_active(id),
+            TabAction::Close(id) => self.close_tabs(&[id]),
+            TabAction::CloseOthers(id) => {
+                let others: Vec<usize> = self.tabs.pane_tabs().into_iter().filter(|&x| x != id).collect();
+                self.close_tabs(&others);
+            }
+            TabAction::CloseToTheRight(id) => {
+                let right = self.tabs.ids_right_of(id);
+                self.close_tabs(&right);
+            }
+            TabAction::Move { id, from, pane, to } => self.tabs.move_tab(id, from, pane, to),
+            TabAction::New => self.new_document(),
+        }
+    }
+
+    /// 关闭有焦点的窗格中的标签；有未保存的修改时先弹出确认对话框。
+    /// 还显示在其他窗格中的文档只去掉这个窗格的标签，不需要确认
+    pub fn close_tabs(&mut self, ids: &[usize]) {
+        let ids: Vec<usize> = ids.iter().copied().filter(|&id| !self.tabs.detach(id)).collect();
+        let pending = PendingClose {
+            ids,
+            quit: false,
+        };
+        if pending.dirty_ids(&self.tabs).is_empty() {
+            self.finish_close(pending);
+        } else {
+            self.pending_close = Some(pending);
+        }
+    }
+
+    fn finish_close(&mut self, pending: PendingClose) {
+        for id in pending.ids {
+            self.tabs.close_tab(id);
+        }
+    }
+
+    fn show_close_dialog(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &self.pending_close else {
+            return;
+        };
+        let Some(choice) = show_close_dialog(ctx, pending, &self.tabs) else {
+            return;
+        };
+        let proceed = match choice {
+            CloseChoice::Save(id) => {
+                self.save_document(id);
+                // 列表里的文件都保存完了就继续关闭
+                self.pending_close
+                    .as_ref()
+                    .is_some_and(|p| p.dirty_ids(&self.tabs).is_empty())
+            }
+            CloseChoice::SaveAll => {
+                let dirty = pending.dirty_ids(&self.tabs);
+                // 任何一个保存失败（或取消了另存为）都停下来，保留对话框
+                dirty.into_iter().all(|id| self.save_document(id))
+            }
+            CloseChoice::DontSave => true,
+            CloseChoice::Cancel => {
+                self.pending_close = None;
+                false
+            }
+        };
+        if !proceed {
+            return;
+        }
+        if let Some(pending) = self.pending_close.take() {
+            if pending.quit {
+                self.allowed_to_close = true;
+                ctx.send_viewport_cmd(ViewportCommand::Close);
+            } else {
+                self.finish_close(pending);
+            }
+        }
+    }
+
+    pub fn new_document(&mut self) {
+        let mut document = Document::new();
+        document.apply_default_indent(self.settings.settings.default_indent());
+        self.tabs.new_tab(document);
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(document) = self.tabs.active_mut() {
+            document.undo();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(document) = self.tabs.active_mut() {
+            document.redo();
+        }
+    }
+
+    pub fn open_file_dialog(&mut self) {
+        if let Some(paths) = rfd::FileDialog::new()
+            .add_filter("文本文件", TEXT_FILE_EXTENSIONS)
+            .add_filter("所有文件", &["*"])
+            .pick_files()
+        {
+            for path in paths {
+                self.open_path(&path);
+            }
+        }
+    }
+
+    /// 打开文件：已经打开的直接切换过去，当前是未改动的空白标签时直接替换它
+    pub fn open_path(&mut self, path: &Path) {
+        if let Err(err) = self.try_open_path(path) {
+            self.error_message = Some(format!("读取失败：{}\n{}", path.display(), err));
+        }
+    }
+
+    fn try_open_path(&mut self, path: &Path) -> io::Result<()> {
+        if let Some(id) = self.tabs.find_by_path(path) {
+            self.tabs.set_active(id);
+            return Ok(());
+        }
+        let document = Document::open(path)?;
+        self.open_document(document);
+        Ok(())
+    }
+
+    /// 打开文件并把光标移到指定行列（均从 0 开始）
+    pub fn open_at(&mut self, path: &Path, line: usize, col: usize) {
+        if let Err(err) = self.try_open_path(path) {
+            self.error_message = Some(format!("读取失败：{}\n{}", path.display(), err));
+            return;
+        }
+        if let Some(document) = self.tabs.active_mut() {
+            document.go_to(LineCol { line, col });
+        }
+    }
+
+    /// 打开新文档并返回它的标签 id
+    pub fn open_document(&mut self, mut document: Document) -> usize {
+        document.apply_default_indent(self.settings.settings.default_indent());
+        let replace = self
+            .tabs
+            .active_id()
+            .filter(|&id| self.tabs.get(id).is_some_and(Document::is_pristine));
+        let new_id = self.tabs.new_tab(document);
+        if let Some(id) = replace {
+            let index = self.tabs.index_of(id).unwrap_or(0);
+            self.tabs.close_tab(id);
+            let pane = self.tabs.focused_pane();
+            self.tabs.move_tab(new_id, pane, pane, index);
+            self.tabs.set_active(new_id);
+        }
+        new_id
+    }
+
+    /// 把拖入的文件（或文件夹中的文件）作为文档打开，无法打开的统一报错
+    pub fn open_dropped_files(&mut self, files: &[egui::DroppedFile]) {
+        let mut errors = Vec::new();
+        for file in files {
+            if let Some(path) = &file.path {
+                let expanded = expand_path(path);
+                for file in &expanded.files {
+                    if let Err(err) = self.try_open_path(file) {
+                        errors.push(format!("{}：{}", file.display(), err));
+                    }
+                }
+                if expanded.skipped > 0 {
+                    errors.push(format!(
+                        "{}：文件太多，只打开了前 {} 个",
+                        path.display(),
+                        MAX_FOLDER_FILES
+                    ));
+                }
+            } else if let Some(bytes) = &file.bytes {
+                // 没有路径时（例如 Web 平台）直接使用文件内容
+                let name = if file.name.is_empty() { "???" } else { &file.name };
+                match Document::from_bytes(bytes) {
+                    Ok(mut document) => {
+                        document.name = Some(name.to_owned());
+                        document.highlighter.set_language(Language::from_path(Path::new(name)));
+                        self.open_document(document);
+                    }
+                    Err(err) => errors.push(format!("{}：{}", name, err)),
+                }
+            } else {
+                errors.push(format!("{}：无法读取文件内容", file.name));
+            }
+        }
+        if !errors.is_empty() {
+            self.error_message = Some(format!("以下文件无法打开：\n{}", errors.join("\n")));
+        }
+    }
+
+    /// 保存当前文档；还没有路径时转为「另存为」。返回是否保存成功。
+    pub fn save(&mut self) -> bool {
+        match self.tabs.active_id() {
+            Some(id) => self.save_document(id),
+            None => false,
+        }
+    }
+
+    pub fn save_as(&mut self) -> bool {
+        match self.tabs.active_id() {
+            Some(id) => self.save_document_as(id),
+            None => false,
+        }
+    }
+
+    /// 保存所有有改动的文档，全部成功时返回 true
+    pub fn save_all(&mut self) -> bool {
+        let dirty: Vec<usize> = self
+            .tabs
+            .iter()
+            .filter(|(_, doc)| doc.is_dirty())
+            .map(|(id, _)| id)
+            .collect();
+        let mut all_saved = true;
+        for id in dirty {
+            all_saved &= self.save_document(id);
+        }
+        all_saved
+    }
+
+    pub fn save_document(&mut self, id: usize) -> bool {
+        let Some(document) = self.tabs.get_mut(id) else {
+            return false;
+        };
+        // 不要悄悄覆盖其他程序对文件的修改
+        if document.check_disk() == Some(DiskChange::Modified) {
+            self.pending_overwrite = Some(id);
+            return false;
+        }
+        match document.save() {
+            Some(Ok(())) => true,
+            Some(Err(err)) => {
+                self.report_save_error(id, &err);
+                false
+            }
+            None => self.save_document_as(id),
+        }
+    }
+
+    pub fn save_document_as(&mut self, id: usize) -> bool {
+        let Some(document) = self.tabs.get_mut(id) else {
+            return false;
+        };
+        let mut dialog = rfd::FileDialog::new()
+            .set_title("另存为")
+            .add_filter("文本文件", TEXT_FILE_EXTENSIONS)
+            .add_filter("所有文件", &["*"])
+            .set_file_name(document.title());
+        if let Some(dir) = document.path.as_ref().and_then(|p| p.parent()) {
+            dialog = dialog.set_directory(dir);
+        }
+        let Some(path) = dialog.save_file() else {
+            return false;
+        };
+        match document.save_as(&path) {
+            Ok(()) => true,
+            Err(err) => {
+                self.report_save_error(id, &err);
+                false
+            }
+        }
+    }
+
+    fn report_save_error(&mut self, id: usize, err: &std::io::Error) {
+        let name = match self.tabs.get(id) {
+            Some(Document { path: Some(path), .. }) => path.display().to_string(),
+            Some(document) => document.title(),
+            None => String::new(),
+        };
+        self.error_message = Some(format!("保存失败：{}\n{}", name, err));
+    }
+
+    fn show_error_dialog(&mut self, ctx: &egui::Context) {
+        let Some(message) = &self.error_message else {
+            return;
+        };
+        let mut close = false;
+        egui::Window::new("错误")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(message);
+                if ui.button("OK").clicked() {
+                    close = true;
+                }
+            });
+        if close {
+            self.error_message = None;
+        }
     }
 }
 
+/// Help → About… 打开的窗口
+fn about_window(ctx: &egui::Context, open: &mut bool) {
+    egui::Window::new("About")
+        .open(open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            ui.heading("Editor-rs");
+            ui.label(format!("版本 {}", env!("CARGO_PKG_VERSION")));
+        });
+}
 
+/// 窗格 `pane` 中文档 `document` 的编辑视图 id
+fn editor_id(pane: usize, document: usize) -> egui::Id {
+    egui::Id::new("editor_text").with((pane, document))
+}
 
 fn custom_central_panel_frame(ctx: &egui::Context) -> egui::Frame {
     use egui::CornerRadius;
@@ -179,38 +1447,3 @@ fn custom_central_panel_frame(ctx: &egui::Context) -> egui::Frame {
         .corner_radius(rounding)
         .outer_margin(1.0)
 }
-
-// Preview hovering files:
-pub fn preview_files_being_dropped(ctx: &egui::Context) {
-    use egui::{Align2, Color32, Id, LayerId, Order, TextStyle};
-    use std::fmt::Write as _;
-
-    if !ctx.input(|i| i.raw.hovered_files.is_empty()) {
-        let text = ctx.input(|i| {
-            let mut text = "Dropping files:\n".to_owned();
-            for file in &i.raw.hovered_files {
-                if let Some(path) = &file.path {
-                    write!(text, "\n{}", path.display()).ok();
-                } else if !file.mime.is_empty() {
-                    write!(text, "\n{}", file.mime).ok();
-                } else {
-                    text += "\n???";
-                }
-            }
-            text
-        });
-
-        let painter =
-            ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("file_drop_target")));
-
-        let screen_rect = ctx.screen_rect();
-        painter.rect_filled(screen_rect, 0.0, Color32::from_black_alpha(192));
-        painter.text(
-            screen_rect.center(),
-            Align2::CENTER_CENTER,
-            text,
-            TextStyle::Heading.resolve(&ctx.style()),
-            Color32::WHITE,
-        );
-    }
-}
\ No newline at end of file
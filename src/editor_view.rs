@@ -0,0 +1,1280 @@
+use crate::buffer::{LineChange, LineCol, TextBuffer};
+use crate::gutter::{Gutter, GutterAction, VisibleLine};
+use crate::highlight::{Highlighter, SyntaxTheme, layout_job};
+use crate::history::EditOp;
+use crate::indent::Indent;
+use crate::line_ending;
+use crate::minimap::{self, Minimap, MinimapView};
+use crate::multi_cursor::{self, caret, range_of, selection};
+use eframe::egui::{
+    self, Align, Color32, CursorIcon, Event, EventFilter, FontId, Galley, Id, ImeEvent, Key,
+    Modifiers, Pos2, Rect, ScrollArea, Sense, TextStyle, Vec2,
+    epaint::text::Fonts,
+    pos2,
+    text::{CCursor, CCursorRange, LayoutJob},
+    vec2,
+};
+use std::ops::Range;
+use std::sync::Arc;
+
+// 超过这个行数的文件不自动换行，每次编辑后重新计算换行的代价太大
+const MAX_WRAP_LINES: usize = 200_000;
+
+/// 只渲染可见行的文本编辑视图，支持多个光标和矩形选择。
+///
+/// 每帧只对视口内的行排版；按键和鼠标操作直接转换成对缓冲区的增量修改，
+/// 因此大文件也不会整体复制。`cursor` 是主光标，`extra_cursors` 是其余的光标，
+/// 都使用整个缓冲区中的绝对字符坐标。
+pub struct TextView<'a> {
+    id: Id,
+    buffer: &'a mut TextBuffer,
+    cursor: &'a mut Option<CCursorRange>,
+    extra_cursors: Option<&'a mut Vec<CCursorRange>>,
+    highlighter: Option<&'a mut Highlighter>,
+    highlights: &'a [Range<usize>],
+    current_highlight: Option<Range<usize>>,
+    scroll_to: Option<usize>,
+    scroll_top: Option<usize>,
+    indent: Indent,
+    wrap: Option<&'a mut WrapLayout>,
+    folded: &'a [Range<usize>],
+    gutter: Option<Gutter<'a>>,
+    minimap: Option<Minimap<'a>>,
+}
+
+pub struct TextViewOutput {
+    pub response: egui::Response,
+    /// 本帧对缓冲区做出的修改（已经写回缓冲区），按应用顺序排列，交给撤销历史记录
+    pub edits: Vec<EditOp>,
+    /// 视口顶部的缓冲区行
+    pub top_line: usize,
+    /// 点击了行号栏上这一行的折叠按钮，或者这一行后面的折叠占位符
+    pub toggle_fold: Option<usize>,
+}
+
+/// 跨帧保存的交互状态
+#[derive(Clone, Default)]
+struct ViewState {
+    /// 连续上下移动时每个光标保持的横坐标
+    preferred_x: Vec<f32>,
+    drag: Option<Drag>,
+    /// 输入法正在组字的内容，确认之前不写进缓冲区
+    preedit: String,
+    /// 不换行时见过的最宽的行，决定水平滚动的范围
+    max_width: f32,
+    /// 在缩略图上点击或拖动到的行，下一帧滚动到视口顶部
+    minimap_line: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+enum Drag {
+    /// 拖动选择：主光标跟随鼠标，选区的另一端不动
+    Select,
+    /// 按住 Alt 拖动：从 `origin`（相对内容左上角）开始的矩形选择
+    Box { origin: Vec2 },
+}
+
+impl<'a> TextView<'a> {
+    pub fn new(id: Id, buffer: &'a mut TextBuffer, cursor: &'a mut Option<CCursorRange>) -> Self {
+        Self {
+            id,
+            buffer,
+            cursor,
+            extra_cursors: None,
+            highlighter: None,
+            highlights: &[],
+            current_highlight: None,
+            scroll_to: None,
+            scroll_top: None,
+            indent: Indent::Tabs,
+            wrap: None,
+            folded: &[],
+            gutter: None,
+            minimap: None,
+        }
+    }
+
+    /// 在左侧显示行号栏
+    pub fn gutter(mut self, gutter: Gutter<'a>) -> Self {
+        self.gutter = Some(gutter);
+        self
+    }
+
+    /// 在右侧显示缩略图
+    pub fn minimap(mut self, minimap: Minimap<'a>) -> Self {
+        self.minimap = Some(minimap);
+        self
+    }
+
+    /// 允许多个光标：Alt+点击添加光标，Alt+拖动矩形选择
+    pub fn extra_cursors(mut self, cursors: &'a mut Vec<CCursorRange>) -> Self {
+        self.extra_cursors = Some(cursors);
+        self
+    }
+
+    /// 按视图宽度自动换行，`layout` 缓存各行换行后的行数
+    pub fn word_wrap(mut self, layout: &'a mut WrapLayout) -> Self {
+        self.wrap = Some(layout);
+        self
+    }
+
+    /// 隐藏折叠起来的行，`lines` 需按起始行排序、互不重叠
+    pub fn folded(mut self, lines: &'a [Range<usize>]) -> Self {
+        self.folded = lines;
+        self
+    }
+
+    /// 按 Tab 键时插入的缩进
+    pub fn indent(mut self, indent: Indent) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// 给若干字符范围加背景色（例如搜索结果），`ranges` 需按起始位置排序
+    pub fn highlights(mut self, ranges: &'a [Range<usize>], current: Option<Range<usize>>) -> Self {
+        self.highlights = ranges;
+        self.current_highlight = current;
+        self
+    }
+
+    /// 滚动到指定字符所在的行
+    pub fn scroll_to(mut self, char_idx: Option<usize>) -> Self {
+        self.scroll_to = char_idx;
+        self
+    }
+
+    /// 滚动到指定行位于视口顶部（恢复上次的滚动位置）
+    pub fn scroll_top_to_line(mut self, line: Option<usize>) -> Self {
+        self.scroll_top = line;
+        self
+    }
+
+    /// 使用语法高亮渲染
+    pub fn highlighter(mut self, highlighter: &'a mut Highlighter) -> Self {
+        self.highlighter = Some(highlighter);
+        self
+    }
+
+    pub fn show(self, ui: &mut egui::Ui) -> TextViewOutput {
+        let Self {
+            id,
+            buffer,
+            cursor,
+            extra_cursors,
+            mut highlighter,
+            highlights,
+            current_highlight,
+            scroll_to,
+            scroll_top,
+            indent,
+            wrap,
+            folded,
+            gutter,
+            minimap,
+        } = self;
+        let font_id = TextStyle::Monospace.resolve(ui.style());
+        let row_height = ui.fonts(|f| f.row_height(&font_id));
+        let text_color = ui.visuals().widgets.inactive.text_color();
+        // 配色跟随 global_theme_preference_switch 选择的明暗主题
+        let theme = SyntaxTheme::for_dark_mode(ui.visuals().dark_mode, text_color);
+        let (match_color, current_match_color) = if ui.visuals().dark_mode {
+            (
+                Color32::from_rgba_unmultiplied(255, 200, 0, 50),
+                Color32::from_rgba_unmultiplied(255, 150, 0, 130),
+            )
+        } else {
+            (
+                Color32::from_rgba_unmultiplied(255, 200, 0, 90),
+                Color32::from_rgba_unmultiplied(255, 140, 0, 170),
+            )
+        };
+        let selection_color = ui.visuals().selection.bg_fill;
+        let caret_stroke = ui.visuals().text_cursor.stroke;
+        let edit_id = id.with("visible_lines");
+        let mut state: ViewState = ui.data_mut(|d| d.get_temp(edit_id)).unwrap_or_default();
+
+        // 缓冲区可能在视图之外被修改（撤销、重新读取），先把光标限制在范围内
+        let len = buffer.len_chars();
+        let clamp = |c: CCursorRange| {
+            CCursorRange::two(
+                CCursor::new(c.secondary.index.min(len)),
+                CCursor::new(c.primary.index.min(len)),
+            )
+        };
+        let mut cursors: Vec<CCursorRange> = cursor
+            .iter()
+            .chain(extra_cursors.iter().flat_map(|extra| extra.iter()))
+            .copied()
+            .map(clamp)
+            .collect();
+
+        let mut wrap = wrap.filter(|_| buffer.len_lines() <= MAX_WRAP_LINES);
+        let mut scroll_area = if wrap.is_some() {
+            ScrollArea::vertical()
+        } else {
+            ScrollArea::both()
+        };
+        if let Some(line) = state.minimap_line.take() {
+            // 直接设置滚动位置，拖动缩略图时视口跟得上
+            let rows = Rows::new(wrap.as_deref(), folded, buffer.len_lines());
+            scroll_area =
+                scroll_area.vertical_scroll_offset(rows.line_to_row(line) as f32 * row_height);
+        }
+
+        // 行号栏在左侧，只跟着文本上下滚动
+        let full_rect = ui.available_rect_before_wrap();
+        let gutter_width = gutter
+            .as_ref()
+            .map_or(0.0, |g| g.width(ui, &font_id, buffer.len_lines()));
+        let minimap_width = if minimap.is_some() {
+            minimap::WIDTH
+        } else {
+            0.0
+        };
+        let (gutter_rect, text_rect) =
+            full_rect.split_left_right_at_x(full_rect.left() + gutter_width);
+        let (text_rect, minimap_rect) =
+            text_rect.split_left_right_at_x(text_rect.right() - minimap_width);
+        let mut text_ui = ui.new_child(
+            egui::UiBuilder::new()
+                .max_rect(text_rect)
+                .layout(*ui.layout()),
+        );
+
+        let output = scroll_area
+            .id_salt(id)
+            .auto_shrink([false; 2])
+            .show_viewport(&mut text_ui, |ui, viewport| {
+                ui.spacing_mut().item_spacing = Vec2::ZERO;
+                // 自动换行时一个缓冲区行可能占多个显示行
+                let wrap_width = ui.available_width();
+                if let Some(layout) = wrap.as_deref_mut() {
+                    ui.fonts(|f| layout.update(buffer, f, &font_id, wrap_width));
+                }
+                let metrics = Metrics {
+                    origin: ui.min_rect().min,
+                    row_height,
+                    font_id: font_id.clone(),
+                    wrap_width: wrap.is_some().then_some(wrap_width),
+                };
+                let rows = Rows::new(wrap.as_deref(), folded, buffer.len_lines());
+                let total_rows = rows.total_rows();
+                let width = if wrap.is_some() {
+                    wrap_width
+                } else {
+                    wrap_width.max(state.max_width + row_height)
+                };
+                let height = (total_rows as f32 * row_height).max(ui.available_height());
+                let rect = Rect::from_min_size(metrics.origin, vec2(width, height));
+                ui.allocate_rect(rect, Sense::hover());
+                let response = ui.interact(rect, edit_id, Sense::click_and_drag());
+                if response.hovered() {
+                    ui.ctx().set_cursor_icon(CursorIcon::Text);
+                }
+
+                if let Some(char_idx) = scroll_to {
+                    let line = buffer.char_to_line(char_idx);
+                    let y = metrics.line_top(&rows, line);
+                    let rect = Rect::from_x_y_ranges(rect.x_range(), y..=y + row_height);
+                    ui.scroll_to_rect(rect, Some(Align::Center));
+                }
+                if let Some(line) = scroll_top {
+                    let y = metrics.line_top(&rows, line.min(buffer.len_lines()));
+                    let rect = Rect::from_x_y_ranges(rect.x_range(), y..=y + row_height);
+                    ui.scroll_to_rect(rect, Some(Align::TOP));
+                }
+
+                // ---- 鼠标 ----
+                let (pressed, down, modifiers) = ui.input(|i| {
+                    (
+                        i.pointer.primary_pressed(),
+                        i.pointer.primary_down(),
+                        i.modifiers,
+                    )
+                });
+                let mut follow = false; // 主光标移动了，视图要跟着滚动
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let index = ui.fonts(|f| metrics.index_at(f, buffer, &rows, pos));
+                    if response.triple_clicked() {
+                        let line = buffer.char_to_line(index);
+                        let start = buffer.line_to_char(line);
+                        let end = buffer
+                            .line_to_char(line + 1)
+                            .max(start + buffer.line_len_chars(line));
+                        cursors = vec![selection(start..end)];
+                    } else if response.double_clicked() {
+                        let word = multi_cursor::word_at(buffer, index).unwrap_or(index..index);
+                        cursors = vec![selection(word)];
+                    } else if pressed && response.is_pointer_button_down_on() {
+                        response.request_focus();
+                        state.preferred_x.clear();
+                        if modifiers.alt && extra_cursors.is_some() {
+                            cursors.insert(0, caret(index));
+                            state.drag = Some(Drag::Box {
+                                origin: pos - metrics.origin,
+                            });
+                        } else if modifiers.shift && !cursors.is_empty() {
+                            cursors.truncate(1);
+                            cursors[0].primary = CCursor::new(index);
+                            state.drag = Some(Drag::Select);
+                        } else {
+                            cursors = vec![caret(index)];
+                            state.drag = Some(Drag::Select);
+                        }
+                    } else if response.dragged() {
+                        match state.drag {
+                            Some(Drag::Select) if !cursors.is_empty() => {
+                                cursors[0].primary = CCursor::new(index);
+                            }
+                            Some(Drag::Box { origin }) => {
+                                cursors = ui.fonts(|f| {
+                                    metrics.box_selection(
+                                        f,
+                                        buffer,
+                                        &rows,
+                                        metrics.origin + origin,
+                                        pos,
+                                    )
+                                });
+                            }
+                            _ => {}
+                        }
+                        follow = true;
+                    }
+                }
+                if !down {
+                    state.drag = None;
+                }
+
+                // ---- 键盘 ----
+                let focused = response.has_focus();
+                let mut edits = Vec::new();
+                if focused {
+                    ui.memory_mut(|m| {
+                        m.set_focus_lock_filter(
+                            edit_id,
+                            EventFilter {
+                                tab: true,
+                                horizontal_arrows: true,
+                                vertical_arrows: true,
+                                escape: false,
+                            },
+                        )
+                    });
+                    if cursors.is_empty() {
+                        cursors.push(caret(0));
+                    }
+                    let page_rows = (viewport.height() / row_height).floor().max(1.0) as isize;
+                    let events = ui.input(|i| i.events.clone());
+                    for event in &events {
+                        let mut vertical = false;
+                        let ops = match event {
+                            Event::Text(text) if state.preedit.is_empty() => {
+                                insert(buffer, &mut cursors, text)
+                            }
+                            Event::Paste(text) => paste(buffer, &mut cursors, text),
+                            Event::Copy => {
+                                let text = multi_cursor::selected_text(buffer, &cursors);
+                                if !text.is_empty() {
+                                    ui.ctx().copy_text(text);
+                                }
+                                continue;
+                            }
+                            Event::Cut => {
+                                let text = multi_cursor::selected_text(buffer, &cursors);
+                                if text.is_empty() {
+                                    continue;
+                                }
+                                ui.ctx().copy_text(text);
+                                insert(buffer, &mut cursors, "")
+                            }
+                            Event::Ime(ImeEvent::Preedit(text)) => {
+                                state.preedit = text.clone();
+                                continue;
+                            }
+                            Event::Ime(ImeEvent::Commit(text)) => {
+                                state.preedit.clear();
+                                if text.is_empty() || text == "\n" || text == "\r" {
+                                    continue;
+                                }
+                                insert(buffer, &mut cursors, text)
+                            }
+                            Event::Ime(ImeEvent::Enabled | ImeEvent::Disabled) => {
+                                state.preedit.clear();
+                                continue;
+                            }
+                            Event::Key {
+                                key,
+                                pressed: true,
+                                modifiers,
+                                ..
+                            } if state.preedit.is_empty() => {
+                                let rows = match key {
+                                    Key::ArrowUp => Some(-1),
+                                    Key::ArrowDown => Some(1),
+                                    Key::PageUp => Some(-page_rows),
+                                    Key::PageDown => Some(page_rows),
+                                    _ => None,
+                                };
+                                if let Some(count) = rows {
+                                    let rows =
+                                        Rows::new(wrap.as_deref(), folded, buffer.len_lines());
+                                    ui.fonts(|f| {
+                                        metrics.move_vertical(
+                                            f,
+                                            buffer,
+                                            &rows,
+                                            &mut cursors,
+                                            &mut state.preferred_x,
+                                            count,
+                                            modifiers.shift,
+                                        )
+                                    });
+                                    vertical = true;
+                                    Vec::new()
+                                } else {
+                                    match handle_key(buffer, &mut cursors, indent, *key, *modifiers)
+                                    {
+                                        Some(ops) => ops,
+                                        None => continue,
+                                    }
+                                }
+                            }
+                            _ => continue,
+                        };
+                        follow = true;
+                        if !vertical {
+                            state.preferred_x.clear();
+                        }
+                        if !ops.is_empty() {
+                            edits.extend(ops);
+                            // 后面的事件要按修改后的内容计算位置
+                            if let Some(layout) = wrap.as_deref_mut() {
+                                ui.fonts(|f| layout.update(buffer, f, &font_id, wrap_width));
+                            }
+                        }
+                    }
+                }
+                multi_cursor::merge(&mut cursors);
+                if extra_cursors.is_none() {
+                    cursors.truncate(1);
+                }
+
+                // ---- 绘制可见行 ----
+                let total_lines = buffer.len_lines();
+                let rows = Rows::new(wrap.as_deref(), folded, total_lines);
+                let first_row = (viewport.min.y / row_height).floor().max(0.0) as usize;
+                let last_row = (viewport.max.y / row_height).ceil() as usize;
+                let first = rows.row_to_line(first_row);
+                let last = (rows.row_to_line(last_row) + 1).clamp(first + 1, total_lines.max(1));
+                let painter = ui.painter();
+                let space_width = ui.fonts(|f| f.glyph_width(&font_id, ' '));
+                let current_line = cursors
+                    .first()
+                    .filter(|c| c.primary == c.secondary)
+                    .map(|c| buffer.char_to_line(c.primary.index));
+                let mut visible = Vec::new();
+                let mut unfold = None;
+                let mut next = rows.skip_hidden(first);
+                while next < last {
+                    let line = next;
+                    next = rows.skip_hidden(line + 1);
+                    let line_start = buffer.line_to_char(line);
+                    let text = buffer.line_text(line);
+                    let line_end = line_start + text.chars().count();
+                    let syntax = highlighter
+                        .as_deref_mut()
+                        .map(|h| (h.language(), h.state_at_line(buffer, line)));
+                    let mut job = match syntax {
+                        Some((language, state)) => {
+                            layout_job(language, state, &text, &font_id, &theme)
+                        }
+                        None => LayoutJob::simple(
+                            text.clone(),
+                            font_id.clone(),
+                            text_color,
+                            f32::INFINITY,
+                        ),
+                    };
+                    metrics.set_wrap(&mut job);
+
+                    // 落在这一行内的查找结果和选区（转换成行内的字符坐标）
+                    let in_line = |r: &Range<usize>| {
+                        r.start.clamp(line_start, line_end) - line_start
+                            ..r.end.clamp(line_start, line_end) - line_start
+                    };
+                    let first_mark = highlights.partition_point(|r| r.end <= line_start);
+                    let mut marks: Vec<(Range<usize>, Color32)> = highlights[first_mark..]
+                        .iter()
+                        .take_while(|r| r.start < line_end)
+                        .map(|r| (in_line(r), match_color))
+                        .collect();
+                    if let Some(r) = &current_highlight {
+                        if r.start < line_end && r.end > line_start {
+                            marks.push((in_line(r), current_match_color));
+                        }
+                    }
+                    for c in &cursors {
+                        let r = range_of(c);
+                        if r.start < line_end && r.end > line_start {
+                            marks.push((in_line(&r), selection_color));
+                        }
+                    }
+                    if !marks.is_empty() {
+                        paint_backgrounds(&mut job, &text, &marks);
+                    }
+                    let galley = ui.fonts(|f| f.layout_job(job));
+                    let top_left = pos2(metrics.origin.x, metrics.line_top(&rows, line));
+                    state.max_width = state.max_width.max(galley.size().x);
+                    let height = galley.size().y.max(row_height);
+                    visible.push(VisibleLine {
+                        line,
+                        top: top_left.y,
+                        height,
+                    });
+                    if current_line == Some(line) {
+                        let rect = Rect::from_x_y_ranges(
+                            ui.clip_rect().x_range(),
+                            top_left.y..=top_left.y + height,
+                        );
+                        painter.rect_filled(rect, 0.0, ui.visuals().faint_bg_color);
+                    }
+
+                    // 选区包含行尾的换行符时，在行尾多画一小块
+                    let end_rect = galley
+                        .pos_from_ccursor(CCursor::new(line_end - line_start))
+                        .translate(top_left.to_vec2());
+                    if cursors.iter().any(|c| {
+                        let r = range_of(c);
+                        r.start <= line_end && r.end > line_end
+                    }) {
+                        let rect =
+                            Rect::from_min_size(end_rect.left_top(), vec2(space_width, row_height));
+                        painter.rect_filled(rect, 0.0, selection_color);
+                    }
+                    painter.galley(top_left, galley.clone(), text_color);
+
+                    if next > line + 1 {
+                        // 折叠起来的行在标题行末尾显示成一个占位符，点击展开
+                        let weak_color = ui.visuals().weak_text_color();
+                        let dots =
+                            ui.fonts(|f| f.layout_no_wrap("…".into(), font_id.clone(), weak_color));
+                        let rect = Rect::from_min_size(
+                            end_rect.left_top() + vec2(space_width, 0.0),
+                            vec2(dots.size().x + space_width, row_height),
+                        );
+                        painter.rect_filled(rect, 3.0, ui.visuals().widgets.inactive.bg_fill);
+                        painter.galley(rect.center() - dots.size() / 2.0, dots, weak_color);
+                        if response.hover_pos().is_some_and(|p| rect.contains(p)) {
+                            ui.ctx().set_cursor_icon(CursorIcon::PointingHand);
+                        }
+                        if response.clicked()
+                            && response
+                                .interact_pointer_pos()
+                                .is_some_and(|p| rect.contains(p))
+                        {
+                            unfold = Some(line);
+                        }
+                    }
+
+                    if focused {
+                        for c in &cursors {
+                            let index = c.primary.index;
+                            if index < line_start || index > line_end {
+                                continue;
+                            }
+                            let rect = galley
+                                .pos_from_ccursor(CCursor::new(index - line_start))
+                                .translate(top_left.to_vec2());
+                            painter.line_segment(
+                                [rect.center_top(), rect.center_bottom()],
+                                caret_stroke,
+                            );
+                        }
+                    }
+                }
+
+                if let Some(primary) = cursors.first().filter(|_| focused) {
+                    let caret_rect =
+                        ui.fonts(|f| metrics.caret_rect(f, buffer, &rows, primary.primary.index));
+                    if !state.preedit.is_empty() {
+                        // 输入法组字中的内容画在主光标处
+                        let galley = ui.fonts(|f| {
+                            f.layout_no_wrap(state.preedit.clone(), font_id.clone(), text_color)
+                        });
+                        let rect = Rect::from_min_size(caret_rect.left_top(), galley.size());
+                        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+                        painter.galley(rect.min, galley, text_color);
+                        painter.hline(rect.x_range(), rect.bottom(), caret_stroke);
+                    }
+                    let to_global = ui
+                        .ctx()
+                        .layer_transform_to_global(ui.layer_id())
+                        .unwrap_or_default();
+                    ui.ctx().output_mut(|o| {
+                        o.ime = Some(egui::output::IMEOutput {
+                            rect: to_global * ui.clip_rect(),
+                            cursor_rect: to_global * caret_rect,
+                        });
+                    });
+                    if follow {
+                        ui.scroll_to_rect(caret_rect.expand2(vec2(row_height, 0.0)), None);
+                    }
+                }
+                if !edits.is_empty() {
+                    // 内容高度可能变了，下一帧重新计算
+                    ui.ctx().request_repaint();
+                }
+
+                let lines = (rows.row_to_line(first_row), rows.row_to_line(last_row));
+                (response, edits, lines, visible, unfold)
+            });
+        ui.allocate_rect(full_rect, Sense::hover());
+        let (response, edits, (top_line, bottom_line), visible, mut toggle_fold) = output.inner;
+
+        if let Some(minimap) = &minimap {
+            let minimap_rect = minimap_rect.intersect(Rect::from_x_y_ranges(
+                minimap_rect.x_range(),
+                output.inner_rect.y_range(),
+            ));
+            // 缩略图不换行，每个缓冲区行占一行
+            let rows = Rows::new(None, folded, buffer.len_lines());
+            let view = MinimapView {
+                buffer,
+                highlighter,
+                theme: &theme,
+                font_id: &font_id,
+                text_color,
+                highlights,
+                current_highlight: current_highlight.clone(),
+                rows: &rows,
+                top_line,
+                bottom_line,
+            };
+            if let Some(line) = minimap.show(ui, minimap_rect, id.with("minimap"), view) {
+                state.minimap_line = Some(line);
+                ui.ctx().request_repaint();
+            }
+        }
+
+        if let Some(gutter) = &gutter {
+            let gutter_rect = gutter_rect.intersect(Rect::from_x_y_ranges(
+                gutter_rect.x_range(),
+                output.inner_rect.y_range(),
+            ));
+            let current_line = cursors
+                .first()
+                .filter(|c| c.primary == c.secondary)
+                .map(|c| buffer.char_to_line(c.primary.index));
+            let action = gutter.show(
+                ui,
+                gutter_rect,
+                id.with("gutter"),
+                &font_id,
+                &visible,
+                current_line,
+                buffer.len_lines(),
+            );
+            match action {
+                Some(GutterAction::SelectLines(lines)) => {
+                    let start = buffer.line_to_char(lines.start);
+                    let end = if lines.end < buffer.len_lines() {
+                        buffer.line_to_char(lines.end)
+                    } else {
+                        buffer.len_chars()
+                    };
+                    cursors = vec![selection(start..end)];
+                    ui.memory_mut(|m| m.request_focus(edit_id));
+                }
+                Some(GutterAction::ToggleFold(line)) => toggle_fold = Some(line),
+                None => {}
+            }
+        }
+
+        ui.data_mut(|d| d.insert_temp(edit_id, state));
+        *cursor = cursors.first().copied();
+        if let Some(extra) = extra_cursors {
+            extra.clear();
+            extra.extend(cursors.iter().skip(1).copied());
+        }
+        TextViewOutput {
+            response,
+            edits,
+            top_line,
+            toggle_fold,
+        }
+    }
+}
+
+/// `id` 对应的文本视图是否有键盘焦点
+pub fn has_focus(ctx: &egui::Context, id: Id) -> bool {
+    ctx.memory(|m| m.has_focus(id.with("visible_lines")))
+}
+
+/// 让 `id` 对应的文本视图获得键盘焦点
+pub fn request_focus(ctx: &egui::Context, id: Id) {
+    ctx.memory_mut(|m| m.request_focus(id.with("visible_lines")));
+}
+
+/// 可见行的排版参数，负责屏幕坐标和字符下标之间的换算
+struct Metrics {
+    /// 内容左上角（第 0 行）的屏幕坐标
+    origin: Pos2,
+    row_height: f32,
+    font_id: FontId,
+    /// 自动换行时的宽度
+    wrap_width: Option<f32>,
+}
+
+impl Metrics {
+    fn set_wrap(&self, job: &mut LayoutJob) {
+        // 不换行时每个缓冲区行恰好占一行高度；换行时在任意字符处折行，
+        // 与 WrapLayout 的计算方式一致
+        job.wrap.max_width = self.wrap_width.unwrap_or(f32::INFINITY);
+        job.wrap.break_anywhere = self.wrap_width.is_some();
+    }
+
+    /// 不带颜色的一行排版，只用来计算位置
+    fn galley(&self, fonts: &Fonts, buffer: &TextBuffer, line: usize) -> Arc<Galley> {
+        let mut job = LayoutJob::simple(
+            buffer.line_text(line),
+            self.font_id.clone(),
+            Color32::PLACEHOLDER,
+            f32::INFINITY,
+        );
+        self.set_wrap(&mut job);
+        fonts.layout_job(job)
+    }
+
+    fn line_top(&self, rows: &Rows, line: usize) -> f32 {
+        self.origin.y + rows.line_to_row(line) as f32 * self.row_height
+    }
+
+    fn line_at_y(&self, rows: &Rows, y: f32) -> usize {
+        let row = ((y - self.origin.y) / self.row_height).floor().max(0.0) as usize;
+        rows.row_to_line(row)
+    }
+
+    /// 屏幕坐标处的字符下标
+    fn index_at(&self, fonts: &Fonts, buffer: &TextBuffer, rows: &Rows, pos: Pos2) -> usize {
+        let line = self.line_at_y(rows, pos.y);
+        let top = self.line_top(rows, line);
+        let galley = self.galley(fonts, buffer, line);
+        let col = galley
+            .cursor_from_pos(vec2(pos.x - self.origin.x, pos.y - top))
+            .ccursor
+            .index;
+        buffer.line_col_to_char(LineCol { line, col })
+    }
+
+    /// 字符下标处的光标矩形（屏幕坐标）
+    fn caret_rect(&self, fonts: &Fonts, buffer: &TextBuffer, rows: &Rows, index: usize) -> Rect {
+        let LineCol { line, col } = buffer.char_to_line_col(index);
+        let galley = self.galley(fonts, buffer, line);
+        galley
+            .pos_from_ccursor(CCursor::new(col))
+            .translate(vec2(self.origin.x, self.line_top(rows, line)))
+    }
+
+    /// 上下移动光标 `count` 个显示行，保持横坐标不变
+    #[allow(clippy::too_many_arguments)]
+    fn move_vertical(
+        &self,
+        fonts: &Fonts,
+        buffer: &TextBuffer,
+        rows: &Rows,
+        cursors: &mut [CCursorRange],
+        preferred_x: &mut Vec<f32>,
+        count: isize,
+        extend: bool,
+    ) {
+        if preferred_x.len() != cursors.len() {
+            *preferred_x = cursors
+                .iter()
+                .map(|c| {
+                    self.caret_rect(fonts, buffer, rows, c.primary.index).left() - self.origin.x
+                })
+                .collect();
+        }
+        let bottom = rows.total_rows() as f32 * self.row_height;
+        for (cursor, &x) in cursors.iter_mut().zip(preferred_x.iter()) {
+            let rect = self.caret_rect(fonts, buffer, rows, cursor.primary.index);
+            let y = rect.center().y + count as f32 * self.row_height;
+            // 第一行再往上移到开头，最后一行再往下移到末尾
+            let index = if y < self.origin.y {
+                0
+            } else if y >= self.origin.y + bottom {
+                buffer.len_chars()
+            } else {
+                self.index_at(fonts, buffer, rows, pos2(self.origin.x + x, y))
+            };
+            cursor.primary = CCursor::new(index);
+            if !extend {
+                cursor.secondary = cursor.primary;
+            }
+        }
+    }
+
+    /// 从 `from` 到 `to` 的矩形选择：每行一个光标，鼠标所在的行是主光标
+    fn box_selection(
+        &self,
+        fonts: &Fonts,
+        buffer: &TextBuffer,
+        rows: &Rows,
+        from: Pos2,
+        to: Pos2,
+    ) -> Vec<CCursorRange> {
+        let first = self.line_at_y(rows, from.y);
+        let last = self.line_at_y(rows, to.y);
+        let col_at = |line: usize, x: f32| {
+            let galley = self.galley(fonts, buffer, line);
+            let col = galley
+                .cursor_from_pos(vec2(x - self.origin.x, self.row_height * 0.5))
+                .ccursor
+                .index;
+            buffer.line_col_to_char(LineCol { line, col })
+        };
+        let lines: Vec<usize> = if first <= last {
+            (first..=last)
+                .rev()
+                .filter(|&l| !rows.is_hidden(l))
+                .collect()
+        } else {
+            (last..=first).filter(|&l| !rows.is_hidden(l)).collect()
+        };
+        lines
+            .into_iter()
+            .map(|line| {
+                CCursorRange::two(
+                    CCursor::new(col_at(line, from.x)),
+                    CCursor::new(col_at(line, to.x)),
+                )
+            })
+            .collect()
+    }
+}
+
+/// 在每个光标处输入文本（替换选中的内容）
+fn insert(buffer: &mut TextBuffer, cursors: &mut [CCursorRange], text: &str) -> Vec<EditOp> {
+    multi_cursor::edit_each(buffer, cursors, |_, _, c| {
+        Some((range_of(c), text.to_owned()))
+    })
+}
+
+/// 粘贴：行数和光标数相同时每个光标粘贴一行
+fn paste(buffer: &mut TextBuffer, cursors: &mut [CCursorRange], text: &str) -> Vec<EditOp> {
+    // 粘贴的内容可能带 `\r\n` 或 `\r`，缓冲区内部只用 `\n`
+    let text = line_ending::normalize(text);
+    let lines: Vec<&str> = text.split('\n').collect();
+    if cursors.len() > 1 && lines.len() == cursors.len() {
+        multi_cursor::edit_each(buffer, cursors, |_, n, c| {
+            Some((range_of(c), lines[n].to_owned()))
+        })
+    } else {
+        insert(buffer, cursors, &text)
+    }
+}
+
+/// 处理一个按键；不是编辑器处理的按键时返回 `None`
+fn handle_key(
+    buffer: &mut TextBuffer,
+    cursors: &mut Vec<CCursorRange>,
+    indent: Indent,
+    key: Key,
+    modifiers: Modifiers,
+) -> Option<Vec<EditOp>> {
+    let word = modifiers.command || modifiers.alt;
+    let len = buffer.len_chars();
+    let ops = match key {
+        Key::Backspace => multi_cursor::edit_each(buffer, cursors, |b, _, c| {
+            let r = range_of(c);
+            if !r.is_empty() {
+                return Some((r, String::new()));
+            }
+            let start = if word {
+                word_boundary(b, r.start, false)
+            } else {
+                r.start.checked_sub(1)?
+            };
+            Some((start..r.start, String::new()))
+        }),
+        Key::Delete => multi_cursor::edit_each(buffer, cursors, |b, _, c| {
+            let r = range_of(c);
+            if !r.is_empty() {
+                return Some((r, String::new()));
+            }
+            if r.end >= b.len_chars() {
+                return None;
+            }
+            let end = if word {
+                word_boundary(b, r.end, true)
+            } else {
+                r.end + 1
+            };
+            Some((r.start..end, String::new()))
+        }),
+        Key::Enter if !modifiers.command => insert(buffer, cursors, "\n"),
+        Key::Tab if modifiers.shift => multi_cursor::outdent_lines(buffer, cursors, indent.width()),
+        Key::Tab if !modifiers.command => {
+            let multi_line = cursors.iter().any(|c| {
+                let r = range_of(c);
+                buffer.char_to_line(r.start) != buffer.char_to_line(r.end)
+            });
+            if multi_line {
+                multi_cursor::indent_lines(buffer, cursors, &indent.text_at(0))
+            } else {
+                multi_cursor::edit_each(buffer, cursors, |b, _, c| {
+                    let r = range_of(c);
+                    let col = b.char_to_line_col(r.start).col;
+                    Some((r, indent.text_at(col)))
+                })
+            }
+        }
+        Key::A if modifiers.command => {
+            *cursors = vec![selection(0..len)];
+            Vec::new()
+        }
+        Key::ArrowLeft | Key::ArrowRight | Key::Home | Key::End => {
+            for cursor in cursors.iter_mut() {
+                let r = range_of(cursor);
+                let head = cursor.primary.index;
+                let target = match key {
+                    // 有选区时左右键先收起选区
+                    Key::ArrowLeft if !modifiers.shift && !r.is_empty() => r.start,
+                    Key::ArrowRight if !modifiers.shift && !r.is_empty() => r.end,
+                    Key::ArrowLeft if word => word_boundary(buffer, head, false),
+                    Key::ArrowRight if word => word_boundary(buffer, head, true),
+                    Key::ArrowLeft => head.saturating_sub(1),
+                    Key::ArrowRight => (head + 1).min(len),
+                    Key::Home if modifiers.command => 0,
+                    Key::End if modifiers.command => len,
+                    Key::Home => smart_home(buffer, head),
+                    _ => {
+                        let line = buffer.char_to_line(head);
+                        buffer.line_to_char(line) + buffer.line_len_chars(line)
+                    }
+                };
+                cursor.primary = CCursor::new(target);
+                if !modifiers.shift {
+                    cursor.secondary = cursor.primary;
+                }
+            }
+            Vec::new()
+        }
+        _ => return None,
+    };
+    Some(ops)
+}
+
+/// Home：先到行首第一个非空白字符，已经在那里时到行首
+fn smart_home(buffer: &TextBuffer, index: usize) -> usize {
+    let line = buffer.char_to_line(index);
+    let start = buffer.line_to_char(line);
+    let indent = buffer
+        .line_text(line)
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .count();
+    if index == start + indent {
+        start
+    } else {
+        start + indent
+    }
+}
+
+/// 向前或向后跳过一个单词（或一串标点）以及它前面的空白，在行首行尾时跨到相邻的行
+fn word_boundary(buffer: &TextBuffer, index: usize, forward: bool) -> usize {
+    let line = buffer.char_to_line(index);
+    let start = buffer.line_to_char(line);
+    let chars: Vec<char> = buffer.line_text(line).chars().collect();
+    let col = index - start;
+    let same_kind =
+        |c: char, word: bool| !c.is_whitespace() && multi_cursor::is_word_char(c) == word;
+    if forward {
+        if col >= chars.len() {
+            return (index + 1).min(buffer.len_chars());
+        }
+        let mut i = col;
+        while i < chars.len() && chars[i].is_whitespace() {
+            i += 1;
+        }
+        if let Some(&c) = chars.get(i) {
+            let word = multi_cursor::is_word_char(c);
+            while i < chars.len() && same_kind(chars[i], word) {
+                i += 1;
+            }
+        }
+        start + i
+    } else {
+        if col == 0 {
+            return index.saturating_sub(1);
+        }
+        let mut i = col;
+        while i > 0 && chars[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        if i > 0 {
+            let word = multi_cursor::is_word_char(chars[i - 1]);
+            while i > 0 && same_kind(chars[i - 1], word) {
+                i -= 1;
+            }
+        }
+        start + i
+    }
+}
+
+/// 自动换行时每个缓冲区行之前有多少显示行。内容变化时只重新测量改过的行，
+/// 宽度变化时只有比新宽度还宽的行需要重新折行。
+#[derive(Default)]
+pub struct WrapLayout {
+    version: u64,
+    width: f32,
+    font_id: Option<FontId>,
+    // 每行不折行时的宽度
+    line_widths: Vec<f32>,
+    // row_starts[i]：第 i 行之前的显示行数，长度为行数 + 1
+    row_starts: Vec<usize>,
+}
+
+impl WrapLayout {
+    fn update(&mut self, buffer: &TextBuffer, fonts: &Fonts, font_id: &FontId, width: f32) {
+        // ASCII 字符的宽度先查好，其余字符逐个询问字体
+        let ascii: Vec<f32> = (0..128u8)
+            .map(|b| fonts.glyph_width(font_id, b as char))
+            .collect();
+        let glyph_width = |c: char| {
+            if c.is_ascii() {
+                ascii[c as usize]
+            } else {
+                fonts.glyph_width(font_id, c)
+            }
+        };
+        let line_width = |line: usize| {
+            buffer
+                .line(line)
+                .chars()
+                .take_while(|&c| c != '\n' && c != '\r')
+                .map(glyph_width)
+                .sum::<f32>()
+        };
+        // 与 egui 的 break_anywhere 一样：放不下下一个字符就折行
+        let line_rows = |line: usize, line_width: f32| {
+            if line_width <= width {
+                return 1;
+            }
+            let mut rows = 1;
+            let mut x = 0.0;
+            for c in buffer.line(line).chars() {
+                if c == '\n' || c == '\r' {
+                    break;
+                }
+                let w = glyph_width(c);
+                if x > 0.0 && x + w > width {
+                    rows += 1;
+                    x = 0.0;
+                }
+                x += w;
+            }
+            rows
+        };
+
+        let line_count = buffer.len_lines();
+        let font_changed = self.font_id.as_ref() != Some(font_id);
+        let measured = self.line_widths.len();
+        let change = if font_changed || self.row_starts.len() != measured + 1 {
+            // 第一次或者字体变了：全部重新测量
+            self.row_starts.clear();
+            Some(LineChange {
+                start: 0,
+                old_end: measured,
+                new_end: line_count,
+            })
+        } else {
+            buffer.changed_lines_since(self.version, measured)
+        };
+        if change.is_none() && self.width == width {
+            return;
+        }
+
+        match change {
+            Some(change) if !self.row_starts.is_empty() && self.width == width => {
+                // 只有改过的行需要重新测量，后面的行整体移动
+                let widths: Vec<f32> = (change.start..change.new_end).map(line_width).collect();
+                let old_rows = self.row_starts[change.old_end] - self.row_starts[change.start];
+                let mut total = self.row_starts[change.start];
+                let starts: Vec<usize> = (change.start..change.new_end)
+                    .zip(&widths)
+                    .map(|(line, &w)| {
+                        total += line_rows(line, w);
+                        total
+                    })
+                    .collect();
+                let new_rows = total - self.row_starts[change.start];
+                self.line_widths
+                    .splice(change.start..change.old_end, widths);
+                self.row_starts
+                    .splice(change.start + 1..change.old_end + 1, starts);
+                for start in &mut self.row_starts[change.new_end + 1..] {
+                    *start = *start + new_rows - old_rows;
+                }
+            }
+            change => {
+                if let Some(change) = change {
+                    let widths: Vec<f32> = (change.start..change.new_end).map(line_width).collect();
+                    self.line_widths
+                        .splice(change.start..change.old_end, widths);
+                }
+                // 宽度变了：不比新宽度宽的行仍然只占一行
+                self.row_starts.clear();
+                self.row_starts.push(0);
+                let mut total = 0;
+                for (line, &w) in self.line_widths.iter().enumerate() {
+                    total += line_rows(line, w);
+                    self.row_starts.push(total);
+                }
+            }
+        }
+        debug_assert_eq!(self.line_widths.len(), line_count);
+        self.version = buffer.version();
+        self.width = width;
+        self.font_id = Some(font_id.clone());
+    }
+
+    fn line_to_row(&self, line: usize) -> usize {
+        self.row_starts[line.min(self.row_starts.len() - 1)]
+    }
+
+    fn row_to_line(&self, row: usize) -> usize {
+        let lines = self.row_starts.len() - 1;
+        self.row_starts
+            .partition_point(|&start| start <= row)
+            .saturating_sub(1)
+            .min(lines.saturating_sub(1))
+    }
+}
+
+/// 缓冲区行和显示行之间的换算：自动换行时一行可能占多个显示行，
+/// 折叠起来的行不占显示行
+pub(crate) struct Rows<'a> {
+    wrap: Option<&'a WrapLayout>,
+    /// 隐藏的行，以及到这一段为止（含）一共隐藏了多少显示行
+    hidden: Vec<(Range<usize>, usize)>,
+    line_count: usize,
+}
+
+impl<'a> Rows<'a> {
+    pub(crate) fn new(
+        wrap: Option<&'a WrapLayout>,
+        hidden: &[Range<usize>],
+        line_count: usize,
+    ) -> Self {
+        let mut rows = Self {
+            wrap,
+            hidden: Vec::with_capacity(hidden.len()),
+            line_count,
+        };
+        let mut total = 0;
+        for r in hidden {
+            // 折叠状态可能还没跟上刚刚的编辑
+            let r = r.start.min(line_count)..r.end.min(line_count);
+            if r.is_empty() {
+                continue;
+            }
+            total += rows.base_row(r.end) - rows.base_row(r.start);
+            rows.hidden.push((r, total));
+        }
+        rows
+    }
+
+    /// 不考虑折叠时 `line` 之前的显示行数
+    fn base_row(&self, line: usize) -> usize {
+        self.wrap
+            .map_or(line.min(self.line_count), |w| w.line_to_row(line))
+    }
+
+    pub(crate) fn line_to_row(&self, line: usize) -> usize {
+        let k = self.hidden.partition_point(|(r, _)| r.end <= line);
+        let before = k.checked_sub(1).map_or(0, |k| self.hidden[k].1);
+        // 隐藏的行算在折叠之后的第一个显示行上
+        let line = match self.hidden.get(k) {
+            Some((r, _)) if r.start <= line => r.start,
+            _ => line,
+        };
+        self.base_row(line) - before
+    }
+
+    pub(crate) fn row_to_line(&self, row: usize) -> usize {
+        let mut before = 0;
+        for (r, total) in &self.hidden {
+            if self.base_row(r.start) - before > row {
+                break;
+            }
+            before = *total;
+        }
+        let row = row + before;
+        self.wrap
+            .map_or(row, |w| w.row_to_line(row))
+            .min(self.line_count.saturating_sub(1))
+    }
+
+    pub(crate) fn total_rows(&self) -> usize {
+        self.base_row(self.line_count) - self.hidden.last().map_or(0, |(_, total)| *total)
+    }
+
+    fn is_hidden(&self, line: usize) -> bool {
+        self.skip_hidden(line) != line
+    }
+
+    /// `line` 被折叠隐藏时跳到这一段之后的第一行
+    fn skip_hidden(&self, line: usize) -> usize {
+        let k = self.hidden.partition_point(|(r, _)| r.end <= line);
+        match self.hidden.get(k) {
+            Some((r, _)) if r.start <= line => r.end,
+            _ => line,
+        }
+    }
+}
+
+/// 按字符范围给 `LayoutJob` 的片段加背景色，必要时把片段切开。
+/// 后出现的范围优先（例如当前匹配覆盖普通匹配）。
+fn paint_backgrounds(job: &mut LayoutJob, text: &str, marks: &[(Range<usize>, Color32)]) {
+    // 字符坐标 → 字节坐标
+    let byte_of = |char_idx: usize| {
+        text.char_indices()
+            .nth(char_idx)
+            .map_or(text.len(), |(i, _)| i)
+    };
+    let marks: Vec<(Range<usize>, Color32)> = marks
+        .iter()
+        .map(|(r, color)| (byte_of(r.start)..byte_of(r.end), *color))
+        .filter(|(r, _)| !r.is_empty())
+        .collect();
+
+    let mut sections = Vec::with_capacity(job.sections.len());
+    for section in job.sections.drain(..) {
+        let range = section.byte_range.clone();
+        let mut cuts = vec![range.start, range.end];
+        for (r, _) in &marks {
+            for pos in [r.start, r.end] {
+                if range.start < pos && pos < range.end {
+                    cuts.push(pos);
+                }
+            }
+        }
+        cuts.sort_unstable();
+        cuts.dedup();
+        for piece in cuts.windows(2) {
+            let mut part = section.clone();
+            part.byte_range = piece[0]..piece[1];
+            if let Some((_, color)) = marks
+                .iter()
+                .rev()
+                .find(|(r, _)| r.start <= piece[0] && piece[1] <= r.end)
+            {
+                part.format.background = *color;
+            }
+            sections.push(part);
+        }
+    }
+    job.sections = sections;
+}
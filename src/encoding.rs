@@ -0,0 +1,306 @@
+use eframe::egui;
+use encoding_rs::{
+    BIG5, EUC_JP, EUC_KR, Encoding, GB18030, GBK, SHIFT_JIS, UTF_8, UTF_16BE, UTF_16LE,
+    WINDOWS_1252,
+};
+use std::fmt;
+
+// 检测编码时最多分析的字节数
+const SNIFF_LEN: usize = 64 * 1024;
+
+/// 编码菜单里列出的编码
+pub const ENCODINGS: &[&Encoding] = &[
+    UTF_8,
+    UTF_16LE,
+    UTF_16BE,
+    GB18030,
+    GBK,
+    BIG5,
+    SHIFT_JIS,
+    EUC_JP,
+    EUC_KR,
+    WINDOWS_1252,
+];
+
+/// 文件的编码：字符集 + 是否带 BOM。保存时按原样写回。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextEncoding {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        Self::new(UTF_8)
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.encoding.name())?;
+        // UTF-16 总是带 BOM，只有 UTF-8 需要特别标出
+        if self.bom && self.encoding == UTF_8 {
+            f.write_str(" with BOM")?;
+        }
+        Ok(())
+    }
+}
+
+impl TextEncoding {
+    /// UTF-16 默认带 BOM，其他编码不带
+    pub fn new(encoding: &'static Encoding) -> Self {
+        Self {
+            encoding,
+            bom: is_utf16(encoding),
+        }
+    }
+
+    /// 先看 BOM，再看是不是 UTF-16 / UTF-8，最后交给 chardetng 猜测
+    pub fn detect(bytes: &[u8]) -> Self {
+        if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+            return Self {
+                encoding,
+                bom: true,
+            };
+        }
+        if let Some(encoding) = guess_utf16(bytes) {
+            return Self {
+                encoding,
+                bom: false,
+            };
+        }
+        if std::str::from_utf8(bytes).is_ok() {
+            return Self::default();
+        }
+        let head = &bytes[..bytes.len().min(SNIFF_LEN)];
+        let mut detector = chardetng::EncodingDetector::new();
+        detector.feed(head, head.len() == bytes.len());
+        Self::new(detector.guess(None, true))
+    }
+
+    /// `bytes` 是否以此编码的 BOM 开头
+    pub fn has_bom(&self, bytes: &[u8]) -> bool {
+        matches!(Encoding::for_bom(bytes), Some((encoding, _)) if encoding == self.encoding)
+    }
+
+    /// 按此编码解码，跳过对应的 BOM；含有无效字节时返回 `None`
+    pub fn decode(&self, bytes: &[u8]) -> Option<String> {
+        let bytes = match Encoding::for_bom(bytes) {
+            Some((encoding, len)) if encoding == self.encoding => &bytes[len..],
+            _ => bytes,
+        };
+        self.encoding
+            .decode_without_bom_handling_and_without_replacement(bytes)
+            .map(|text| text.into_owned())
+    }
+
+    /// 编码为字节，需要时在开头写入 BOM；有字符无法用此编码表示时返回 `Err`
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::with_capacity(text.len() + 3);
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            // encoding_rs 不提供 UTF-16 编码器，手动转换
+            let big_endian = self.encoding == UTF_16BE;
+            if self.bom {
+                bytes.extend_from_slice(if big_endian {
+                    &[0xFE, 0xFF]
+                } else {
+                    &[0xFF, 0xFE]
+                });
+            }
+            for unit in text.encode_utf16() {
+                let pair = if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                };
+                bytes.extend_from_slice(&pair);
+            }
+            return Ok(bytes);
+        }
+        if self.bom && self.encoding == UTF_8 {
+            bytes.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+        }
+        let (encoded, _, had_errors) = self.encoding.encode(text);
+        if had_errors {
+            let bad = text
+                .chars()
+                .find(|c| {
+                    let mut buf = [0; 4];
+                    self.encoding.encode(c.encode_utf8(&mut buf)).2
+                })
+                .unwrap_or('?');
+            return Err(format!(
+                "字符 “{}” 无法用 {} 编码保存",
+                bad,
+                self.encoding.name()
+            ));
+        }
+        bytes.extend_from_slice(&encoded);
+        Ok(bytes)
+    }
+}
+
+/// 编码菜单中的选择
+pub enum EncodingAction {
+    /// 用另一种编码重新解释文件内容
+    Reopen(TextEncoding),
+    /// 换一种编码保存
+    SaveWith(TextEncoding),
+}
+
+/// 显示当前编码的按钮，点开后可以重新解释或转换编码。
+/// 有未保存的修改时不能重新打开，以免丢失内容。
+pub fn encoding_button(
+    ui: &mut egui::Ui,
+    current: TextEncoding,
+    can_reopen: bool,
+) -> Option<EncodingAction> {
+    let mut action = None;
+    ui.menu_button(current.to_string(), |ui| {
+        ui.add_enabled_ui(can_reopen, |ui| {
+            ui.menu_button("Reopen with Encoding", |ui| {
+                for &encoding in ENCODINGS {
+                    let selected = encoding == current.encoding;
+                    if ui.selectable_label(selected, encoding.name()).clicked() {
+                        action = Some(EncodingAction::Reopen(TextEncoding::new(encoding)));
+                        ui.close_menu();
+                    }
+                }
+            });
+        })
+        .response
+        .on_disabled_hover_text("先保存或撤销修改");
+        ui.menu_button("Save with Encoding", |ui| {
+            let utf8_bom = TextEncoding {
+                encoding: UTF_8,
+                bom: true,
+            };
+            let choices = ENCODINGS
+                .iter()
+                .map(|&e| TextEncoding::new(e))
+                .flat_map(|e| {
+                    if e.encoding == UTF_8 {
+                        vec![e, utf8_bom]
+                    } else {
+                        vec![e]
+                    }
+                });
+            for encoding in choices {
+                if ui
+                    .selectable_label(encoding == current, encoding.to_string())
+                    .clicked()
+                {
+                    action = Some(EncodingAction::SaveWith(encoding));
+                    ui.close_menu();
+                }
+            }
+        });
+    });
+    action
+}
+
+pub fn is_utf16(encoding: &Encoding) -> bool {
+    encoding == UTF_16LE || encoding == UTF_16BE
+}
+
+/// 看起来像 UTF-16 文本（有 BOM，或者 ASCII 字符的高字节大多为 0）。
+/// 这类文件含有大量 NUL 字节，不能当作二进制文件。
+pub fn looks_utf16(bytes: &[u8]) -> bool {
+    matches!(Encoding::for_bom(bytes), Some((e, _)) if is_utf16(e)) || guess_utf16(bytes).is_some()
+}
+
+/// 没有 BOM 的 UTF-16：统计偶数位和奇数位上的 NUL 字节
+fn guess_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(SNIFF_LEN) & !1];
+    if head.len() < 4 {
+        return None;
+    }
+    let pairs = head.len() / 2;
+    let mut even_zeros = 0;
+    let mut odd_zeros = 0;
+    for pair in head.chunks_exact(2) {
+        even_zeros += usize::from(pair[0] == 0);
+        odd_zeros += usize::from(pair[1] == 0);
+    }
+    // 大部分字符在 ASCII / Latin-1 范围内时，另一半字节几乎都是 0
+    if odd_zeros * 10 >= pairs * 6 && even_zeros * 20 <= pairs {
+        Some(UTF_16LE)
+    } else if even_zeros * 10 >= pairs * 6 && odd_zeros * 20 <= pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf8_bom() -> TextEncoding {
+        TextEncoding {
+            encoding: UTF_8,
+            bom: true,
+        }
+    }
+
+    #[test]
+    fn utf8_with_and_without_bom() {
+        let plain = "你好，world\n".as_bytes();
+        assert_eq!(TextEncoding::detect(plain), TextEncoding::new(UTF_8));
+        assert_eq!(
+            TextEncoding::new(UTF_8).decode(plain).unwrap(),
+            "你好，world\n"
+        );
+
+        let with_bom = [&[0xEF, 0xBB, 0xBF], plain].concat();
+        let encoding = TextEncoding::detect(&with_bom);
+        assert_eq!(encoding, utf8_bom());
+        assert!(encoding.has_bom(&with_bom));
+        assert_eq!(encoding.decode(&with_bom).unwrap(), "你好，world\n");
+        assert_eq!(encoding.to_string(), "UTF-8 with BOM");
+    }
+
+    #[test]
+    fn utf16_with_and_without_bom() {
+        let text = "hello, 世界\n";
+        for encoding in [UTF_16LE, UTF_16BE] {
+            let with_bom = TextEncoding::new(encoding);
+            let bytes = with_bom.encode(text).unwrap();
+            assert_eq!(TextEncoding::detect(&bytes), with_bom);
+            assert!(looks_utf16(&bytes));
+            assert_eq!(with_bom.decode(&bytes).unwrap(), text);
+
+            let without_bom = TextEncoding {
+                encoding,
+                bom: false,
+            };
+            let bytes = without_bom.encode(text).unwrap();
+            assert_eq!(bytes.len(), text.encode_utf16().count() * 2);
+            assert_eq!(TextEncoding::detect(&bytes), without_bom);
+            assert!(looks_utf16(&bytes));
+            assert_eq!(without_bom.decode(&bytes).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        let text = "第一行\r\n第二行";
+        for encoding in [
+            TextEncoding::new(UTF_8),
+            utf8_bom(),
+            TextEncoding::new(GB18030),
+        ] {
+            let bytes = encoding.encode(text).unwrap();
+            assert_eq!(encoding.has_bom(&bytes), encoding.bom);
+            assert_eq!(encoding.decode(&bytes).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn invalid_bytes_and_unencodable_characters() {
+        assert_eq!(TextEncoding::new(UTF_8).decode(b"ab\xFFcd"), None);
+        assert!(TextEncoding::new(WINDOWS_1252).encode("你好").is_err());
+        // 不是 UTF-8 的字节交给 chardetng 猜测
+        assert_ne!(TextEncoding::detect(b"caf\xE9 cr\xE8me").encoding, UTF_8);
+    }
+}
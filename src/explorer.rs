@@ -0,0 +1,446 @@
+use eframe::egui;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// 目录中的一项
+struct Entry {
+    name: String,
+    path: PathBuf,
+    is_dir: bool,
+}
+
+/// 需要用户输入或确认的文件操作
+enum Prompt {
+    NewFile(PathBuf),
+    NewFolder(PathBuf),
+    Rename(PathBuf),
+    Delete(PathBuf),
+}
+
+/// 资源管理器产生的、需要编辑器处理的事件
+pub enum ExplorerAction {
+    Open(PathBuf),
+    /// 文件或文件夹被重命名 / 移动，已打开的文档要跟着改路径
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Error(String),
+}
+
+/// 左侧的文件资源管理器：打开的工作区文件夹以树形显示，目录展开时才读取。
+#[derive(Default)]
+pub struct Explorer {
+    pub open: bool,
+    root: Option<PathBuf>,
+    // 已读取的目录内容；文件系统变化时删掉对应的项，下次显示时重新读取
+    listings: HashMap<PathBuf, Vec<Entry>>,
+    expanded: HashSet<PathBuf>,
+    selected: Option<PathBuf>,
+    prompt: Option<(Prompt, String)>,
+    focus_prompt: bool,
+    watcher: Option<RecommendedWatcher>,
+    watched: HashSet<PathBuf>,
+    events: Option<Receiver<notify::Result<notify::Event>>>,
+}
+
+impl Explorer {
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// 打开工作区文件夹
+    pub fn set_root(&mut self, ctx: &egui::Context, root: PathBuf) {
+        let root = root.canonicalize().unwrap_or(root);
+        self.listings.clear();
+        self.expanded.clear();
+        self.expanded.insert(root.clone());
+        self.watched.clear();
+        self.selected = None;
+        self.prompt = None;
+
+        // 监听回调在其他线程执行，收到变化后唤醒界面
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+            ctx.request_repaint();
+        });
+        match watcher {
+            Ok(watcher) => {
+                self.watcher = Some(watcher);
+                self.events = Some(receiver);
+            }
+            Err(err) => {
+                log::warn!("无法监听文件夹变化：{}", err);
+                self.watcher = None;
+                self.events = None;
+            }
+        }
+        self.root = Some(root);
+        self.open = true;
+    }
+
+    /// 处理文件系统变化：让受影响的目录在下次显示时重新读取
+    fn poll_events(&mut self) {
+        let Some(events) = &self.events else {
+            return;
+        };
+        let mut stale = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            let Ok(event) = event else {
+                // 事件可能丢失，全部重新读取
+                self.listings.clear();
+                continue;
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            for path in event.paths {
+                if let Some(parent) = path.parent() {
+                    stale.push(parent.to_path_buf());
+                }
+                stale.push(path);
+            }
+        }
+        for path in stale {
+            self.listings.remove(&path);
+            if !path.exists() {
+                // 被删除的目录需要重新监听
+                self.watched.remove(&path);
+            }
+        }
+    }
+
+    fn load(&mut self, dir: &Path) {
+        if self.listings.contains_key(dir) {
+            return;
+        }
+        let entries = read_entries(dir).unwrap_or_default();
+        if !self.watched.contains(dir) {
+            if let Some(watcher) = &mut self.watcher {
+                if watcher.watch(dir, RecursiveMode::NonRecursive).is_ok() {
+                    self.watched.insert(dir.to_path_buf());
+                }
+            }
+        }
+        self.listings.insert(dir.to_path_buf(), entries);
+    }
+
+    pub fn side_panel(&mut self, ctx: &egui::Context) -> Vec<ExplorerAction> {
+        self.poll_events();
+        let mut actions = Vec::new();
+        let Some(root) = self.root.clone() else {
+            egui::SidePanel::left("explorer_panel")
+                .resizable(true)
+                .default_width(220.0)
+                .show_animated(ctx, self.open, |ui| {
+                    ui.heading("Explorer");
+                    ui.separator();
+                    ui.label("没有打开的文件夹");
+                    if ui.button("Open Folder…").clicked() {
+                        if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                            self.set_root(ui.ctx(), dir);
+                        }
+                    }
+                });
+            return actions;
+        };
+
+        egui::SidePanel::left("explorer_panel")
+            .resizable(true)
+            .default_width(220.0)
+            .show_animated(ctx, self.open, |ui| {
+                ui.horizontal(|ui| {
+                    let name = root.file_name().map_or_else(
+                        || root.display().to_string(),
+                        |n| n.to_string_lossy().into_owned(),
+                    );
+                    let heading = ui
+                        .add(
+                            egui::Label::new(egui::RichText::new(name).heading())
+                                .sense(egui::Sense::click()),
+                        )
+                        .on_hover_text(root.display().to_string());
+                    // 拖到标题上表示移动到工作区根目录
+                    if let Some(from) = heading.dnd_release_payload::<PathBuf>() {
+                        actions.extend(move_into(&from, &root));
+                    }
+                    heading.context_menu(|ui| self.dir_menu(ui, &root));
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("⟳").on_hover_text("Refresh").clicked() {
+                            self.listings.clear();
+                        }
+                        if ui.small_button("🗀").on_hover_text("New Folder…").clicked() {
+                            self.ask(Prompt::NewFolder(root.clone()), String::new());
+                        }
+                        if ui.small_button("🗋").on_hover_text("New File…").clicked() {
+                            self.ask(Prompt::NewFile(root.clone()), String::new());
+                        }
+                    });
+                });
+                ui.separator();
+                egui::ScrollArea::both()
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        self.dir_ui(ui, &root, &mut actions);
+                        // 空白处的右键菜单作用于根目录
+                        let rest = ui.allocate_response(ui.available_size(), egui::Sense::click());
+                        rest.context_menu(|ui| self.dir_menu(ui, &root));
+                    });
+            });
+
+        self.prompt_window(ctx, &mut actions);
+        actions
+    }
+
+    /// 显示目录下的所有项，展开的子目录递归显示
+    fn dir_ui(&mut self, ui: &mut egui::Ui, dir: &Path, actions: &mut Vec<ExplorerAction>) {
+        self.load(dir);
+        let entries: Vec<(String, PathBuf, bool)> = self.listings[dir]
+            .iter()
+            .map(|e| (e.name.clone(), e.path.clone(), e.is_dir))
+            .collect();
+        for (name, path, is_dir) in entries {
+            let expanded = is_dir && self.expanded.contains(&path);
+            let label = if is_dir {
+                format!(
+                    "{} {} {}",
+                    if expanded { "⏷" } else { "⏵" },
+                    folder_icon(expanded),
+                    name
+                )
+            } else {
+                format!("   {} {}", file_icon(&path), name)
+            };
+            let selected = self.selected.as_ref() == Some(&path);
+            let response = ui
+                .selectable_label(selected, label)
+                .interact(egui::Sense::click_and_drag());
+
+            // 拖动文件或文件夹到另一个文件夹上即移动
+            response.dnd_set_drag_payload(path.clone());
+            if is_dir {
+                if let Some(from) = response.dnd_release_payload::<PathBuf>() {
+                    actions.extend(move_into(&from, &path));
+                }
+            }
+
+            if response.clicked() {
+                self.selected = Some(path.clone());
+                if is_dir {
+                    if !self.expanded.remove(&path) {
+                        self.expanded.insert(path.clone());
+                    }
+                } else {
+                    actions.push(ExplorerAction::Open(path.clone()));
+                }
+            }
+            response.context_menu(|ui| {
+                if is_dir {
+                    self.dir_menu(ui, &path);
+                    ui.separator();
+                } else if ui.button("Open").clicked() {
+                    actions.push(ExplorerAction::Open(path.clone()));
+                    ui.close_menu();
+                }
+                if ui.button("Rename…").clicked() {
+                    self.ask(Prompt::Rename(path.clone()), name.clone());
+                    ui.close_menu();
+                }
+                if ui.button("Delete…").clicked() {
+                    self.ask(Prompt::Delete(path.clone()), String::new());
+                    ui.close_menu();
+                }
+            });
+
+            if expanded {
+                ui.indent(&path, |ui| self.dir_ui(ui, &path, actions));
+            }
+        }
+    }
+
+    fn dir_menu(&mut self, ui: &mut egui::Ui, dir: &Path) {
+        if ui.button("New File…").clicked() {
+            self.ask(Prompt::NewFile(dir.to_path_buf()), String::new());
+            ui.close_menu();
+        }
+        if ui.button("New Folder…").clicked() {
+            self.ask(Prompt::NewFolder(dir.to_path_buf()), String::new());
+            ui.close_menu();
+        }
+    }
+
+    fn ask(&mut self, prompt: Prompt, name: String) {
+        self.prompt = Some((prompt, name));
+        self.focus_prompt = true;
+    }
+
+    /// 新建 / 重命名时输入名字，删除前确认
+    fn prompt_window(&mut self, ctx: &egui::Context, actions: &mut Vec<ExplorerAction>) {
+        let Some((prompt, name)) = &mut self.prompt else {
+            return;
+        };
+        let title = match prompt {
+            Prompt::NewFile(_) => "New File",
+            Prompt::NewFolder(_) => "New Folder",
+            Prompt::Rename(_) => "Rename",
+            Prompt::Delete(_) => "Delete",
+        };
+        let mut confirmed = false;
+        let mut cancelled = false;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                if let Prompt::Delete(path) = prompt {
+                    ui.label(format!(
+                        "确定要删除 {} 吗？此操作无法撤销。",
+                        path.display()
+                    ));
+                } else {
+                    let edit = ui.text_edit_singleline(name);
+                    if self.focus_prompt {
+                        edit.request_focus();
+                        self.focus_prompt = false;
+                    }
+                    confirmed |= edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                }
+                ui.horizontal(|ui| {
+                    confirmed |= ui.button("OK").clicked();
+                    cancelled |= ui.button("Cancel").clicked();
+                });
+            });
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            cancelled = true;
+        }
+        if cancelled {
+            self.prompt = None;
+            return;
+        }
+        if !confirmed {
+            return;
+        }
+        let Some((prompt, name)) = self.prompt.take() else {
+            return;
+        };
+        let name = name.trim();
+        let needs_name = !matches!(prompt, Prompt::Delete(_));
+        if needs_name && (name.is_empty() || name.contains(['/', '\\'])) {
+            actions.push(ExplorerAction::Error(format!("无效的文件名：{}", name)));
+            return;
+        }
+        let result = match prompt {
+            Prompt::NewFile(dir) => {
+                let path = dir.join(name);
+                create_file(&path).map(|()| {
+                    self.expanded.insert(dir);
+                    actions.push(ExplorerAction::Open(path));
+                })
+            }
+            Prompt::NewFolder(dir) => fs::create_dir(dir.join(name)).map(|()| {
+                self.expanded.insert(dir);
+            }),
+            Prompt::Rename(from) => {
+                let to = from.with_file_name(name);
+                rename(&from, &to).map(|()| actions.push(ExplorerAction::Moved { from, to }))
+            }
+            Prompt::Delete(path) => {
+                if path.is_dir() {
+                    fs::remove_dir_all(&path)
+                } else {
+                    fs::remove_file(&path)
+                }
+            }
+        };
+        if let Err(err) = result {
+            actions.push(ExplorerAction::Error(format!("{} 失败：{}", title, err)));
+        }
+        // 不依赖监听，立即刷新
+        self.listings.clear();
+    }
+}
+
+/// 读取目录，文件夹排在前面，按名字排序（不区分大小写）
+fn read_entries(dir: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = fs::read_dir(dir)?
+        .flatten()
+        .filter(|e| e.file_name() != ".git")
+        .map(|e| {
+            let path = e.path();
+            Entry {
+                name: e.file_name().to_string_lossy().into_owned(),
+                is_dir: path.is_dir(),
+                path,
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        b.is_dir
+            .cmp(&a.is_dir)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    Ok(entries)
+}
+
+fn create_file(path: &Path) -> io::Result<()> {
+    // create_new：已经存在时报错，不覆盖
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map(|_| ())
+}
+
+fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    if to.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} 已存在", to.display()),
+        ));
+    }
+    fs::rename(from, to)
+}
+
+/// 把 `from` 移动到文件夹 `dir` 中
+fn move_into(from: &Path, dir: &Path) -> Option<ExplorerAction> {
+    let to = dir.join(from.file_name()?);
+    // 拖到自己所在的文件夹，或者把文件夹拖进它自己
+    if to == from || dir.starts_with(from) {
+        return None;
+    }
+    Some(match rename(from, &to) {
+        Ok(()) => ExplorerAction::Moved {
+            from: from.to_path_buf(),
+            to,
+        },
+        Err(err) => ExplorerAction::Error(format!("移动失败：{}\n{}", from.display(), err)),
+    })
+}
+
+fn folder_icon(expanded: bool) -> &'static str {
+    if expanded { "📂" } else { "📁" }
+}
+
+fn file_icon(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "rs" => "🦀",
+        "toml" | "ini" | "cfg" | "yaml" | "yml" => "⚙",
+        "md" | "txt" => "📝",
+        "json" => "📋",
+        "sh" | "bash" | "zsh" => "🖥",
+        "png" | "jpg" | "jpeg" | "gif" | "bmp" | "svg" | "ico" => "🖼",
+        "zip" | "gz" | "tar" | "7z" | "rar" => "📦",
+        "lock" => "🔒",
+        _ => "📄",
+    }
+}
@@ -0,0 +1,192 @@
+use crate::document::looks_binary;
+use eframe::egui;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// 拖入文件夹时最多打开的文件数，避免误拖一个大目录卡死
+pub const MAX_FOLDER_FILES: usize = 64;
+// 超过上限后继续数没有打开的文件，数到这么多就停下
+const MAX_COUNTED: usize = 10_000;
+// 判断是否为二进制文件时读取的字节数
+const SNIFF_LEN: usize = 8 * 1024;
+
+/// 展开拖入的路径得到的文件
+pub struct Expanded {
+    pub files: Vec<PathBuf>,
+    /// 超过 `MAX_FOLDER_FILES` 没有打开的文件数，最多数到 `MAX_COUNTED`
+    pub skipped: usize,
+}
+
+impl Expanded {
+    /// 预览中说明有多少文件没有打开
+    fn skipped_text(&self) -> Option<String> {
+        match self.skipped {
+            0 => None,
+            MAX_COUNTED.. => Some(format!("{}+ more not opened", MAX_COUNTED)),
+            n => Some(format!("{} more not opened", n)),
+        }
+    }
+}
+
+/// 展开拖入的路径：文件原样返回，文件夹递归收集其中的文件（跳过隐藏文件和目录）。
+/// 符号链接指回上层目录时每个目录只进入一次。
+pub fn expand_path(path: &Path) -> Expanded {
+    let mut expanded = Expanded {
+        files: Vec::new(),
+        skipped: 0,
+    };
+    if !path.is_dir() {
+        expanded.files.push(path.to_path_buf());
+        return expanded;
+    }
+    let mut visited = HashSet::new();
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let is_new = fs::canonicalize(&dir).is_ok_and(|real| visited.insert(real));
+        if !is_new {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+        entries.sort();
+        for entry in entries {
+            let hidden = entry
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if hidden {
+                continue;
+            }
+            if entry.is_dir() {
+                dirs.push(entry);
+            } else if expanded.files.len() < MAX_FOLDER_FILES {
+                expanded.files.push(entry);
+            } else {
+                expanded.skipped += 1;
+                if expanded.skipped >= MAX_COUNTED {
+                    return expanded;
+                }
+            }
+        }
+    }
+    expanded
+}
+
+/// 只读文件开头的一小段，判断能否作为文本打开；不能时返回原因。
+pub fn check_file(path: &Path) -> Result<(), String> {
+    let mut file = fs::File::open(path).map_err(|err| err.to_string())?;
+    let mut head = vec![0; SNIFF_LEN];
+    let len = file.read(&mut head).map_err(|err| err.to_string())?;
+    if looks_binary(&head[..len]) {
+        return Err("二进制文件".to_owned());
+    }
+    Ok(())
+}
+
+/// 悬停预览的缓存：只有悬停的文件列表变化时才重新检查磁盘。
+#[derive(Default)]
+pub struct DropPreview {
+    paths: Vec<Option<PathBuf>>,
+    text: String,
+}
+
+impl DropPreview {
+    fn update(&mut self, files: &[egui::HoveredFile]) {
+        let paths: Vec<_> = files.iter().map(|f| f.path.clone()).collect();
+        if paths == self.paths && !self.text.is_empty() {
+            return;
+        }
+
+        let mut opened = Vec::new();
+        let mut rejected = Vec::new();
+        for file in files {
+            match &file.path {
+                Some(path) if path.is_dir() => {
+                    let expanded = expand_path(path);
+                    let mut line = format!("📁 {} ({} files", path.display(), expanded.files.len());
+                    if let Some(skipped) = expanded.skipped_text() {
+                        line += &format!(", {}", skipped);
+                    }
+                    opened.push(line + ")");
+                }
+                Some(path) => match check_file(path) {
+                    Ok(()) => opened.push(format!("✔ {}", path.display())),
+                    Err(reason) => rejected.push(format!("✖ {} — {}", path.display(), reason)),
+                },
+                // 没有路径（例如 Web 平台）时松开后才能拿到内容
+                None if !file.mime.is_empty() => opened.push(format!("? {}", file.mime)),
+                None => opened.push("? ???".to_owned()),
+            }
+        }
+
+        let mut text = String::new();
+        if !opened.is_empty() {
+            text += "Will open:\n";
+            text += &opened.join("\n");
+        }
+        if !rejected.is_empty() {
+            if !text.is_empty() {
+                text += "\n\n";
+            }
+            text += "Cannot open:\n";
+            text += &rejected.join("\n");
+        }
+        self.paths = paths;
+        self.text = text;
+    }
+}
+
+// Preview hovering files:
+pub fn preview_files_being_dropped(ctx: &egui::Context, preview: &mut DropPreview) {
+    use egui::{Align2, Color32, Id, LayerId, Order, TextStyle};
+
+    let hovered = ctx.input(|i| i.raw.hovered_files.clone());
+    if hovered.is_empty() {
+        preview.paths.clear();
+        preview.text.clear();
+        return;
+    }
+    preview.update(&hovered);
+
+    let painter = ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("file_drop_target")));
+
+    let screen_rect = ctx.screen_rect();
+    painter.rect_filled(screen_rect, 0.0, Color32::from_black_alpha(192));
+    painter.text(
+        screen_rect.center(),
+        Align2::CENTER_CENTER,
+        &preview.text,
+        TextStyle::Heading.resolve(&ctx.style()),
+        Color32::WHITE,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn expand_path_stops_at_limit_and_skips_symlink_cycles() {
+        let root = std::env::temp_dir().join(format!("editor-rs-drop-{}", std::process::id()));
+        let dir = root.join("sub");
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..MAX_FOLDER_FILES + 3 {
+            fs::write(dir.join(format!("{:03}.txt", i)), "").unwrap();
+        }
+        // 指回上层目录
+        std::os::unix::fs::symlink(&root, dir.join("loop")).unwrap();
+
+        let expanded = expand_path(&root);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(expanded.files.len(), MAX_FOLDER_FILES);
+        assert_eq!(expanded.skipped, 3);
+        assert_eq!(
+            expanded.skipped_text().as_deref(),
+            Some("3 more not opened")
+        );
+    }
+}
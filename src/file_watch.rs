@@ -0,0 +1,367 @@
+use crate::diff::{DiffLine, MAX_DIFF_LINES, diff_lines, diff_view};
+use crate::document::{DiskChange, Document};
+use crate::encoding::TextEncoding;
+use crate::history::{EditKind, EditOp};
+use crate::line_ending;
+use eframe::egui;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// 监听打开的文件所在的目录，发现文件被其他程序修改。
+///
+/// 监听目录而不是文件本身：很多程序保存时先写临时文件再改名替换，
+/// 直接监听文件会在第一次替换后失效。
+#[derive(Default)]
+pub struct FileWatcher {
+    watcher: Option<RecommendedWatcher>,
+    events: Option<Receiver<notify::Result<notify::Event>>>,
+    watched: HashSet<PathBuf>,
+}
+
+impl FileWatcher {
+    /// 让监听的目录与打开的文件一致：新打开的加上，不再需要的去掉
+    pub fn sync<'a>(&mut self, ctx: &egui::Context, paths: impl IntoIterator<Item = &'a Path>) {
+        let wanted: HashSet<PathBuf> = paths
+            .into_iter()
+            .filter_map(|p| p.parent().map(Path::to_path_buf))
+            .collect();
+        if wanted == self.watched {
+            return;
+        }
+        if self.watcher.is_none() {
+            let (sender, receiver) = mpsc::channel();
+            let ctx = ctx.clone();
+            let watcher = notify::recommended_watcher(move |event| {
+                let _ = sender.send(event);
+                ctx.request_repaint();
+            });
+            match watcher {
+                Ok(watcher) => {
+                    self.watcher = Some(watcher);
+                    self.events = Some(receiver);
+                }
+                Err(err) => {
+                    log::warn!("无法监听文件变化：{}", err);
+                    // 以后不再尝试，避免每帧报错
+                    self.watched = wanted;
+                    return;
+                }
+            }
+        }
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        for dir in self.watched.difference(&wanted) {
+            let _ = watcher.unwatch(dir);
+        }
+        for dir in wanted.difference(&self.watched) {
+            if let Err(err) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                log::warn!("无法监听 {}：{}", dir.display(), err);
+            }
+        }
+        self.watched = wanted;
+    }
+
+    /// 取出发生了变化的文件
+    pub fn poll(&self) -> HashSet<PathBuf> {
+        let Some(events) = &self.events else {
+            return HashSet::new();
+        };
+        events
+            .try_iter()
+            .flatten()
+            .filter(|event| !event.kind.is_access())
+            .flat_map(|event| event.paths)
+            .collect()
+    }
+}
+
+/// 文件在磁盘上被修改后，横幅中的选择
+pub enum DiskAction {
+    Reload,
+    KeepMine,
+    Compare,
+}
+
+/// 显示在编辑区上方的提示条
+pub fn disk_change_banner(ui: &mut egui::Ui, change: DiskChange) -> Option<DiskAction> {
+    let mut action = None;
+    let fill = ui.visuals().warn_fg_color.gamma_multiply(0.15);
+    egui::Frame::NONE
+        .fill(fill)
+        .inner_margin(egui::Margin::symmetric(8, 4))
+        .show(ui, |ui| {
+            ui.horizontal(|ui| match change {
+                DiskChange::Modified => {
+                    ui.label("⚠ 文件已在磁盘上被修改，编辑器中还有未保存的修改。");
+                    if ui
+                        .button("Reload")
+                        .on_hover_text("放弃编辑器中的修改")
+                        .clicked()
+                    {
+                        action = Some(DiskAction::Reload);
+                    }
+                    if ui
+                        .button("Keep Mine")
+                        .on_hover_text("保留编辑器中的内容，保存时覆盖磁盘上的文件")
+                        .clicked()
+                    {
+                        action = Some(DiskAction::KeepMine);
+                    }
+                    if ui.button("Compare").clicked() {
+                        action = Some(DiskAction::Compare);
+                    }
+                }
+                DiskChange::Deleted => {
+                    ui.label("⚠ 文件已在磁盘上被删除，保存时会重新创建。");
+                    if ui.button("OK").clicked() {
+                        action = Some(DiskAction::KeepMine);
+                    }
+                }
+            });
+        });
+    action
+}
+
+/// 磁盘上的文件和编辑器中的内容的对比，可以逐处选择采用磁盘上的内容
+pub struct Comparison {
+    pub title: String,
+    /// 比较的文档
+    pub doc: usize,
+    hunks: Vec<MergeHunk>,
+    // 比较时缓冲区的版本，之后又编辑过就不能再按这些差异合并
+    version: u64,
+}
+
+/// 一处差异
+struct MergeHunk {
+    diff: Vec<DiffLine>,
+    /// 在编辑器内容中的字符下标
+    start: usize,
+    mine: String,
+    disk: String,
+    take: bool,
+}
+
+/// 对比窗口中的选择
+pub enum ComparisonAction {
+    Close,
+    /// 把勾选的差异换成磁盘上的内容
+    Merge,
+}
+
+impl Comparison {
+    /// 读取磁盘上的文件，和编辑器中的内容逐行比较
+    pub fn with_disk(doc: usize, document: &Document) -> io::Result<Self> {
+        let Some(path) = &document.path else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "文件还没有保存过"));
+        };
+        let bytes = fs::read(path)?;
+        // 先按文档的编码解码：可能是用户手动选择的，重新检测不一定能猜对
+        let disk = document
+            .encoding
+            .decode(&bytes)
+            .or_else(|| TextEncoding::detect(&bytes).decode(&bytes))
+            .unwrap_or_else(|| String::from_utf8_lossy(&bytes).into_owned());
+        Ok(Self {
+            title: document.title(),
+            doc,
+            hunks: merge_hunks(&line_ending::normalize(&disk), &document.buffer.to_string()),
+            version: document.buffer.version(),
+        })
+    }
+
+    /// 把勾选的差异换成磁盘上的内容，作为一个撤销步骤记录。
+    /// 比较之后编辑器中的内容又修改过时返回错误
+    pub fn merge(&self, document: &mut Document) -> Result<(), String> {
+        if document.buffer.version() != self.version {
+            return Err("比较之后编辑器中的内容又修改过，请重新比较".to_owned());
+        }
+        let mut ops = Vec::new();
+        let mut delta: isize = 0;
+        for hunk in self.hunks.iter().filter(|hunk| hunk.take) {
+            ops.push(EditOp {
+                start: (hunk.start as isize + delta) as usize,
+                removed: hunk.mine.clone(),
+                inserted: hunk.disk.clone(),
+            });
+            delta += hunk.disk.chars().count() as isize - hunk.mine.chars().count() as isize;
+        }
+        if !ops.is_empty() {
+            document.apply_edits(EditKind::Other, ops);
+        }
+        // 已经处理过磁盘上的修改，保存时不再提示
+        document.keep_mine();
+        Ok(())
+    }
+}
+
+/// 按行比较 `disk` → `mine`。每一行带着自己的换行符，拼起来就是原来的内容
+fn merge_hunks(disk: &str, mine: &str) -> Vec<MergeHunk> {
+    let disk: Vec<&str> = disk.split_inclusive('\n').collect();
+    let mine: Vec<&str> = mine.split_inclusive('\n').collect();
+    let chars = |lines: &[&str]| lines.iter().map(|l| l.chars().count()).sum::<usize>();
+    let text = |lines: &[&str]| {
+        lines
+            .iter()
+            .map(|l| l.strip_suffix('\n').unwrap_or(l).to_owned())
+            .collect::<Vec<_>>()
+    };
+    let (mut start, mut line) = (0, 0);
+    diff_lines(&disk, &mine)
+        .into_iter()
+        .map(|hunk| {
+            start += chars(&mine[line..hunk.new.start]);
+            line = hunk.new.start;
+            let mut diff = vec![DiffLine::Header(hunk.old.start + 1, hunk.new.start + 1)];
+            diff.extend(
+                text(&disk[hunk.old.clone()])
+                    .into_iter()
+                    .map(DiffLine::Removed),
+            );
+            diff.extend(
+                text(&mine[hunk.new.clone()])
+                    .into_iter()
+                    .map(DiffLine::Added),
+            );
+            MergeHunk {
+                diff,
+                start,
+                mine: mine[hunk.new].concat(),
+                disk: disk[hunk.old].concat(),
+                take: false,
+            }
+        })
+        .collect()
+}
+
+/// 显示对比窗口
+pub fn comparison_window(
+    ctx: &egui::Context,
+    comparison: &mut Comparison,
+) -> Option<ComparisonAction> {
+    let mut open = true;
+    let mut action = None;
+    egui::Window::new(format!("比较：{}", comparison.title))
+        .open(&mut open)
+        .default_size([640.0, 480.0])
+        .show(ctx, |ui| {
+            ui.weak("「-」磁盘上的内容，「+」编辑器中的内容");
+            let hunks = &mut comparison.hunks;
+            ui.horizontal(|ui| {
+                let any = hunks.iter().any(|hunk| hunk.take);
+                if ui
+                    .add_enabled(any, egui::Button::new("Take Selected"))
+                    .on_hover_text("把勾选的差异换成磁盘上的内容，可以一次撤销")
+                    .clicked()
+                {
+                    action = Some(ComparisonAction::Merge);
+                }
+                if ui
+                    .add_enabled(!hunks.is_empty(), egui::Button::new("Take All"))
+                    .on_hover_text("把所有差异换成磁盘上的内容，可以一次撤销")
+                    .clicked()
+                {
+                    hunks.iter_mut().for_each(|hunk| hunk.take = true);
+                    action = Some(ComparisonAction::Merge);
+                }
+            });
+            ui.separator();
+            egui::ScrollArea::both().show(ui, |ui| {
+                if hunks.is_empty() {
+                    ui.weak("内容相同");
+                    return;
+                }
+                let mut shown = 0;
+                for hunk in hunks.iter_mut() {
+                    if shown >= MAX_DIFF_LINES {
+                        ui.weak("……差异太多，后面的没有显示");
+                        break;
+                    }
+                    ui.checkbox(&mut hunk.take, "采用磁盘上的内容");
+                    diff_view(ui, &hunk.diff);
+                    shown += hunk.diff.len();
+                }
+            });
+        });
+    if !open {
+        action = Some(ComparisonAction::Close);
+    }
+    action
+}
+
+pub enum OverwriteChoice {
+    Overwrite,
+    Compare,
+    Cancel,
+}
+
+/// 保存时发现磁盘上的文件更新，询问是否覆盖
+pub fn overwrite_dialog(ctx: &egui::Context, title: &str) -> Option<OverwriteChoice> {
+    let mut choice = None;
+    egui::Window::new("覆盖磁盘上的修改？")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{} 在打开后被其他程序修改过。保存会覆盖这些修改。",
+                title
+            ));
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if ui.button("Overwrite").clicked() {
+                    choice = Some(OverwriteChoice::Overwrite);
+                }
+                if ui.button("Compare").clicked() {
+                    choice = Some(OverwriteChoice::Compare);
+                }
+                if ui.button("Cancel").clicked() {
+                    choice = Some(OverwriteChoice::Cancel);
+                }
+            });
+        });
+    choice
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_takes_selected_disk_hunks_as_one_undo_step() {
+        let dir = std::env::temp_dir().join(format!("editor-rs-merge-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.txt");
+        fs::write(&path, "a\r\nB\r\nc\r\nd\r\nE\r\n").unwrap();
+        let mut document = Document::open(&path).unwrap();
+        document.replace_text("a\nbb\nc\nd\ne\nf");
+
+        let mut comparison = Comparison::with_disk(1, &document).unwrap();
+        assert_eq!(comparison.hunks.len(), 2);
+        assert_eq!(comparison.hunks[0].start, 2);
+        assert_eq!(comparison.hunks[1].start, 9);
+        comparison
+            .hunks
+            .iter_mut()
+            .for_each(|hunk| hunk.take = true);
+        comparison.merge(&mut document).unwrap();
+        assert_eq!(document.buffer.to_string(), "a\nB\nc\nd\nE\n");
+        assert_eq!(document.check_disk(), None);
+
+        document.undo();
+        assert_eq!(document.buffer.to_string(), "a\nbb\nc\nd\ne\nf");
+        // 比较之后编辑过，不能再合并
+        assert!(comparison.merge(&mut document).is_err());
+
+        let mut comparison = Comparison::with_disk(1, &document).unwrap();
+        comparison.hunks[1].take = true;
+        comparison.merge(&mut document).unwrap();
+        assert_eq!(document.buffer.to_string(), "a\nbb\nc\nd\nE\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,462 @@
+use crate::document::Document;
+use crate::history::{EditKind, EditOp};
+use eframe::egui::{self, Color32, Key, TextEdit, text::CCursor, text::CCursorRange};
+use regex::{Captures, Regex, RegexBuilder};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+// 匹配数量上限，防止在超大文件里搜一个字符时卡住（全部替换不受限制）
+const MAX_MATCHES: usize = 100_000;
+// 编辑文档时重新搜索的间隔，连续输入时不必每个字符都搜一遍全文
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// 查找 / 替换栏（Ctrl+F / Ctrl+H），停靠在中央编辑区上方。
+#[derive(Default)]
+pub struct FindBar {
+    pub open: bool,
+    pub show_replace: bool,
+    pub query: String,
+    pub replacement: String,
+    pub use_regex: bool,
+    pub match_case: bool,
+    pub whole_word: bool,
+    // 当前文档中的所有匹配（字符范围，按位置排序）
+    matches: Vec<Range<usize>>,
+    // 匹配超过 MAX_MATCHES，只记下了前面的
+    truncated: bool,
+    current: Option<usize>,
+    error: Option<String>,
+    // 上次搜索时的 (标签 id, 缓冲区版本, 查询条件)，变化时重新搜索
+    searched: Option<SearchKey>,
+    // 上次搜索之后文档第一次被修改的时间
+    edited_at: Option<Instant>,
+    focus_query: bool,
+}
+
+type SearchKey = (usize, u64, String, bool, bool, bool);
+
+/// 查找栏上的操作，需要修改文档，由调用方在持有文档时执行
+enum FindAction {
+    Next,
+    Previous,
+    ReplaceOne,
+    ReplaceAll,
+}
+
+impl FindBar {
+    /// 打开查找栏，选中的单行文本作为查询内容
+    pub fn open(&mut self, document: Option<&Document>, replace: bool) {
+        self.open = true;
+        self.show_replace = replace;
+        self.focus_query = true;
+        if let Some(selected) = document.and_then(selected_text) {
+            if !selected.contains('\n') {
+                self.query = selected;
+            }
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.matches.clear();
+        self.current = None;
+        self.searched = None;
+    }
+
+    /// 当前文档中所有匹配的字符范围，供编辑器高亮
+    pub fn matches(&self) -> &[Range<usize>] {
+        if self.open { &self.matches } else { &[] }
+    }
+
+    pub fn current_match(&self) -> Option<Range<usize>> {
+        self.current.and_then(|i| self.matches.get(i).cloned())
+    }
+
+    /// 根据查询条件构造正则；普通文本模式下先转义
+    pub fn build_regex(&self) -> Result<Regex, regex::Error> {
+        build_regex(
+            &self.query,
+            self.use_regex,
+            self.match_case,
+            self.whole_word,
+        )
+    }
+
+    fn search_key(&self, doc_id: usize, version: u64) -> SearchKey {
+        (
+            doc_id,
+            version,
+            self.query.clone(),
+            self.use_regex,
+            self.match_case,
+            self.whole_word,
+        )
+    }
+
+    /// 查询条件或文档内容变化时重新搜索
+    pub fn update_matches(&mut self, doc_id: usize, document: &Document) {
+        let key = self.search_key(doc_id, document.buffer.version());
+        if self.searched.as_ref() == Some(&key) {
+            return;
+        }
+        self.searched = Some(key);
+        self.edited_at = None;
+        self.matches.clear();
+        self.truncated = false;
+        self.error = None;
+        if self.query.is_empty() {
+            self.current = None;
+            return;
+        }
+        match self.build_regex() {
+            Ok(regex) => {
+                let text = document.buffer.to_string();
+                self.matches = regex
+                    .find_iter(&text)
+                    .filter(|m| !m.is_empty())
+                    .take(MAX_MATCHES + 1)
+                    .map(|m| {
+                        document.buffer.byte_to_char(m.start())
+                            ..document.buffer.byte_to_char(m.end())
+                    })
+                    .collect();
+                self.truncated = self.matches.len() > MAX_MATCHES;
+                self.matches.truncate(MAX_MATCHES);
+            }
+            Err(err) => self.error = Some(err.to_string()),
+        }
+        // 当前匹配保持在光标附近
+        let cursor = document
+            .cursor
+            .map_or(0, |c| c.primary.index.min(c.secondary.index));
+        self.current = (!self.matches.is_empty())
+            .then(|| self.matches.partition_point(|m| m.start < cursor) % self.matches.len());
+    }
+
+    /// 只是文档被编辑时，每隔 `DEBOUNCE` 才重新搜索一次，期间沿用上次的匹配
+    fn update_matches_debounced(
+        &mut self,
+        ctx: &egui::Context,
+        doc_id: usize,
+        document: &Document,
+    ) {
+        let edited = self.searched.as_ref().is_some_and(|searched| {
+            searched.1 != document.buffer.version()
+                && *searched == self.search_key(doc_id, searched.1)
+        });
+        if edited {
+            let elapsed = self.edited_at.get_or_insert_with(Instant::now).elapsed();
+            if elapsed < DEBOUNCE {
+                // 旧的匹配可能已经超出了变短的文档
+                let len = document.buffer.len_chars();
+                self.matches.retain(|m| m.end <= len);
+                self.current = self.current.filter(|&i| i < self.matches.len());
+                ctx.request_repaint_after(DEBOUNCE - elapsed);
+                return;
+            }
+        }
+        self.update_matches(doc_id, document);
+    }
+
+    /// 渲染查找栏，并对文档执行导航 / 替换
+    pub fn ui(&mut self, ui: &mut egui::Ui, doc_id: usize, document: &mut Document) {
+        self.update_matches_debounced(ui.ctx(), doc_id, document);
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            let query = ui.add(
+                TextEdit::singleline(&mut self.query)
+                    .hint_text("Find")
+                    .desired_width(240.0),
+            );
+            if self.focus_query {
+                query.request_focus();
+                self.focus_query = false;
+            }
+            if query.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                action = Some(if ui.input(|i| i.modifiers.shift) {
+                    FindAction::Previous
+                } else {
+                    FindAction::Next
+                });
+                query.request_focus();
+            }
+
+            ui.toggle_value(&mut self.match_case, "Aa")
+                .on_hover_text("Match Case");
+            ui.toggle_value(&mut self.whole_word, "ab")
+                .on_hover_text("Match Whole Word");
+            ui.toggle_value(&mut self.use_regex, ".*")
+                .on_hover_text("Use Regular Expression");
+
+            if ui
+                .button("↑")
+                .on_hover_text("Previous Match (Shift+Enter)")
+                .clicked()
+            {
+                action = Some(FindAction::Previous);
+            }
+            if ui.button("↓").on_hover_text("Next Match (Enter)").clicked() {
+                action = Some(FindAction::Next);
+            }
+
+            // 匹配数量 / 错误提示
+            match (&self.error, self.current) {
+                (Some(err), _) => {
+                    ui.colored_label(Color32::RED, "Invalid regex")
+                        .on_hover_text(err);
+                }
+                (None, Some(current)) => {
+                    let more = if self.truncated { "+" } else { "" };
+                    ui.label(format!("{} of {}{}", current + 1, self.matches.len(), more));
+                }
+                (None, None) if !self.query.is_empty() => {
+                    ui.label("No results");
+                }
+                _ => {}
+            }
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("×").on_hover_text("Close (Escape)").clicked() {
+                    self.open = false;
+                }
+                let toggle = if self.show_replace { "⏶" } else { "⏷" };
+                if ui.button(toggle).on_hover_text("Toggle Replace").clicked() {
+                    self.show_replace = !self.show_replace;
+                }
+            });
+        });
+
+        if self.show_replace {
+            ui.horizontal(|ui| {
+                ui.add(
+                    TextEdit::singleline(&mut self.replacement)
+                        .hint_text("Replace")
+                        .desired_width(240.0),
+                );
+                let has_matches = !self.matches.is_empty();
+                if ui
+                    .add_enabled(has_matches, egui::Button::new("Replace"))
+                    .clicked()
+                {
+                    action = Some(FindAction::ReplaceOne);
+                }
+                if ui
+                    .add_enabled(has_matches, egui::Button::new("Replace All"))
+                    .clicked()
+                {
+                    action = Some(FindAction::ReplaceAll);
+                }
+            });
+        }
+
+        // Escape 由快捷键表中的 edit.closeFindBar 处理
+        if !self.open {
+            self.close();
+            return;
+        }
+
+        if action.is_some() {
+            // 操作要用最新的匹配
+            self.update_matches(doc_id, document);
+        }
+        match action {
+            Some(FindAction::Next) => self.step(document, true),
+            Some(FindAction::Previous) => self.step(document, false),
+            Some(FindAction::ReplaceOne) => self.replace_one(doc_id, document),
+            Some(FindAction::ReplaceAll) => self.replace_all(doc_id, document),
+            None => {}
+        }
+    }
+
+    /// 跳到下一个 / 上一个匹配：选中它并让编辑器滚动过去
+    pub fn step(&mut self, document: &mut Document, forward: bool) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let len = self.matches.len();
+        let index = match self.current {
+            // 光标正停在当前匹配上时才前进，否则先跳到当前匹配
+            Some(i) if is_selected(document, &self.matches[i]) => {
+                if forward {
+                    (i + 1) % len
+                } else {
+                    (i + len - 1) % len
+                }
+            }
+            Some(i) => i,
+            None => 0,
+        };
+        self.current = Some(index);
+        select(document, self.matches[index].clone());
+    }
+
+    fn replace_one(&mut self, doc_id: usize, document: &mut Document) {
+        let Some(range) = self.current_match() else {
+            return;
+        };
+        // 第一次点击先选中当前匹配，和其他编辑器的行为一致
+        if !is_selected(document, &range) {
+            select(document, range);
+            return;
+        }
+        let Ok(regex) = self.build_regex() else {
+            return;
+        };
+        // 在全文中取捕获组，`^`、`\b` 等仍然能看到匹配前后的内容
+        let text = document.buffer.to_string();
+        let start = document.buffer.char_to_byte(range.start);
+        let inserted = match regex
+            .captures_at(&text, start)
+            .filter(|captures| captures.get(0).is_some_and(|m| m.start() == start))
+        {
+            Some(captures) => self.expand_replacement(&captures),
+            None => self.replacement.clone(),
+        };
+        let matched = document.buffer.slice_to_string(range.clone());
+        let end = range.start + inserted.chars().count();
+        document.cursor = Some(CCursorRange::one(CCursor::new(end)));
+        document.extra_cursors.clear();
+        document.apply_edits(
+            EditKind::Other,
+            vec![EditOp {
+                start: range.start,
+                removed: matched,
+                inserted,
+            }],
+        );
+        self.update_matches(doc_id, document);
+        self.step(document, true);
+    }
+
+    /// 全部替换，作为一个撤销步骤。重新搜索全文，不受 `MAX_MATCHES` 限制
+    fn replace_all(&mut self, doc_id: usize, document: &mut Document) {
+        let Ok(regex) = self.build_regex() else {
+            return;
+        };
+        let text = document.buffer.to_string();
+        let mut ops = Vec::with_capacity(self.matches.len());
+        // 前面的替换会改变后面匹配的位置
+        let mut delta: isize = 0;
+        for captures in regex.captures_iter(&text) {
+            let Some(m) = captures.get(0).filter(|m| !m.is_empty()) else {
+                continue;
+            };
+            let matched = m.as_str().to_owned();
+            let inserted = self.expand_replacement(&captures);
+            let start = (document.buffer.byte_to_char(m.start()) as isize + delta) as usize;
+            delta += inserted.chars().count() as isize - matched.chars().count() as isize;
+            ops.push(EditOp {
+                start,
+                removed: matched,
+                inserted,
+            });
+        }
+        document.apply_edits(EditKind::ReplaceAll, ops);
+        self.update_matches(doc_id, document);
+    }
+
+    /// 正则模式下支持 `$1`、`${name}` 等捕获组引用
+    fn expand_replacement(&self, captures: &Captures) -> String {
+        if !self.use_regex {
+            return self.replacement.clone();
+        }
+        let mut out = String::new();
+        captures.expand(&self.replacement, &mut out);
+        out
+    }
+}
+
+pub fn build_regex(
+    query: &str,
+    use_regex: bool,
+    match_case: bool,
+    whole_word: bool,
+) -> Result<Regex, regex::Error> {
+    let mut pattern = if use_regex {
+        query.to_owned()
+    } else {
+        regex::escape(query)
+    };
+    if whole_word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!match_case)
+        .multi_line(true)
+        .build()
+}
+
+fn selected_text(document: &Document) -> Option<String> {
+    let cursor = document.cursor?;
+    let (a, b) = (cursor.primary.index, cursor.secondary.index);
+    (a != b).then(|| document.buffer.slice_to_string(a.min(b)..a.max(b)))
+}
+
+fn is_selected(document: &Document, range: &Range<usize>) -> bool {
+    document.cursor.is_some_and(|c| {
+        c.primary.index.min(c.secondary.index) == range.start
+            && c.primary.index.max(c.secondary.index) == range.end
+    })
+}
+
+fn select(document: &mut Document, range: Range<usize>) {
+    document.cursor = Some(CCursorRange::two(
+        CCursor::new(range.start),
+        CCursor::new(range.end),
+    ));
+    document.extra_cursors.clear();
+    document.scroll_request = Some(range.start);
+    document.history.break_coalescing();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replace_all(text: &str, query: &str, replacement: &str, use_regex: bool) -> String {
+        let mut document = Document::from_bytes(text.as_bytes()).unwrap();
+        let mut find = FindBar {
+            query: query.to_owned(),
+            replacement: replacement.to_owned(),
+            use_regex,
+            match_case: true,
+            ..FindBar::default()
+        };
+        find.update_matches(0, &document);
+        find.replace_all(0, &mut document);
+        document.buffer.to_string()
+    }
+
+    #[test]
+    fn replace_all_expands_captures() {
+        assert_eq!(
+            replace_all("foo bar\nbaz", r"^(\w+)", "<$1>", true),
+            "<foo> bar\n<baz>"
+        );
+        // 普通文本模式下 $1 原样插入
+        assert_eq!(replace_all("a.b", ".", "$1", false), "a$1b");
+    }
+
+    #[test]
+    fn replacement_captures_see_surrounding_text() {
+        // 单独看匹配到的 "ab" 时 ^a(b) 能匹配，在全文中只有第二个分支能匹配
+        assert_eq!(replace_all("zab", r"^a(b)|a(b)", "[$1|$2]", true), "z[|b]");
+    }
+
+    #[test]
+    fn replace_all_is_not_limited_to_shown_matches() {
+        let text = "a".repeat(MAX_MATCHES + 10);
+        let mut document = Document::from_bytes(text.as_bytes()).unwrap();
+        let mut find = FindBar {
+            query: "a".to_owned(),
+            replacement: "b".to_owned(),
+            ..FindBar::default()
+        };
+        find.update_matches(0, &document);
+        assert_eq!(find.matches().len(), 0); // 查找栏没有打开
+        assert!(find.truncated);
+        find.replace_all(0, &mut document);
+        assert_eq!(document.buffer.to_string(), "b".repeat(MAX_MATCHES + 10));
+    }
+}
@@ -0,0 +1,385 @@
+use crate::document::looks_binary;
+use crate::encoding::TextEncoding;
+use crate::find::build_regex;
+use eframe::egui::{self, Color32, TextFormat, text::LayoutJob};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+// 每个文件最多收集多少条结果，总结果数上限
+const MAX_HITS_PER_FILE: usize = 1_000;
+const MAX_TOTAL_HITS: usize = 20_000;
+// 预览中匹配前后最多保留的字符数
+const PREVIEW_CONTEXT: usize = 60;
+
+/// 一条搜索结果
+pub struct SearchHit {
+    pub line: usize, // 从 0 开始
+    pub col: usize,  // 字符列，从 0 开始
+    pub preview: String,
+    /// 匹配在 `preview` 中的字节范围
+    pub preview_match: std::ops::Range<usize>,
+}
+
+/// 一个文件中的所有结果
+pub struct FileResults {
+    pub path: PathBuf,
+    pub hits: Vec<SearchHit>,
+}
+
+enum SearchMessage {
+    File(FileResults),
+    Done {
+        files_searched: usize,
+        truncated: bool,
+    },
+}
+
+/// 正在后台运行的搜索
+struct SearchJob {
+    receiver: Receiver<SearchMessage>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Drop for SearchJob {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// 点击结果后要打开的位置
+pub struct OpenLocation {
+    pub path: PathBuf,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// 在文件夹中查找（Ctrl+Shift+F），结果显示在右侧面板。
+#[derive(Default)]
+pub struct FindInFiles {
+    pub open: bool,
+    pub root: Option<PathBuf>,
+    pub query: String,
+    pub use_regex: bool,
+    pub match_case: bool,
+    pub whole_word: bool,
+    results: Vec<FileResults>,
+    job: Option<SearchJob>,
+    status: String,
+    focus_query: bool,
+}
+
+impl FindInFiles {
+    pub fn show(&mut self, default_root: Option<&Path>) {
+        self.open = true;
+        self.focus_query = true;
+        if self.root.is_none() {
+            self.root = default_root.map(Path::to_path_buf);
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.job.is_some()
+    }
+
+    /// 在后台线程开始搜索，之前未完成的搜索会被取消
+    pub fn start(&mut self, ctx: &egui::Context) {
+        self.cancel();
+        self.results.clear();
+        let Some(root) = self.root.clone() else {
+            self.status = "请先选择要搜索的文件夹".to_owned();
+            return;
+        };
+        if self.query.is_empty() {
+            self.status.clear();
+            return;
+        }
+        let regex = match build_regex(
+            &self.query,
+            self.use_regex,
+            self.match_case,
+            self.whole_word,
+        ) {
+            Ok(regex) => regex,
+            Err(err) => {
+                self.status = format!("Invalid regex: {}", err);
+                return;
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = cancel.clone();
+        let ctx = ctx.clone();
+        thread::Builder::new()
+            .name("find-in-files".to_owned())
+            .spawn(move || search_worker(&root, &regex, &sender, &worker_cancel, &ctx))
+            .expect("failed to spawn find-in-files thread");
+
+        self.job = Some(SearchJob { receiver, cancel });
+        self.status = "Searching…".to_owned();
+    }
+
+    pub fn cancel(&mut self) {
+        if self.job.take().is_some() {
+            self.status = "Cancelled".to_owned();
+        }
+    }
+
+    /// 取回后台线程发来的结果
+    fn poll(&mut self) {
+        let Some(job) = &self.job else {
+            return;
+        };
+        let mut finished = None;
+        while let Ok(message) = job.receiver.try_recv() {
+            match message {
+                SearchMessage::File(file) => self.results.push(file),
+                SearchMessage::Done {
+                    files_searched,
+                    truncated,
+                } => finished = Some((files_searched, truncated)),
+            }
+        }
+        if let Some((files_searched, truncated)) = finished {
+            self.job = None;
+            let hits: usize = self.results.iter().map(|f| f.hits.len()).sum();
+            self.status = format!(
+                "{} results in {} files ({} files searched){}",
+                hits,
+                self.results.len(),
+                files_searched,
+                if truncated { ", truncated" } else { "" }
+            );
+        }
+    }
+
+    /// 右侧结果面板，返回用户点击的结果位置
+    pub fn side_panel(&mut self, ctx: &egui::Context) -> Option<OpenLocation> {
+        if !self.open {
+            return None;
+        }
+        self.poll();
+        let mut clicked = None;
+
+        egui::SidePanel::right("find_in_files_panel")
+            .resizable(true)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Find in Files");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button("×").clicked() {
+                            self.open = false;
+                            self.cancel();
+                        }
+                    });
+                });
+
+                ui.horizontal(|ui| {
+                    let root = self.root.as_ref().map_or("(未选择文件夹)".to_owned(), |p| {
+                        p.display().to_string()
+                    });
+                    if ui.button("📁").on_hover_text("选择文件夹").clicked() {
+                        if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                            self.root = Some(dir);
+                        }
+                    }
+                    ui.label(root);
+                });
+
+                let mut search = false;
+                ui.horizontal(|ui| {
+                    let query = ui.add(
+                        egui::TextEdit::singleline(&mut self.query)
+                            .hint_text("Search")
+                            .desired_width(ui.available_width() - 100.0),
+                    );
+                    if self.focus_query {
+                        query.request_focus();
+                        self.focus_query = false;
+                    }
+                    search |= query.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    ui.toggle_value(&mut self.match_case, "Aa")
+                        .on_hover_text("Match Case");
+                    ui.toggle_value(&mut self.whole_word, "ab")
+                        .on_hover_text("Match Whole Word");
+                    ui.toggle_value(&mut self.use_regex, ".*")
+                        .on_hover_text("Use Regular Expression");
+                });
+
+                ui.horizontal(|ui| {
+                    if self.is_running() {
+                        ui.spinner();
+                        if ui.button("Cancel").clicked() {
+                            self.cancel();
+                        }
+                    } else if ui.button("Search").clicked() {
+                        search = true;
+                    }
+                    ui.label(&self.status);
+                });
+                if search {
+                    self.start(ui.ctx());
+                }
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        for file in &self.results {
+                            let title = file
+                                .path
+                                .strip_prefix(self.root.as_deref().unwrap_or(Path::new("")))
+                                .unwrap_or(&file.path)
+                                .display()
+                                .to_string();
+                            egui::CollapsingHeader::new(format!("{} ({})", title, file.hits.len()))
+                                .id_salt(&file.path)
+                                .default_open(true)
+                                .show(ui, |ui| {
+                                    for hit in &file.hits {
+                                        let job = preview_job(ui, hit);
+                                        if ui.selectable_label(false, job).clicked() {
+                                            clicked = Some(OpenLocation {
+                                                path: file.path.clone(),
+                                                line: hit.line,
+                                                col: hit.col,
+                                            });
+                                        }
+                                    }
+                                });
+                        }
+                    });
+            });
+        clicked
+    }
+}
+
+/// 结果行：行号 + 预览，匹配部分加背景色
+fn preview_job(ui: &egui::Ui, hit: &SearchHit) -> LayoutJob {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let color = ui.visuals().text_color();
+    let weak = ui.visuals().weak_text_color();
+    let mut job = LayoutJob::default();
+    job.append(
+        &format!("{:>5}  ", hit.line + 1),
+        0.0,
+        TextFormat::simple(font_id.clone(), weak),
+    );
+    let text = &hit.preview;
+    let range = hit.preview_match.clone();
+    job.append(
+        &text[..range.start],
+        0.0,
+        TextFormat::simple(font_id.clone(), color),
+    );
+    job.append(
+        &text[range.clone()],
+        0.0,
+        TextFormat {
+            background: Color32::from_rgba_unmultiplied(255, 200, 0, 90),
+            ..TextFormat::simple(font_id.clone(), color)
+        },
+    );
+    job.append(&text[range.end..], 0.0, TextFormat::simple(font_id, color));
+    job
+}
+
+fn search_worker(
+    root: &Path,
+    regex: &Regex,
+    sender: &Sender<SearchMessage>,
+    cancel: &AtomicBool,
+    ctx: &egui::Context,
+) {
+    let mut files_searched = 0;
+    let mut total_hits = 0;
+    let mut truncated = false;
+
+    // ignore 会自动遵守 .gitignore / .ignore，并跳过隐藏文件
+    for entry in ignore::WalkBuilder::new(root).build().flatten() {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let Ok(bytes) = std::fs::read(entry.path()) else {
+            continue;
+        };
+        if looks_binary(&bytes) {
+            continue;
+        }
+        files_searched += 1;
+        let text = TextEncoding::detect(&bytes)
+            .decode(&bytes)
+            .unwrap_or_else(|| String::from_utf8_lossy(&bytes).into_owned());
+        let hits = search_text(&text, regex);
+        if hits.is_empty() {
+            continue;
+        }
+        total_hits += hits.len();
+        let file = FileResults {
+            path: entry.into_path(),
+            hits,
+        };
+        if sender.send(SearchMessage::File(file)).is_err() {
+            return; // 接收方已经放弃这次搜索
+        }
+        ctx.request_repaint();
+        if total_hits >= MAX_TOTAL_HITS {
+            truncated = true;
+            break;
+        }
+    }
+    let _ = sender.send(SearchMessage::Done {
+        files_searched,
+        truncated,
+    });
+    ctx.request_repaint();
+}
+
+fn search_text(text: &str, regex: &Regex) -> Vec<SearchHit> {
+    let mut hits = Vec::new();
+    for (line_idx, line) in text.lines().enumerate() {
+        for m in regex.find_iter(line) {
+            if m.is_empty() {
+                continue;
+            }
+            hits.push(make_hit(line_idx, line, m.range()));
+            if hits.len() >= MAX_HITS_PER_FILE {
+                return hits;
+            }
+        }
+    }
+    hits
+}
+
+/// 截取匹配附近的一段作为预览，去掉行首缩进
+fn make_hit(line_idx: usize, line: &str, range: std::ops::Range<usize>) -> SearchHit {
+    let col = line[..range.start].chars().count();
+    let indent = line.len() - line.trim_start().len();
+    let mut start = indent.min(range.start);
+    // 匹配前面的内容太长时只保留一部分
+    if line[start..range.start].chars().count() > PREVIEW_CONTEXT {
+        start = line[..range.start]
+            .char_indices()
+            .rev()
+            .nth(PREVIEW_CONTEXT - 1)
+            .map_or(start, |(i, _)| i);
+    }
+    let end = line[range.end..]
+        .char_indices()
+        .nth(PREVIEW_CONTEXT)
+        .map_or(line.len(), |(i, _)| range.end + i);
+    SearchHit {
+        line: line_idx,
+        col,
+        preview: line[start..end].to_owned(),
+        preview_match: range.start - start..range.end - start,
+    }
+}
@@ -0,0 +1,652 @@
+//! 代码折叠：按语言结构（括号、Markdown 标题、TOML 表）或者缩进计算可以折叠的区域，
+//! 记下哪些区域折叠起来了。
+//!
+//! 区域用行号表示，标题行始终可见，折叠时隐藏它后面的 `start + 1..=end` 行。
+//! 内容变化后重新计算区域；连续输入时先按增删的行移动原来的区域，停下来一会儿再重新计算。
+
+use crate::buffer::TextBuffer;
+use crate::highlight::Language;
+use eframe::egui;
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+// 超过这个行数的文件不计算折叠区域，每次编辑后整篇扫描的代价太大
+const MAX_LINES: usize = 100_000;
+// 编辑时重新计算区域的间隔
+const DEBOUNCE: Duration = Duration::from_millis(300);
+// 按缩进计算时一个 Tab 算几列
+const TAB_WIDTH: usize = 4;
+
+/// 一个可以折叠的区域：`start` 是标题行，折叠后隐藏 `start + 1..=end`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FoldRegion {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl FoldRegion {
+    fn contains(&self, line: usize) -> bool {
+        self.start <= line && line <= self.end
+    }
+}
+
+/// 一个文档的折叠区域和折叠状态
+#[derive(Default)]
+pub struct Folds {
+    /// 按标题行排序，每个标题行只有一个区域
+    regions: Vec<FoldRegion>,
+    /// 折叠起来的区域的标题行
+    collapsed: BTreeSet<usize>,
+    /// 区域对应的缓冲区版本、行数和语言
+    version: Option<u64>,
+    line_count: usize,
+    language: Language,
+    /// 区域只是按增删的行移动过，还需要重新计算；最后一次编辑的时间
+    edited_at: Option<Instant>,
+    // 由上面的内容得出，供行号栏和文本视图使用
+    markers: Vec<(usize, bool)>,
+    hidden: Vec<Range<usize>>,
+}
+
+impl Folds {
+    /// 内容或语言变化后立即重新计算区域
+    pub fn update(&mut self, buffer: &TextBuffer, language: Language) {
+        self.shift(buffer);
+        if self.version.is_some() && self.language == language && self.edited_at.is_none() {
+            return;
+        }
+        self.language = language;
+        self.recompute(buffer);
+    }
+
+    /// 每帧绘制之前调用：编辑时先移动原来的区域，停止编辑 `DEBOUNCE` 之后才重新计算
+    pub fn update_debounced(
+        &mut self,
+        ctx: &egui::Context,
+        buffer: &TextBuffer,
+        language: Language,
+    ) {
+        if self.version.is_none() || self.language != language {
+            self.update(buffer, language);
+            return;
+        }
+        self.shift(buffer);
+        let Some(edited_at) = self.edited_at else {
+            return;
+        };
+        let elapsed = edited_at.elapsed();
+        if elapsed < DEBOUNCE {
+            ctx.request_repaint_after(DEBOUNCE - elapsed);
+            return;
+        }
+        self.recompute(buffer);
+    }
+
+    /// 折叠的标题行和区域跟着增删的行移动，被删掉的就不要了
+    fn shift(&mut self, buffer: &TextBuffer) {
+        let version = buffer.version();
+        let Some(old) = self.version.filter(|&old| old != version) else {
+            return;
+        };
+        let line_count = buffer.len_lines();
+        if let Some(change) = buffer.changed_lines_since(old, self.line_count) {
+            let map = |line: usize| {
+                if line <= change.start {
+                    Some(line)
+                } else if line >= change.old_end {
+                    Some(line - change.old_end + change.new_end)
+                } else {
+                    // 改过的几行中还在的
+                    (line < change.new_end).then_some(line)
+                }
+            };
+            self.collapsed = std::mem::take(&mut self.collapsed)
+                .into_iter()
+                .filter_map(map)
+                .collect();
+            self.regions = std::mem::take(&mut self.regions)
+                .into_iter()
+                .filter_map(|r| {
+                    let start = map(r.start)?;
+                    // 区域的最后一行在改过的几行中时，跟着插入的行延长
+                    let end = if r.end < change.start {
+                        r.end
+                    } else {
+                        (r.end + change.new_end)
+                            .saturating_sub(change.old_end)
+                            .max(change.start)
+                    };
+                    (start < end && end < line_count).then_some(FoldRegion { start, end })
+                })
+                .collect();
+        }
+        self.version = Some(version);
+        self.line_count = line_count;
+        self.edited_at = Some(Instant::now());
+        self.rebuild();
+    }
+
+    fn recompute(&mut self, buffer: &TextBuffer) {
+        let line_count = buffer.len_lines();
+        self.regions = if line_count <= MAX_LINES {
+            compute_regions(buffer, self.language)
+        } else {
+            Vec::new()
+        };
+        self.version = Some(buffer.version());
+        self.line_count = line_count;
+        self.edited_at = None;
+        self.rebuild();
+    }
+
+    pub fn regions(&self) -> &[FoldRegion] {
+        &self.regions
+    }
+
+    /// 行号栏上的折叠按钮：(标题行, 是否已折叠)，按行排序
+    pub fn markers(&self) -> &[(usize, bool)] {
+        &self.markers
+    }
+
+    /// 被折叠隐藏的行，按行排序、互不重叠
+    pub fn hidden(&self) -> &[Range<usize>] {
+        &self.hidden
+    }
+
+    pub fn is_hidden(&self, line: usize) -> bool {
+        let i = self.hidden.partition_point(|r| r.end <= line);
+        self.hidden.get(i).is_some_and(|r| r.start <= line)
+    }
+
+    /// `line` 被隐藏时返回隐藏它的折叠区域的标题行，否则返回 `line`
+    pub fn header_of(&self, line: usize) -> usize {
+        let i = self.hidden.partition_point(|r| r.end <= line);
+        match self.hidden.get(i) {
+            Some(r) if r.start <= line => r.start - 1,
+            _ => line,
+        }
+    }
+
+    /// 折叠起来的区域的标题行，保存会话时使用
+    pub fn collapsed_lines(&self) -> Vec<usize> {
+        self.collapsed.iter().copied().collect()
+    }
+
+    /// 恢复会话时设置折叠的区域；下次计算区域时去掉已经不存在的
+    pub fn set_collapsed_lines(&mut self, lines: &[usize]) {
+        self.collapsed = lines.iter().copied().collect();
+        self.rebuild();
+    }
+
+    /// 切换以 `line` 为标题行的区域
+    pub fn toggle(&mut self, line: usize) {
+        if !self.collapsed.remove(&line) && self.region_at(line).is_some() {
+            self.collapsed.insert(line);
+        }
+        self.rebuild();
+    }
+
+    /// 折叠包含 `line` 的最内层还没有折叠的区域
+    pub fn fold_at(&mut self, line: usize) -> bool {
+        let region = self
+            .regions
+            .iter()
+            .rev()
+            .find(|r| r.contains(line) && !self.collapsed.contains(&r.start));
+        match region {
+            Some(r) => {
+                self.collapsed.insert(r.start);
+                self.rebuild();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 展开包含 `line` 的最内层已经折叠的区域；`recursive` 时展开包含 `line`
+    /// 的最内层区域以及其中所有的区域
+    pub fn unfold_at(&mut self, line: usize, recursive: bool) -> bool {
+        let changed = if recursive {
+            let Some(outer) = self
+                .regions
+                .iter()
+                .rev()
+                .find(|r| r.contains(line))
+                .copied()
+            else {
+                return false;
+            };
+            let before = self.collapsed.len();
+            self.collapsed.retain(|&l| !outer.contains(l));
+            self.collapsed.len() != before
+        } else {
+            let region = self
+                .regions
+                .iter()
+                .rev()
+                .find(|r| r.contains(line) && self.collapsed.contains(&r.start));
+            match region {
+                Some(r) => self.collapsed.remove(&r.start),
+                None => false,
+            }
+        };
+        self.rebuild();
+        changed
+    }
+
+    pub fn fold_all(&mut self) {
+        self.collapsed = self.regions.iter().map(|r| r.start).collect();
+        self.rebuild();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.collapsed.clear();
+        self.rebuild();
+    }
+
+    /// 折叠第 `level` 层（最外层为 1）的所有区域，包含 `keep` 行的除外
+    pub fn fold_level(&mut self, level: usize, keep: Option<usize>) {
+        let depths = depths(&self.regions);
+        for (region, depth) in self.regions.iter().zip(depths) {
+            if depth == level && !keep.is_some_and(|line| region.contains(line)) {
+                self.collapsed.insert(region.start);
+            }
+        }
+        self.rebuild();
+    }
+
+    /// 展开隐藏着 `line` 的区域（例如光标或查找结果移到了折叠的行里）。
+    /// 有区域被展开时返回 `true`
+    pub fn reveal(&mut self, line: usize) -> bool {
+        if !self.is_hidden(line) {
+            return false;
+        }
+        let regions = &self.regions;
+        self.collapsed.retain(|&start| {
+            regions
+                .binary_search_by_key(&start, |r| r.start)
+                .map_or(true, |i| !(start < line && line <= regions[i].end))
+        });
+        self.rebuild();
+        true
+    }
+
+    fn region_at(&self, line: usize) -> Option<&FoldRegion> {
+        self.regions
+            .binary_search_by_key(&line, |r| r.start)
+            .ok()
+            .map(|i| &self.regions[i])
+    }
+
+    fn rebuild(&mut self) {
+        // 计算过区域之后才知道哪些折叠还有效；恢复会话时先保留着
+        if self.version.is_some() {
+            let regions = &self.regions;
+            self.collapsed
+                .retain(|line| regions.binary_search_by_key(line, |r| r.start).is_ok());
+        }
+        self.markers = self
+            .regions
+            .iter()
+            .map(|r| (r.start, self.collapsed.contains(&r.start)))
+            .collect();
+        self.hidden.clear();
+        for r in &self.regions {
+            if !self.collapsed.contains(&r.start) {
+                continue;
+            }
+            let range = r.start + 1..r.end + 1;
+            match self.hidden.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => self.hidden.push(range),
+            }
+        }
+    }
+}
+
+/// 每个区域的嵌套层数，最外层为 1
+fn depths(regions: &[FoldRegion]) -> Vec<usize> {
+    let mut ends: Vec<usize> = Vec::new();
+    regions
+        .iter()
+        .map(|r| {
+            while ends.last().is_some_and(|&end| end < r.start) {
+                ends.pop();
+            }
+            ends.push(r.end);
+            ends.len()
+        })
+        .collect()
+}
+
+fn compute_regions(buffer: &TextBuffer, language: Language) -> Vec<FoldRegion> {
+    let text = buffer.to_string();
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut regions = match language {
+        Language::Rust => bracket_regions(&text, Syntax::RUST),
+        Language::Json => bracket_regions(&text, Syntax::JSON),
+        Language::Toml => {
+            let mut regions = bracket_regions(&text, Syntax::TOML);
+            regions.extend(toml_regions(&lines));
+            regions
+        }
+        Language::Markdown => markdown_regions(&lines),
+        Language::PlainText | Language::Shell => indent_regions(&lines),
+    };
+    // 同一行开始的多个区域只保留最大的一个
+    regions.retain(|r| r.end > r.start);
+    regions.sort_by_key(|r| (r.start, std::cmp::Reverse(r.end)));
+    regions.dedup_by_key(|r| r.start);
+    regions
+}
+
+/// 括号匹配时需要跳过的注释和字符串写法
+#[derive(Clone, Copy)]
+struct Syntax {
+    line_comment: &'static str,
+    block_comments: bool,
+    /// `'` 开始字符字面量（Rust 中也可能是生命周期）
+    char_literals: bool,
+    /// TOML 的 `'...'` 字符串
+    single_quote_strings: bool,
+}
+
+impl Syntax {
+    const RUST: Syntax = Syntax {
+        line_comment: "//",
+        block_comments: true,
+        char_literals: true,
+        single_quote_strings: false,
+    };
+    const JSON: Syntax = Syntax {
+        line_comment: "//",
+        block_comments: true,
+        char_literals: false,
+        single_quote_strings: false,
+    };
+    const TOML: Syntax = Syntax {
+        line_comment: "#",
+        block_comments: false,
+        char_literals: false,
+        single_quote_strings: true,
+    };
+}
+
+/// 跨行的 `{}`、`[]`、`()`：隐藏到右括号的上一行，右括号前面还有内容时连右括号所在的行一起隐藏
+fn bracket_regions(text: &str, syntax: Syntax) -> Vec<FoldRegion> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut regions = Vec::new();
+    let mut open: Vec<(char, usize)> = Vec::new();
+    let mut line = 0;
+    let mut i = 0;
+    let starts_with = |i: usize, s: &str| {
+        s.chars()
+            .enumerate()
+            .all(|(k, c)| chars.get(i + k) == Some(&c))
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+        } else if starts_with(i, syntax.line_comment) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        } else if syntax.block_comments && starts_with(i, "/*") {
+            // Rust 的块注释可以嵌套
+            let mut depth = 0;
+            while i < chars.len() {
+                if starts_with(i, "/*") {
+                    depth += 1;
+                    i += 2;
+                } else if starts_with(i, "*/") {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+            }
+            continue;
+        } else if c == '"' || (c == '\'' && syntax.single_quote_strings) {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                if chars[i] == '\\' && c == '"' {
+                    i += 1;
+                    if chars.get(i) == Some(&'\n') {
+                        line += 1;
+                    }
+                }
+                i += 1;
+            }
+        } else if c == '\'' && syntax.char_literals {
+            // '{'、'\''；否则是生命周期，只跳过引号
+            if chars.get(i + 1) == Some(&'\\') {
+                i += 2;
+                while i < chars.len() && chars[i] != '\'' && chars[i] != '\n' {
+                    i += 1;
+                }
+            } else if chars.get(i + 2) == Some(&'\'') {
+                i += 2;
+            }
+        } else if c == 'r' && syntax.char_literals && matches!(chars.get(i + 1), Some('"' | '#')) {
+            // 原始字符串 r"..."、r#"..."#
+            let hashes = chars[i + 1..].iter().take_while(|&&c| c == '#').count();
+            if chars.get(i + 1 + hashes) == Some(&'"') {
+                let closing: String = std::iter::once('"')
+                    .chain(std::iter::repeat_n('#', hashes))
+                    .collect();
+                i += 2 + hashes;
+                while i < chars.len() && !starts_with(i, &closing) {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                i += closing.len();
+                continue;
+            }
+        } else if matches!(c, '{' | '[' | '(') {
+            open.push((c, line));
+        } else if let Some(opening) = match c {
+            '}' => Some('{'),
+            ']' => Some('['),
+            ')' => Some('('),
+            _ => None,
+        } {
+            // 括号不配对时丢掉中间没有闭合的
+            if let Some(k) = open.iter().rposition(|&(o, _)| o == opening) {
+                let (_, start) = open[k];
+                open.truncate(k);
+                if line > start {
+                    // `}`、`})` 这样只有右括号的行留着不折叠
+                    let line_start = chars[..i]
+                        .iter()
+                        .rposition(|&c| c == '\n')
+                        .map_or(0, |p| p + 1);
+                    let only_closing = chars[line_start..i]
+                        .iter()
+                        .all(|c| c.is_whitespace() || matches!(c, '}' | ']' | ')'));
+                    let end = if only_closing { line - 1 } else { line };
+                    regions.push(FoldRegion { start, end });
+                }
+            }
+        }
+        i += 1;
+    }
+    regions
+}
+
+/// 标题到下一个同级或更高级的标题之前；代码块从开始的围栏到结束的围栏
+fn markdown_regions(lines: &[&str]) -> Vec<FoldRegion> {
+    let mut regions = Vec::new();
+    let mut headings: Vec<(usize, usize)> = Vec::new();
+    let mut fence: Option<(usize, &str)> = None;
+    for (line, text) in lines.iter().enumerate() {
+        let trimmed = text.trim_start();
+        if let Some((start, marker)) = fence {
+            if trimmed.starts_with(marker) {
+                regions.push(FoldRegion { start, end: line });
+                fence = None;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some((line, &trimmed[..3]));
+            continue;
+        }
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        let is_heading = (1..=6).contains(&level)
+            && trimmed[level..]
+                .chars()
+                .next()
+                .is_none_or(char::is_whitespace);
+        if !is_heading {
+            continue;
+        }
+        while let Some(&(start, l)) = headings.last() {
+            if l < level {
+                break;
+            }
+            headings.pop();
+            regions.push(section(lines, start, line));
+        }
+        headings.push((line, level));
+    }
+    for (start, _) in headings {
+        regions.push(section(lines, start, lines.len()));
+    }
+    regions
+}
+
+/// 表头到下一个不属于它的表头之前（`[a.b]` 属于 `[a]`）
+fn toml_regions(lines: &[&str]) -> Vec<FoldRegion> {
+    let mut regions = Vec::new();
+    let mut tables: Vec<(usize, String)> = Vec::new();
+    let mut depth = 0isize;
+    for (line, text) in lines.iter().enumerate() {
+        let code = text.split('#').next().unwrap_or("").trim();
+        let name = code
+            .strip_prefix("[[")
+            .and_then(|s| s.strip_suffix("]]"))
+            .or_else(|| code.strip_prefix('[').and_then(|s| s.strip_suffix(']')))
+            .filter(|_| depth == 0);
+        let Some(name) = name else {
+            // 跨行的数组里以 `[` 开头的行不是表头
+            depth += code.matches('[').count() as isize - code.matches(']').count() as isize;
+            depth = depth.max(0);
+            continue;
+        };
+        let name: String = name
+            .split('.')
+            .map(|part| part.trim())
+            .collect::<Vec<_>>()
+            .join(".");
+        while let Some((start, parent)) = tables.last() {
+            if name.starts_with(&format!("{}.", parent)) {
+                break;
+            }
+            regions.push(section(lines, *start, line));
+            tables.pop();
+        }
+        tables.push((line, name));
+    }
+    for (start, _) in tables {
+        regions.push(section(lines, start, lines.len()));
+    }
+    regions
+}
+
+/// 从 `start` 到 `next` 之前，去掉末尾的空行
+fn section(lines: &[&str], start: usize, next: usize) -> FoldRegion {
+    let mut end = next.saturating_sub(1).max(start);
+    while end > start && lines[end].trim().is_empty() {
+        end -= 1;
+    }
+    FoldRegion { start, end }
+}
+
+/// 后面缩进更深的行（中间可以有空行）都属于这一行
+fn indent_regions(lines: &[&str]) -> Vec<FoldRegion> {
+    let indent_of = |text: &str| {
+        if text.trim().is_empty() {
+            return None;
+        }
+        Some(
+            text.chars()
+                .take_while(|c| c.is_whitespace())
+                .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+                .sum::<usize>(),
+        )
+    };
+    let mut regions = Vec::new();
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut last_nonblank = 0;
+    for (line, text) in lines.iter().enumerate() {
+        let Some(indent) = indent_of(text) else {
+            continue;
+        };
+        while let Some(&(start, i)) = open.last() {
+            if i < indent {
+                break;
+            }
+            open.pop();
+            regions.push(FoldRegion {
+                start,
+                end: last_nonblank,
+            });
+        }
+        open.push((line, indent));
+        last_nonblank = line;
+    }
+    for (start, _) in open {
+        regions.push(FoldRegion {
+            start,
+            end: last_nonblank,
+        });
+    }
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifts_regions_until_recomputed() {
+        let mut buffer = TextBuffer::from_text("fn a() {\n    1\n}\nfn b() {\n    2\n}\n");
+        let mut folds = Folds::default();
+        folds.update(&buffer, Language::Rust);
+        folds.toggle(3);
+        assert!(folds.is_hidden(4) && !folds.is_hidden(5));
+
+        // 在第一个函数中插入两行：区域延长，后面的折叠跟着下移，还没有重新计算
+        let ctx = egui::Context::default();
+        buffer.insert(buffer.line_to_char(1), "    0\n    0\n");
+        folds.update_debounced(&ctx, &buffer, Language::Rust);
+        assert!(folds.edited_at.is_some());
+        assert_eq!(folds.collapsed_lines(), vec![5]);
+        assert!(folds.is_hidden(6) && !folds.is_hidden(7));
+
+        // 删掉折叠的区域
+        let start = buffer.line_to_char(5);
+        buffer.remove(start..buffer.len_chars());
+        folds.update_debounced(&ctx, &buffer, Language::Rust);
+        assert!(folds.collapsed_lines().is_empty());
+        assert_eq!(folds.regions(), &[FoldRegion { start: 0, end: 3 }]);
+
+        folds.update(&buffer, Language::Rust);
+        assert!(folds.edited_at.is_none());
+        assert_eq!(folds.regions(), &[FoldRegion { start: 0, end: 3 }]);
+    }
+}
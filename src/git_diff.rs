@@ -0,0 +1,255 @@
+//! 和 git 仓库中 HEAD 版本比较，在行号栏上标出新增、修改和删除的行。
+//!
+//! HEAD 中的内容由 `git show` 在后台线程读取；之后编辑停下来一会儿就在后台线程重新比较一次。
+//! 不在仓库中的文件、没有安装 git 时不显示标记。
+
+use crate::diff::diff_lines;
+use crate::encoding::TextEncoding;
+use crate::gutter::{Decoration, DecorationKind};
+use crate::line_ending;
+use crate::tabs::Tabs;
+use eframe::egui;
+use ropey::Rope;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 在文档的 `Decorations` 中登记的来源
+const SOURCE: &str = "git";
+/// 连续输入时比较的间隔
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+struct Entry {
+    path: PathBuf,
+    /// HEAD 中的各行；文件不在仓库中时为 `None`
+    base: Option<Arc<Vec<String>>>,
+    /// 需要重新读取 HEAD 中的内容
+    stale: bool,
+    loading: bool,
+    /// 后台线程正在比较
+    diffing: bool,
+    /// 上次比较时缓冲区的版本
+    version: Option<u64>,
+    /// 上次比较之后第一次发现内容变化的时间
+    changed_at: Option<Instant>,
+}
+
+impl Entry {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            base: None,
+            stale: true,
+            loading: false,
+            diffing: false,
+            version: None,
+            changed_at: None,
+        }
+    }
+}
+
+/// 读取结果：文档 id、路径和 HEAD 中的各行
+type Loaded = (usize, PathBuf, Option<Vec<String>>);
+/// 比较结果：文档 id、路径和生成的标记
+type Compared = (usize, PathBuf, Vec<Decoration>);
+
+pub struct GitMarkers {
+    entries: HashMap<usize, Entry>,
+    sender: Sender<Loaded>,
+    receiver: Receiver<Loaded>,
+    diff_sender: Sender<Compared>,
+    diff_receiver: Receiver<Compared>,
+}
+
+impl Default for GitMarkers {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let (diff_sender, diff_receiver) = mpsc::channel();
+        Self {
+            entries: HashMap::new(),
+            sender,
+            receiver,
+            diff_sender,
+            diff_receiver,
+        }
+    }
+}
+
+impl GitMarkers {
+    /// 重新读取所有文件在 HEAD 中的内容（例如切回窗口时，可能在外面提交过）
+    pub fn refresh(&mut self) {
+        for entry in self.entries.values_mut() {
+            entry.stale = true;
+        }
+    }
+
+    /// 每帧调用：收取后台读取和比较的结果，内容变化后重新比较
+    pub fn update(&mut self, ctx: &egui::Context, tabs: &mut Tabs) {
+        while let Ok((id, path, lines)) = self.receiver.try_recv() {
+            if let Some(entry) = self.entries.get_mut(&id).filter(|e| e.path == path) {
+                entry.base = lines.map(Arc::new);
+                entry.loading = false;
+                entry.version = None;
+            }
+        }
+        while let Ok((id, path, decorations)) = self.diff_receiver.try_recv() {
+            let Some(entry) = self.entries.get_mut(&id).filter(|e| e.path == path) else {
+                continue;
+            };
+            entry.diffing = false;
+            // 比较期间又有修改时先显示这次的结果，下面会接着再比较一次
+            if entry.base.is_some() {
+                if let Some(document) = tabs.get_mut(id) {
+                    document.decorations.set(SOURCE, decorations);
+                }
+            }
+        }
+        self.entries.retain(|&id, _| tabs.get(id).is_some());
+
+        let now = Instant::now();
+        for (id, document) in tabs.iter_mut() {
+            let Some(path) = document.path.clone() else {
+                self.entries.remove(&id);
+                document.decorations.clear(SOURCE);
+                continue;
+            };
+            let entry = self
+                .entries
+                .entry(id)
+                .or_insert_with(|| Entry::new(path.clone()));
+            if entry.path != path {
+                // 另存为或者重命名了
+                *entry = Entry::new(path.clone());
+            }
+            if entry.stale && !entry.loading {
+                entry.stale = false;
+                entry.loading = true;
+                load(
+                    ctx,
+                    self.sender.clone(),
+                    id,
+                    path.clone(),
+                    document.encoding,
+                );
+            }
+            let Some(base) = entry.base.clone() else {
+                document.decorations.clear(SOURCE);
+                continue;
+            };
+            let version = document.buffer.version();
+            if entry.version == Some(version) || entry.diffing {
+                continue;
+            }
+            // 连续输入时不要每个字符都比较一次
+            let since = *entry.changed_at.get_or_insert(now);
+            if entry.version.is_some() && now - since < DEBOUNCE {
+                ctx.request_repaint_after(DEBOUNCE - (now - since));
+                continue;
+            }
+            entry.changed_at = None;
+            entry.version = Some(version);
+            entry.diffing = true;
+            compare(
+                ctx,
+                self.diff_sender.clone(),
+                id,
+                path,
+                base,
+                document.buffer.rope().clone(),
+            );
+        }
+    }
+}
+
+/// 在后台线程读取 `path` 在 HEAD 中的内容
+fn load(
+    ctx: &egui::Context,
+    sender: Sender<Loaded>,
+    id: usize,
+    path: PathBuf,
+    encoding: TextEncoding,
+) {
+    let ctx = ctx.clone();
+    thread::spawn(move || {
+        let lines = head_text(&path, encoding).map(|text| {
+            line_ending::normalize(&text)
+                .split('\n')
+                .map(str::to_owned)
+                .collect()
+        });
+        if sender.send((id, path, lines)).is_ok() {
+            ctx.request_repaint();
+        }
+    });
+}
+
+/// 在后台线程比较 HEAD 中的内容和缓冲区的快照
+fn compare(
+    ctx: &egui::Context,
+    sender: Sender<Compared>,
+    id: usize,
+    path: PathBuf,
+    base: Arc<Vec<String>>,
+    text: Rope,
+) {
+    let ctx = ctx.clone();
+    thread::spawn(move || {
+        let decorations = decorations(&base, &text);
+        if sender.send((id, path, decorations)).is_ok() {
+            ctx.request_repaint();
+        }
+    });
+}
+
+fn head_text(path: &Path, encoding: TextEncoding) -> Option<String> {
+    let dir = path.parent()?;
+    let name = path.file_name()?.to_str()?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("HEAD:./{}", name))
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    encoding
+        .decode(&output.stdout)
+        .or_else(|| TextEncoding::detect(&output.stdout).decode(&output.stdout))
+}
+
+/// 按行比较 HEAD 中的内容和缓冲区，生成标记；修改和删除的标记带着原来的内容
+fn decorations(base: &[String], text: &Rope) -> Vec<Decoration> {
+    let text = text.to_string();
+    let lines: Vec<&str> = text.split('\n').collect();
+    let base: Vec<&str> = base.iter().map(String::as_str).collect();
+    let mut decorations = Vec::new();
+    for hunk in diff_lines(&base, &lines) {
+        let old: Arc<str> = base[hunk.old.clone()].join("\n").into();
+        if hunk.new.is_empty() {
+            decorations.push(Decoration {
+                line: hunk.new.start.min(lines.len().saturating_sub(1)),
+                kind: DecorationKind::Deleted,
+                message: Some(old),
+            });
+        } else if hunk.old.is_empty() {
+            decorations.extend(hunk.new.map(|line| Decoration {
+                line,
+                kind: DecorationKind::Added,
+                message: None,
+            }));
+        } else {
+            decorations.extend(hunk.new.map(|line| Decoration {
+                line,
+                kind: DecorationKind::Modified,
+                message: Some(old.clone()),
+            }));
+        }
+    }
+    decorations
+}
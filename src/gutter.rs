@@ -0,0 +1,445 @@
+//! 编辑区左侧的行号栏。
+//!
+//! 从左到右依次是：诊断和标注图标、行号、折叠按钮、git 修改标记。
+//! 其他模块通过文档的 `Decorations` 按来源登记行标记，例如 git 比较结果登记在 `"git"` 下，
+//! 更新时整体替换同一来源的标记。
+
+use eframe::egui::{
+    self, Align2, Color32, CursorIcon, FontId, Id, Pos2, Rect, Sense, Shape, Stroke, pos2, vec2,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// 行号的显示方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineNumbers {
+    Absolute,
+    /// 当前行显示行号，其余行显示到当前行的距离
+    Relative,
+    Off,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecorationKind {
+    /// 相对 git HEAD 新增的行
+    Added,
+    /// 相对 git HEAD 修改过的行
+    Modified,
+    /// 这一行之前有被删除的行
+    Deleted,
+    Error,
+    Warning,
+    Info,
+    /// 类似断点的标注
+    Annotation,
+}
+
+impl DecorationKind {
+    fn is_git(self) -> bool {
+        matches!(self, Self::Added | Self::Modified | Self::Deleted)
+    }
+
+    /// 同一行有多个图标时只画优先级最高的
+    fn priority(self) -> u8 {
+        match self {
+            Self::Error => 4,
+            Self::Warning => 3,
+            Self::Info => 2,
+            Self::Annotation => 1,
+            Self::Added | Self::Modified | Self::Deleted => 0,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Added => "新增",
+            Self::Modified => "修改前",
+            Self::Deleted => "已删除",
+            Self::Error => "错误",
+            Self::Warning => "警告",
+            Self::Info => "提示",
+            Self::Annotation => "标注",
+        }
+    }
+
+    pub(crate) fn color(self, dark_mode: bool) -> Color32 {
+        match (self, dark_mode) {
+            (Self::Added, true) => Color32::from_rgb(80, 160, 80),
+            (Self::Added, false) => Color32::from_rgb(60, 150, 60),
+            (Self::Modified, true) => Color32::from_rgb(60, 130, 200),
+            (Self::Modified, false) => Color32::from_rgb(40, 110, 190),
+            (Self::Deleted | Self::Error, _) => Color32::from_rgb(220, 70, 60),
+            (Self::Warning, _) => Color32::from_rgb(230, 170, 30),
+            (Self::Info, _) => Color32::from_rgb(70, 150, 220),
+            (Self::Annotation, _) => Color32::from_rgb(200, 50, 50),
+        }
+    }
+}
+
+/// 一个行标记
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoration {
+    pub line: usize,
+    pub kind: DecorationKind,
+    /// 点击标记时弹出的说明；同一段修改的各行共用一份
+    pub message: Option<Arc<str>>,
+}
+
+/// 文档上各个来源登记的行标记
+#[derive(Default)]
+pub struct Decorations {
+    sources: BTreeMap<&'static str, Vec<Decoration>>,
+}
+
+impl Decorations {
+    /// 替换来源 `source` 的全部标记
+    pub fn set(&mut self, source: &'static str, mut decorations: Vec<Decoration>) {
+        if decorations.is_empty() {
+            self.sources.remove(source);
+            return;
+        }
+        decorations.sort_by_key(|d| d.line);
+        self.sources.insert(source, decorations);
+    }
+
+    pub fn clear(&mut self, source: &'static str) {
+        self.sources.remove(source);
+    }
+
+    pub fn get(&self, source: &'static str) -> &[Decoration] {
+        self.sources.get(source).map_or(&[], Vec::as_slice)
+    }
+
+    /// 第 `line` 行上所有来源的标记
+    pub fn on_line(&self, line: usize) -> impl Iterator<Item = &Decoration> {
+        self.sources.values().flat_map(move |list| {
+            let start = list.partition_point(|d| d.line < line);
+            list[start..].iter().take_while(move |d| d.line == line)
+        })
+    }
+}
+
+/// 行号栏的内容，通过 `TextView::gutter` 传入
+pub struct Gutter<'a> {
+    pub line_numbers: LineNumbers,
+    pub decorations: &'a Decorations,
+    /// 可以折叠的行以及是否已经折叠，按行排序
+    pub folds: &'a [(usize, bool)],
+}
+
+/// 一个可见的缓冲区行在屏幕上的位置
+pub(crate) struct VisibleLine {
+    pub line: usize,
+    pub top: f32,
+    pub height: f32,
+}
+
+pub(crate) enum GutterAction {
+    /// 点击或拖过行号：选中这些行
+    SelectLines(Range<usize>),
+    ToggleFold(usize),
+}
+
+/// 跨帧保存的交互状态
+#[derive(Clone, Copy, Default)]
+struct GutterState {
+    /// 拖动选择时按下的那一行
+    anchor: Option<usize>,
+    /// 弹出说明的那一行
+    popup: Option<usize>,
+}
+
+/// 各列的宽度
+struct Columns {
+    icon: f32,
+    numbers: f32,
+    fold: f32,
+    git: f32,
+}
+
+impl Columns {
+    fn new(gutter: &Gutter, digit_width: f32, row_height: f32, line_count: usize) -> Self {
+        let digits = line_count.max(1).to_string().len().max(3);
+        Self {
+            icon: row_height,
+            numbers: match gutter.line_numbers {
+                LineNumbers::Off => 0.0,
+                _ => digits as f32 * digit_width + 8.0,
+            },
+            fold: (row_height * 0.8).round(),
+            git: 6.0,
+        }
+    }
+
+    fn total(&self) -> f32 {
+        self.icon + self.numbers + self.fold + self.git
+    }
+}
+
+impl Gutter<'_> {
+    pub(crate) fn width(&self, ui: &egui::Ui, font_id: &FontId, line_count: usize) -> f32 {
+        let (digit_width, row_height) =
+            ui.fonts(|f| (f.glyph_width(font_id, '0'), f.row_height(font_id)));
+        Columns::new(self, digit_width, row_height, line_count).total()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn show(
+        &self,
+        ui: &mut egui::Ui,
+        rect: Rect,
+        id: Id,
+        font_id: &FontId,
+        lines: &[VisibleLine],
+        current_line: Option<usize>,
+        line_count: usize,
+    ) -> Option<GutterAction> {
+        let (digit_width, row_height) =
+            ui.fonts(|f| (f.glyph_width(font_id, '0'), f.row_height(font_id)));
+        let columns = Columns::new(self, digit_width, row_height, line_count);
+        let icon_right = rect.left() + columns.icon;
+        let numbers_right = icon_right + columns.numbers;
+        let fold_right = numbers_right + columns.fold;
+        let dark_mode = ui.visuals().dark_mode;
+        let mut state: GutterState = ui.data_mut(|d| d.get_temp(id)).unwrap_or_default();
+        let mut action = None;
+
+        let response = ui.interact(rect, id, Sense::click_and_drag());
+        let line_at = |y: f32| {
+            lines
+                .iter()
+                .find(|l| y >= l.top && y < l.top + l.height)
+                .or_else(|| lines.last().filter(|l| y >= l.top))
+                .map(|l| l.line)
+        };
+        let fold_at = |line: usize| {
+            self.folds
+                .binary_search_by_key(&line, |&(l, _)| l)
+                .ok()
+                .map(|i| self.folds[i].1)
+        };
+        let has_message = |line: usize| self.decorations.on_line(line).any(|d| d.message.is_some());
+
+        if let Some(pos) = response.hover_pos() {
+            let clickable =
+                line_at(pos.y).is_some_and(|line| match column_at(&columns, rect, pos) {
+                    Column::Fold => fold_at(line).is_some(),
+                    Column::Icon | Column::Git => has_message(line),
+                    Column::Numbers => false,
+                });
+            if clickable {
+                ui.ctx().set_cursor_icon(CursorIcon::PointingHand);
+            }
+        }
+        let popup_was_open = state.popup.is_some();
+        if let Some(pos) = response.interact_pointer_pos() {
+            if let Some(line) = line_at(pos.y) {
+                let column = column_at(&columns, rect, pos);
+                if response.clicked() {
+                    match column {
+                        Column::Fold if fold_at(line).is_some() => {
+                            action = Some(GutterAction::ToggleFold(line));
+                        }
+                        Column::Icon | Column::Git if has_message(line) => {
+                            state.popup = (state.popup != Some(line)).then_some(line);
+                        }
+                        _ => {}
+                    }
+                }
+                let pressed = ui.input(|i| i.pointer.primary_pressed());
+                if pressed && column == Column::Numbers {
+                    state.anchor = Some(line);
+                }
+                if let Some(anchor) = state.anchor {
+                    action = Some(GutterAction::SelectLines(
+                        anchor.min(line)..anchor.max(line) + 1,
+                    ));
+                }
+            }
+        }
+        if !ui.input(|i| i.pointer.any_down()) {
+            state.anchor = None;
+        }
+
+        // ---- 绘制 ----
+        let painter = ui.painter_at(rect);
+        let weak = ui.visuals().weak_text_color();
+        let strong = ui.visuals().strong_text_color();
+        painter.vline(
+            rect.right() - 0.5,
+            rect.y_range(),
+            ui.visuals().widgets.noninteractive.bg_stroke,
+        );
+        for visible in lines {
+            let line = visible.line;
+            let row = Rect::from_min_max(
+                pos2(rect.left(), visible.top),
+                pos2(rect.right(), visible.top + visible.height),
+            );
+            let center_y = visible.top + row_height / 2.0;
+
+            if self.line_numbers != LineNumbers::Off {
+                let current = current_line == Some(line);
+                let number = match (self.line_numbers, current_line) {
+                    (LineNumbers::Relative, Some(cur)) if !current => cur.abs_diff(line),
+                    _ => line + 1,
+                };
+                painter.text(
+                    pos2(numbers_right - 4.0, visible.top),
+                    Align2::RIGHT_TOP,
+                    number.to_string(),
+                    font_id.clone(),
+                    if current { strong } else { weak },
+                );
+            }
+
+            let decorations: Vec<&Decoration> = self.decorations.on_line(line).collect();
+            if let Some(icon) = decorations
+                .iter()
+                .filter(|d| !d.kind.is_git())
+                .max_by_key(|d| d.kind.priority())
+            {
+                let center = pos2(rect.left() + columns.icon / 2.0, center_y);
+                paint_icon(&painter, icon.kind, center, row_height * 0.3, dark_mode);
+            }
+            for decoration in decorations.iter().filter(|d| d.kind.is_git()) {
+                let color = decoration.kind.color(dark_mode);
+                let x = fold_right + 1.0..=fold_right + columns.git - 2.0;
+                if decoration.kind == DecorationKind::Deleted {
+                    // 删除的行在这一行的上边缘画一个小三角
+                    let (left, top) = (*x.start(), row.top());
+                    painter.add(Shape::convex_polygon(
+                        vec![
+                            pos2(left, top - 4.0),
+                            pos2(left + 5.0, top),
+                            pos2(left, top + 4.0),
+                        ],
+                        color,
+                        Stroke::NONE,
+                    ));
+                } else {
+                    painter.rect_filled(Rect::from_x_y_ranges(x, row.y_range()), 0.0, color);
+                }
+            }
+            if let Some(collapsed) = fold_at(line) {
+                let center = pos2(numbers_right + columns.fold / 2.0, center_y);
+                paint_fold_toggle(&painter, center, row_height * 0.25, collapsed, weak);
+            }
+        }
+
+        // ---- 点击标记弹出的说明 ----
+        if let Some(line) = state.popup {
+            match lines.iter().find(|l| l.line == line) {
+                Some(visible) => {
+                    let anchor = pos2(rect.right(), visible.top + visible.height);
+                    let area = egui::Area::new(id.with("popup"))
+                        .order(egui::Order::Foreground)
+                        .fixed_pos(anchor)
+                        .show(ui.ctx(), |ui| {
+                            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                                ui.set_max_width(480.0);
+                                for decoration in self.decorations.on_line(line) {
+                                    let Some(message) = &decoration.message else {
+                                        continue;
+                                    };
+                                    ui.colored_label(
+                                        decoration.kind.color(dark_mode),
+                                        decoration.kind.label(),
+                                    );
+                                    ui.label(egui::RichText::new(&**message).monospace());
+                                }
+                            });
+                        });
+                    if popup_was_open && area.response.clicked_elsewhere() && !response.clicked() {
+                        state.popup = None;
+                    }
+                }
+                None => state.popup = None,
+            }
+            if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                state.popup = None;
+            }
+        }
+
+        ui.data_mut(|d| d.insert_temp(id, state));
+        action
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Icon,
+    Numbers,
+    Fold,
+    Git,
+}
+
+fn column_at(columns: &Columns, rect: Rect, pos: Pos2) -> Column {
+    let x = pos.x - rect.left();
+    if x < columns.icon {
+        Column::Icon
+    } else if x < columns.icon + columns.numbers {
+        Column::Numbers
+    } else if x < columns.icon + columns.numbers + columns.fold {
+        Column::Fold
+    } else {
+        Column::Git
+    }
+}
+
+fn paint_icon(
+    painter: &egui::Painter,
+    kind: DecorationKind,
+    center: Pos2,
+    radius: f32,
+    dark_mode: bool,
+) {
+    let color = kind.color(dark_mode);
+    match kind {
+        DecorationKind::Error => {
+            let rect = Rect::from_center_size(center, vec2(radius, radius) * 2.0);
+            painter.rect_filled(rect, 2.0, color);
+        }
+        DecorationKind::Warning => {
+            let points = vec![
+                center + vec2(0.0, -radius * 1.1),
+                center + vec2(radius * 1.1, radius),
+                center + vec2(-radius * 1.1, radius),
+            ];
+            painter.add(Shape::convex_polygon(points, color, Stroke::NONE));
+        }
+        DecorationKind::Info => {
+            painter.circle_stroke(center, radius, Stroke::new(1.5, color));
+        }
+        _ => {
+            painter.circle_filled(center, radius, color);
+        }
+    }
+}
+
+/// 折叠按钮：展开时是向下的三角，折叠后是向右的三角
+fn paint_fold_toggle(
+    painter: &egui::Painter,
+    center: Pos2,
+    size: f32,
+    collapsed: bool,
+    color: Color32,
+) {
+    let points = if collapsed {
+        vec![
+            center + vec2(-size * 0.6, -size),
+            center + vec2(size * 0.8, 0.0),
+            center + vec2(-size * 0.6, size),
+        ]
+    } else {
+        vec![
+            center + vec2(-size, -size * 0.6),
+            center + vec2(size, -size * 0.6),
+            center + vec2(0.0, size * 0.8),
+        ]
+    };
+    painter.add(Shape::convex_polygon(points, color, Stroke::NONE));
+}
@@ -0,0 +1,812 @@
+use crate::buffer::TextBuffer;
+use eframe::egui::{Color32, FontId, TextFormat, text::LayoutJob};
+use std::path::Path;
+
+/// 编辑器支持高亮的语言，根据文件扩展名选择。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    PlainText,
+    Rust,
+    Toml,
+    Markdown,
+    Json,
+    Shell,
+}
+
+impl Language {
+    pub const ALL: [Language; 6] = [
+        Language::PlainText,
+        Language::Rust,
+        Language::Toml,
+        Language::Markdown,
+        Language::Json,
+        Language::Shell,
+    ];
+
+    pub fn from_path(path: &Path) -> Self {
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match file_name.as_str() {
+            "cargo.lock" => return Language::Toml,
+            ".bashrc" | ".zshrc" | ".profile" | ".bash_profile" => return Language::Shell,
+            _ => {}
+        }
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "rs" => Language::Rust,
+            "toml" => Language::Toml,
+            "md" | "markdown" => Language::Markdown,
+            "json" => Language::Json,
+            "sh" | "bash" | "zsh" => Language::Shell,
+            _ => Language::PlainText,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::PlainText => "Plain Text",
+            Language::Rust => "Rust",
+            Language::Toml => "TOML",
+            Language::Markdown => "Markdown",
+            Language::Json => "JSON",
+            Language::Shell => "Shell",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    Type,
+    Function,
+    Macro,
+    Attribute,
+    String,
+    Number,
+    Comment,
+    Key,
+    Variable,
+    Heading,
+    Emphasis,
+    Code,
+    Link,
+    Punctuation,
+}
+
+/// 行首的词法状态，跨行的注释、字符串等需要从上一行带过来。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineState {
+    #[default]
+    Normal,
+    /// Rust 块注释，记录嵌套层数
+    BlockComment(u32),
+    /// 跨行的普通字符串，记录引号
+    Str(char),
+    /// Rust 原始字符串，记录 `#` 的个数
+    RawStr(u8),
+    /// TOML 的 `"""` / `'''` 多行字符串
+    TomlMultiLine(char),
+    /// Markdown 代码块内部
+    CodeFence,
+}
+
+/// 高亮配色，跟随 egui 的明暗主题
+pub struct SyntaxTheme {
+    pub plain: Color32,
+    pub keyword: Color32,
+    pub ty: Color32,
+    pub function: Color32,
+    pub macro_: Color32,
+    pub attribute: Color32,
+    pub string: Color32,
+    pub number: Color32,
+    pub comment: Color32,
+    pub key: Color32,
+    pub variable: Color32,
+    pub heading: Color32,
+    pub punctuation: Color32,
+}
+
+impl SyntaxTheme {
+    pub fn for_dark_mode(dark_mode: bool, plain: Color32) -> Self {
+        if dark_mode {
+            Self {
+                plain,
+                keyword: Color32::from_rgb(0xc6, 0x78, 0xdd),
+                ty: Color32::from_rgb(0xe5, 0xc0, 0x7b),
+                function: Color32::from_rgb(0x61, 0xaf, 0xef),
+                macro_: Color32::from_rgb(0x56, 0xb6, 0xc2),
+                attribute: Color32::from_rgb(0xd1, 0x9a, 0x66),
+                string: Color32::from_rgb(0x98, 0xc3, 0x79),
+                number: Color32::from_rgb(0xd1, 0x9a, 0x66),
+                comment: Color32::from_rgb(0x7f, 0x84, 0x8e),
+                key: Color32::from_rgb(0xe0, 0x6c, 0x75),
+                variable: Color32::from_rgb(0xe0, 0x6c, 0x75),
+                heading: Color32::from_rgb(0x61, 0xaf, 0xef),
+                punctuation: Color32::from_rgb(0xab, 0xb2, 0xbf),
+            }
+        } else {
+            Self {
+                plain,
+                keyword: Color32::from_rgb(0xa6, 0x26, 0xa4),
+                ty: Color32::from_rgb(0xc1, 0x84, 0x01),
+                function: Color32::from_rgb(0x40, 0x78, 0xf2),
+                macro_: Color32::from_rgb(0x01, 0x84, 0xbc),
+                attribute: Color32::from_rgb(0x98, 0x68, 0x01),
+                string: Color32::from_rgb(0x50, 0xa1, 0x4f),
+                number: Color32::from_rgb(0x98, 0x68, 0x01),
+                comment: Color32::from_rgb(0xa0, 0xa1, 0xa7),
+                key: Color32::from_rgb(0xe4, 0x56, 0x49),
+                variable: Color32::from_rgb(0xe4, 0x56, 0x49),
+                heading: Color32::from_rgb(0x40, 0x78, 0xf2),
+                punctuation: Color32::from_rgb(0x38, 0x3a, 0x42),
+            }
+        }
+    }
+
+    pub fn color(&self, kind: TokenKind) -> Color32 {
+        match kind {
+            TokenKind::Plain | TokenKind::Emphasis => self.plain,
+            TokenKind::Keyword => self.keyword,
+            TokenKind::Type => self.ty,
+            TokenKind::Function => self.function,
+            TokenKind::Macro => self.macro_,
+            TokenKind::Attribute => self.attribute,
+            TokenKind::String | TokenKind::Code => self.string,
+            TokenKind::Number => self.number,
+            TokenKind::Comment => self.comment,
+            TokenKind::Key => self.key,
+            TokenKind::Variable => self.variable,
+            TokenKind::Heading | TokenKind::Link => self.heading,
+            TokenKind::Punctuation => self.punctuation,
+        }
+    }
+}
+
+/// 一个文档的高亮器：缓存每一行行首的词法状态。
+///
+/// 编辑之后只让被修改行之后的缓存失效，渲染时从最近的有效状态继续向下扫描，
+/// 因此在文件中间输入时只需要重新扫描修改处到可见区域之间的几行。
+#[derive(Default)]
+pub struct Highlighter {
+    language: Language,
+    // states[i] 是第 i 行行首的状态
+    states: Vec<LineState>,
+    version: u64,
+}
+
+impl Highlighter {
+    pub fn new(language: Language) -> Self {
+        Self {
+            language,
+            ..Self::default()
+        }
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    pub fn set_language(&mut self, language: Language) {
+        if language != self.language {
+            self.language = language;
+            self.states.clear();
+        }
+    }
+
+    /// 第 `line` 行行首的状态，必要时从缓存的最后一行继续扫描
+    pub fn state_at_line(&mut self, buffer: &TextBuffer, line: usize) -> LineState {
+        if self.language == Language::PlainText {
+            return LineState::Normal;
+        }
+        if let Some(changed) = buffer.first_changed_line_since(self.version) {
+            self.states.truncate(changed + 1);
+        }
+        self.version = buffer.version();
+        if self.states.is_empty() {
+            self.states.push(LineState::Normal);
+        }
+
+        let line = line.min(buffer.len_lines().saturating_sub(1));
+        let mut text = String::new();
+        while self.states.len() <= line {
+            let index = self.states.len() - 1;
+            text.clear();
+            text.extend(buffer.line(index).chunks());
+            let mut state = self.states[index];
+            tokenize_line(self.language, &text, &mut state, |_, _| {});
+            self.states.push(state);
+        }
+        self.states[line]
+    }
+}
+
+/// 把从 `state` 开始的若干行文本转换成带颜色的 `LayoutJob`
+pub fn layout_job(
+    language: Language,
+    mut state: LineState,
+    text: &str,
+    font_id: &FontId,
+    theme: &SyntaxTheme,
+) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    for line in text.split_inclusive('\n') {
+        let mut tokens = Vec::new();
+        tokenize_line(language, line, &mut state, |range, kind| {
+            tokens.push((range, kind))
+        });
+        // tokenize_line 只报告非 Plain 的片段，中间的空隙按普通文本补齐
+        let mut pos = 0;
+        for (range, kind) in tokens {
+            if range.start > pos {
+                append(
+                    &mut job,
+                    &line[pos..range.start],
+                    TokenKind::Plain,
+                    font_id,
+                    theme,
+                );
+            }
+            append(&mut job, &line[range.clone()], kind, font_id, theme);
+            pos = range.end;
+        }
+        if pos < line.len() {
+            append(&mut job, &line[pos..], TokenKind::Plain, font_id, theme);
+        }
+    }
+    job
+}
+
+fn append(job: &mut LayoutJob, text: &str, kind: TokenKind, font_id: &FontId, theme: &SyntaxTheme) {
+    let mut format = TextFormat::simple(font_id.clone(), theme.color(kind));
+    match kind {
+        TokenKind::Emphasis => format.italics = true,
+        TokenKind::Link => format.underline = eframe::egui::Stroke::new(1.0, theme.color(kind)),
+        _ => {}
+    }
+    job.append(text, 0.0, format);
+}
+
+// ---- 各语言的词法扫描 ----
+//
+// 每个扫描函数处理一行（可能带换行符），通过 `emit(字节范围, 类型)` 报告高亮片段，
+// 并把行尾的状态写回 `state`。
+
+type Emit<'a> = dyn FnMut(std::ops::Range<usize>, TokenKind) + 'a;
+
+pub fn tokenize_line(
+    language: Language,
+    line: &str,
+    state: &mut LineState,
+    mut emit: impl FnMut(std::ops::Range<usize>, TokenKind),
+) {
+    match language {
+        Language::PlainText => {}
+        Language::Rust => rust_line(line, state, &mut emit),
+        Language::Toml => toml_line(line, state, &mut emit),
+        Language::Markdown => markdown_line(line, state, &mut emit),
+        Language::Json => json_line(line, &mut emit),
+        Language::Shell => shell_line(line, state, &mut emit),
+    }
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "yield",
+];
+
+const SHELL_KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "for", "do", "done", "case", "esac", "while", "until",
+    "function", "in", "return", "export", "local", "readonly", "set", "unset", "shift", "exit",
+    "source", "echo", "cd",
+];
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// 从 `start` 开始的标识符结束位置
+fn ident_end(line: &str, start: usize) -> usize {
+    line[start..]
+        .char_indices()
+        .find(|&(_, c)| !is_ident_char(c))
+        .map_or(line.len(), |(i, _)| start + i)
+}
+
+/// 数字字面量的结束位置（包括十六进制、下划线、后缀等）
+fn number_end(line: &str, start: usize) -> usize {
+    line[start..]
+        .char_indices()
+        .find(|&(_, c)| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+        .map_or(line.len(), |(i, _)| start + i)
+}
+
+/// 扫描到字符串结束引号之后；没有找到时返回 `None`（字符串延续到下一行）
+fn string_end(line: &str, start: usize, quote: char, escapes: bool) -> Option<usize> {
+    let mut chars = line[start..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        if escapes && c == '\\' {
+            chars.next();
+        } else if c == quote {
+            return Some(start + i + c.len_utf8());
+        }
+    }
+    None
+}
+
+fn line_content_end(line: &str) -> usize {
+    line.trim_end_matches(['\n', '\r']).len()
+}
+
+fn rust_line(line: &str, state: &mut LineState, emit: &mut Emit) {
+    let end = line_content_end(line);
+    let mut i = 0;
+    while i < end {
+        match *state {
+            LineState::BlockComment(depth) => {
+                let start = i;
+                let mut depth = depth;
+                while i < end && depth > 0 {
+                    if line[i..].starts_with("/*") {
+                        depth += 1;
+                        i += 2;
+                    } else if line[i..].starts_with("*/") {
+                        depth -= 1;
+                        i += 2;
+                    } else {
+                        i += line[i..].chars().next().map_or(1, char::len_utf8);
+                    }
+                }
+                emit(start..i, TokenKind::Comment);
+                *state = if depth == 0 {
+                    LineState::Normal
+                } else {
+                    LineState::BlockComment(depth)
+                };
+                continue;
+            }
+            LineState::Str(quote) => {
+                match string_end(line, i, quote, true) {
+                    Some(e) => {
+                        emit(i..e, TokenKind::String);
+                        i = e;
+                        *state = LineState::Normal;
+                    }
+                    None => {
+                        emit(i..end, TokenKind::String);
+                        i = end;
+                    }
+                }
+                continue;
+            }
+            LineState::RawStr(hashes) => {
+                let closing = format!("\"{}", "#".repeat(hashes as usize));
+                match line[i..].find(&closing) {
+                    Some(pos) => {
+                        let e = i + pos + closing.len();
+                        emit(i..e, TokenKind::String);
+                        i = e;
+                        *state = LineState::Normal;
+                    }
+                    None => {
+                        emit(i..end, TokenKind::String);
+                        i = end;
+                    }
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let rest = &line[i..end];
+        let c = rest.chars().next().unwrap_or(' ');
+        if rest.starts_with("//") {
+            emit(i..end, TokenKind::Comment);
+            return;
+        } else if rest.starts_with("/*") {
+            emit(i..i + 2, TokenKind::Comment);
+            *state = LineState::BlockComment(1);
+            i += 2;
+        } else if rest.starts_with("#[") || rest.starts_with("#![") {
+            let e = rest.find(']').map_or(end, |p| i + p + 1);
+            emit(i..e, TokenKind::Attribute);
+            i = e;
+        } else if let Some(hashes) = raw_string_prefix(rest) {
+            // r"..." / r#"..."# / br"..."
+            let prefix = rest.find('"').unwrap_or(0) + 1;
+            emit(i..i + prefix, TokenKind::String);
+            i += prefix;
+            *state = LineState::RawStr(hashes);
+        } else if c == '"' || rest.starts_with("b\"") {
+            let open = if c == '"' { 1 } else { 2 };
+            *state = LineState::Str('"');
+            emit(i..i + open, TokenKind::String);
+            i += open;
+        } else if c == '\'' {
+            // 字符字面量 'a' / '\n'，否则当作生命周期 'a
+            match char_literal_end(rest) {
+                Some(len) => {
+                    emit(i..i + len, TokenKind::String);
+                    i += len;
+                }
+                None => {
+                    let e = ident_end(line, i + 1).min(end);
+                    emit(i..e, TokenKind::Type);
+                    i = e.max(i + 1);
+                }
+            }
+        } else if c.is_ascii_digit() {
+            let e = number_end(line, i).min(end);
+            emit(i..e, TokenKind::Number);
+            i = e;
+        } else if is_ident_start(c) {
+            let e = ident_end(line, i).min(end);
+            let word = &line[i..e];
+            let next = line[e..end].trim_start().chars().next();
+            let kind = if RUST_KEYWORDS.contains(&word) {
+                TokenKind::Keyword
+            } else if line[e..end].starts_with('!') {
+                TokenKind::Macro
+            } else if next == Some('(') {
+                TokenKind::Function
+            } else if word.starts_with(char::is_uppercase) {
+                TokenKind::Type
+            } else {
+                TokenKind::Plain
+            };
+            if kind != TokenKind::Plain {
+                let e = if kind == TokenKind::Macro { e + 1 } else { e };
+                emit(i..e, kind);
+                i = e;
+            } else {
+                i = e;
+            }
+        } else {
+            i += c.len_utf8();
+        }
+    }
+}
+
+/// `r"`、`r#"`、`br"` 等原始字符串前缀，返回 `#` 的个数
+fn raw_string_prefix(rest: &str) -> Option<u8> {
+    let rest = rest.strip_prefix('b').unwrap_or(rest);
+    let rest = rest.strip_prefix('r')?;
+    let hashes = rest.chars().take_while(|&c| c == '#').count();
+    rest[hashes..].starts_with('"').then_some(hashes as u8)
+}
+
+fn char_literal_end(rest: &str) -> Option<usize> {
+    let mut chars = rest.char_indices().skip(1);
+    let (_, c) = chars.next()?;
+    if c == '\\' {
+        // 转义：找到下一个单引号
+        let close = rest[2..].find('\'')?;
+        return Some(2 + close + 1);
+    }
+    let (i, close) = chars.next()?;
+    (close == '\'').then_some(i + 1)
+}
+
+fn toml_line(line: &str, state: &mut LineState, emit: &mut Emit) {
+    let end = line_content_end(line);
+    let mut i = 0;
+    if let LineState::TomlMultiLine(quote) = *state {
+        let closing: String = std::iter::repeat_n(quote, 3).collect();
+        match line.find(&closing) {
+            Some(pos) => {
+                i = pos + 3;
+                emit(0..i, TokenKind::String);
+                *state = LineState::Normal;
+            }
+            None => {
+                emit(0..end, TokenKind::String);
+                return;
+            }
+        }
+    }
+
+    let trimmed = line[i..end].trim_start();
+    let indent = end - i - trimmed.len();
+    if i == 0 && trimmed.starts_with('[') {
+        // [table] / [[array]]
+        let e = trimmed.rfind(']').map_or(end, |p| indent + p + 1);
+        emit(indent..e, TokenKind::Heading);
+        i = e;
+    } else if i == 0 {
+        // key = value 中的 key
+        if let Some(eq) = key_end(trimmed) {
+            emit(indent..indent + eq, TokenKind::Key);
+            i = indent + eq;
+        }
+    }
+
+    while i < end {
+        let rest = &line[i..end];
+        let c = rest.chars().next().unwrap_or(' ');
+        if c == '#' {
+            emit(i..end, TokenKind::Comment);
+            return;
+        } else if rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+            let quote = c;
+            let closing: String = std::iter::repeat_n(quote, 3).collect();
+            match rest[3..].find(&closing) {
+                Some(pos) => {
+                    let e = i + 3 + pos + 3;
+                    emit(i..e, TokenKind::String);
+                    i = e;
+                }
+                None => {
+                    emit(i..end, TokenKind::String);
+                    *state = LineState::TomlMultiLine(quote);
+                    return;
+                }
+            }
+        } else if c == '"' || c == '\'' {
+            let e = string_end(line, i + 1, c, c == '"').unwrap_or(end).min(end);
+            emit(i..e, TokenKind::String);
+            i = e;
+        } else if c.is_ascii_digit()
+            || ((c == '-' || c == '+') && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            let e = rest[1..]
+                .char_indices()
+                .find(|&(_, c)| !(c.is_ascii_alphanumeric() || "_.:-+".contains(c)))
+                .map_or(end, |(p, _)| i + 1 + p);
+            emit(i..e, TokenKind::Number);
+            i = e;
+        } else if is_ident_start(c) {
+            let e = ident_end(line, i).min(end);
+            if matches!(&line[i..e], "true" | "false" | "inf" | "nan") {
+                emit(i..e, TokenKind::Keyword);
+            }
+            i = e;
+        } else {
+            i += c.len_utf8();
+        }
+    }
+}
+
+/// `key = ...` 中 key 的结束位置（不含空白）
+fn key_end(trimmed: &str) -> Option<usize> {
+    let eq = trimmed.find('=')?;
+    let key = trimmed[..eq].trim_end();
+    if key.is_empty() || key.contains('"') && !key.starts_with('"') {
+        return None;
+    }
+    Some(key.len())
+}
+
+fn markdown_line(line: &str, state: &mut LineState, emit: &mut Emit) {
+    let end = line_content_end(line);
+    let trimmed = line[..end].trim_start();
+    let indent = end - trimmed.len();
+
+    if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+        emit(indent..end, TokenKind::Code);
+        *state = if *state == LineState::CodeFence {
+            LineState::Normal
+        } else {
+            LineState::CodeFence
+        };
+        return;
+    }
+    if *state == LineState::CodeFence {
+        emit(0..end, TokenKind::Code);
+        return;
+    }
+    if trimmed.starts_with('#') {
+        emit(indent..end, TokenKind::Heading);
+        return;
+    }
+    if trimmed.starts_with('>') {
+        emit(indent..end, TokenKind::Comment);
+        return;
+    }
+
+    let mut i = indent;
+    // 列表标记
+    let marker = trimmed
+        .strip_prefix(['-', '*', '+'])
+        .filter(|r| r.starts_with(' '))
+        .map(|_| 1)
+        .or_else(|| {
+            let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+            (digits > 0 && trimmed[digits..].starts_with(". ")).then_some(digits + 1)
+        });
+    if let Some(len) = marker {
+        emit(i..i + len, TokenKind::Keyword);
+        i += len;
+    }
+
+    while i < end {
+        let rest = &line[i..end];
+        let c = rest.chars().next().unwrap_or(' ');
+        if c == '`' {
+            let e = rest[1..].find('`').map_or(end, |p| i + p + 2);
+            emit(i..e, TokenKind::Code);
+            i = e;
+        } else if rest.starts_with("**") || rest.starts_with("__") {
+            let delim = &rest[..2];
+            match rest[2..].find(delim) {
+                Some(p) => {
+                    let e = i + 2 + p + 2;
+                    emit(i..e, TokenKind::Emphasis);
+                    i = e;
+                }
+                None => i += 2,
+            }
+        } else if (c == '*' || c == '_') && rest.len() > 1 && !rest[1..].starts_with(' ') {
+            match rest[1..].find(c) {
+                Some(p) => {
+                    let e = i + 1 + p + 1;
+                    emit(i..e, TokenKind::Emphasis);
+                    i = e;
+                }
+                None => i += 1,
+            }
+        } else if c == '[' {
+            // [text](url)
+            match rest
+                .find("](")
+                .and_then(|p| rest[p..].find(')').map(|q| p + q + 1))
+            {
+                Some(len) => {
+                    emit(i..i + len, TokenKind::Link);
+                    i += len;
+                }
+                None => i += 1,
+            }
+        } else {
+            i += c.len_utf8();
+        }
+    }
+}
+
+fn json_line(line: &str, emit: &mut Emit) {
+    let end = line_content_end(line);
+    let mut i = 0;
+    while i < end {
+        let rest = &line[i..end];
+        let c = rest.chars().next().unwrap_or(' ');
+        if c == '"' {
+            let e = string_end(line, i + 1, '"', true).unwrap_or(end).min(end);
+            // 后面紧跟冒号的是键
+            let is_key = line[e..end].trim_start().starts_with(':');
+            emit(
+                i..e,
+                if is_key {
+                    TokenKind::Key
+                } else {
+                    TokenKind::String
+                },
+            );
+            i = e;
+        } else if c.is_ascii_digit() || c == '-' {
+            let e = rest[1..]
+                .char_indices()
+                .find(|&(_, c)| !(c.is_ascii_digit() || "eE.+-".contains(c)))
+                .map_or(end, |(p, _)| i + 1 + p);
+            emit(i..e, TokenKind::Number);
+            i = e;
+        } else if is_ident_start(c) {
+            let e = ident_end(line, i).min(end);
+            if matches!(&line[i..e], "true" | "false" | "null") {
+                emit(i..e, TokenKind::Keyword);
+            }
+            i = e;
+        } else if "{}[]:,".contains(c) {
+            emit(i..i + 1, TokenKind::Punctuation);
+            i += 1;
+        } else {
+            i += c.len_utf8();
+        }
+    }
+}
+
+fn shell_line(line: &str, state: &mut LineState, emit: &mut Emit) {
+    let end = line_content_end(line);
+    let mut i = 0;
+    if let LineState::Str(quote) = *state {
+        match string_end(line, 0, quote, quote == '"') {
+            Some(e) => {
+                emit(0..e, TokenKind::String);
+                i = e;
+                *state = LineState::Normal;
+            }
+            None => {
+                emit(0..end, TokenKind::String);
+                return;
+            }
+        }
+    }
+
+    while i < end {
+        let rest = &line[i..end];
+        let c = rest.chars().next().unwrap_or(' ');
+        let at_word_start = i == 0 || line[..i].ends_with(char::is_whitespace);
+        if c == '#' && at_word_start {
+            emit(i..end, TokenKind::Comment);
+            return;
+        } else if c == '"' || c == '\'' {
+            match string_end(line, i + 1, c, c == '"') {
+                Some(e) => {
+                    emit(i..e, TokenKind::String);
+                    i = e;
+                }
+                None => {
+                    emit(i..end, TokenKind::String);
+                    *state = LineState::Str(c);
+                    return;
+                }
+            }
+        } else if c == '$' {
+            let e = if rest.starts_with("${") {
+                rest.find('}').map_or(end, |p| i + p + 1)
+            } else {
+                let name_end = ident_end(line, i + 1).min(end);
+                // $1、$?、$@ 等特殊变量
+                if name_end == i + 1 {
+                    let next = rest[1..].chars().next().map_or(0, char::len_utf8);
+                    (i + 1 + next).min(end)
+                } else {
+                    name_end
+                }
+            };
+            emit(i..e, TokenKind::Variable);
+            i = e;
+        } else if c.is_ascii_digit() && at_word_start {
+            let e = number_end(line, i).min(end);
+            emit(i..e, TokenKind::Number);
+            i = e;
+        } else if is_ident_start(c) {
+            let e = ident_end(line, i).min(end);
+            if at_word_start && SHELL_KEYWORDS.contains(&&line[i..e]) {
+                emit(i..e, TokenKind::Keyword);
+            } else if line[e..end].starts_with("()") {
+                emit(i..e, TokenKind::Function);
+            }
+            i = e;
+        } else {
+            i += c.len_utf8();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(language: Language, line: &str) -> Vec<(String, TokenKind)> {
+        let mut state = LineState::Normal;
+        let mut tokens = Vec::new();
+        tokenize_line(language, line, &mut state, |range, kind| {
+            tokens.push((line[range].to_owned(), kind))
+        });
+        tokens
+    }
+
+    #[test]
+    fn shell_dollar_before_multibyte_char() {
+        let tokens = tokens(Language::Shell, "echo 总计$，完成\n");
+        assert!(tokens.contains(&("$，".to_owned(), TokenKind::Variable)));
+    }
+
+    #[test]
+    fn shell_dollar_at_line_end() {
+        let tokens = tokens(Language::Shell, "echo $");
+        assert_eq!(tokens.last(), Some(&("$".to_owned(), TokenKind::Variable)));
+    }
+}
@@ -0,0 +1,449 @@
+use crate::buffer::TextBuffer;
+use eframe::egui::text::CCursorRange;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// 连续输入间隔超过这个时间就开始新的撤销步骤
+const COALESCE_TIMEOUT: Duration = Duration::from_millis(1000);
+// 默认的撤销历史内存上限
+const DEFAULT_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// 一次最小的文本替换：把 `start` 处的 `removed` 替换成 `inserted`。
+#[derive(Clone, Debug)]
+pub struct EditOp {
+    pub start: usize, // 字符下标
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl EditOp {
+    fn byte_size(&self) -> usize {
+        self.removed.len() + self.inserted.len()
+    }
+
+    fn apply(&self, buffer: &mut TextBuffer) {
+        let end = self.start + self.removed.chars().count();
+        buffer.replace(self.start..end, &self.inserted);
+    }
+
+    fn revert(&self, buffer: &mut TextBuffer) {
+        let end = self.start + self.inserted.chars().count();
+        buffer.replace(self.start..end, &self.removed);
+    }
+
+    /// 应用后缓冲区增加的字符数
+    fn len_change(&self) -> isize {
+        self.inserted.chars().count() as isize - self.removed.chars().count() as isize
+    }
+}
+
+/// 编辑的类型，决定相邻的编辑能否合并成一个撤销步骤。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditKind {
+    Typing,
+    Delete,
+    Paste,
+    ReplaceAll,
+    Other,
+}
+
+impl EditKind {
+    /// 根据一次编辑的内容推断类型
+    pub fn classify(op: &EditOp) -> Self {
+        let single_char = |s: &str| s.chars().count() == 1 && s != "\n";
+        match (op.removed.is_empty(), op.inserted.is_empty()) {
+            (true, false) if single_char(&op.inserted) => EditKind::Typing,
+            (false, true) if single_char(&op.removed) => EditKind::Delete,
+            (_, false) => EditKind::Paste,
+            _ => EditKind::Other,
+        }
+    }
+}
+
+struct Group {
+    id: u64,
+    kind: EditKind,
+    ops: Vec<EditOp>,
+    cursor_before: Option<CCursorRange>,
+    cursor_after: Option<CCursorRange>,
+    last_edit: Instant,
+}
+
+impl Group {
+    fn byte_size(&self) -> usize {
+        self.ops.iter().map(EditOp::byte_size).sum()
+    }
+
+    /// 尝试把连续输入 / 连续删除合并进当前步骤。多个光标时 `ops` 和步骤中的修改
+    /// 一一对应，每一处都要能合并；否则不改动当前步骤
+    fn try_merge(
+        &mut self,
+        ops: &[EditOp],
+        kind: EditKind,
+        cursor_before: Option<CCursorRange>,
+    ) -> bool {
+        if kind != self.kind
+            || !matches!(kind, EditKind::Typing | EditKind::Delete)
+            || self.last_edit.elapsed() > COALESCE_TIMEOUT
+            || cursor_before != self.cursor_after
+            || ops.len() != self.ops.len()
+        {
+            return false;
+        }
+        let mut merged = self.ops.clone();
+        // 前面的修改合并后变长或变短，后面的修改位置跟着移动
+        let mut shift = 0isize;
+        for (last, op) in merged.iter_mut().zip(ops) {
+            last.start = (last.start as isize + shift) as usize;
+            let before = last.len_change();
+            if !merge_op(last, op, kind) {
+                return false;
+            }
+            shift += last.len_change() - before;
+        }
+        self.ops = merged;
+        true
+    }
+}
+
+/// 把紧接着 `last` 的一次输入或删除并进去
+fn merge_op(last: &mut EditOp, op: &EditOp, kind: EditKind) -> bool {
+    match kind {
+        EditKind::Typing if op.start == last.start + last.inserted.chars().count() => {
+            // 单词结束后的空白另起一步，撤销时按词回退
+            let word_ended = op.inserted.chars().all(char::is_whitespace)
+                && !last.inserted.ends_with(char::is_whitespace);
+            if word_ended {
+                return false;
+            }
+            last.inserted.push_str(&op.inserted);
+        }
+        // Backspace：向前删除
+        EditKind::Delete if op.start + 1 == last.start => {
+            last.removed.insert_str(0, &op.removed);
+            last.start = op.start;
+        }
+        // Delete：向后删除
+        EditKind::Delete if op.start == last.start => {
+            last.removed.push_str(&op.removed);
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// 文档的撤销 / 重做历史。
+///
+/// 每个撤销步骤（`Group`）可以包含多次编辑，例如全部替换；
+/// 连续输入会被合并成一步。总大小超过 `max_bytes` 时丢弃最早的步骤。
+pub struct History {
+    undo: VecDeque<Group>,
+    redo: Vec<Group>,
+    max_bytes: usize,
+    used_bytes: usize,
+    next_id: u64,
+    // 撤销栈为空时的状态标识；丢弃最早的步骤后变成被丢弃步骤的标识，
+    // 这样全部撤销后不会和最初（例如刚打开文件时）的状态混淆
+    base_id: u64,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::with_budget(DEFAULT_MAX_BYTES)
+    }
+}
+
+impl History {
+    pub fn with_budget(max_bytes: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            max_bytes,
+            used_bytes: 0,
+            next_id: 1,
+            base_id: 0,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// 当前内容对应的状态标识，用来判断文档是否回到了保存时的状态
+    pub fn state_id(&self) -> u64 {
+        self.undo.back().map_or(self.base_id, |g| g.id)
+    }
+
+    /// 记录一次已经作用到缓冲区上的编辑
+    pub fn record(
+        &mut self,
+        op: EditOp,
+        cursor_before: Option<CCursorRange>,
+        cursor_after: Option<CCursorRange>,
+    ) {
+        self.record_all(vec![op], cursor_before, cursor_after);
+    }
+
+    /// 记录多个光标同时作用到缓冲区上的编辑，作为一个撤销步骤；`ops` 需按应用顺序排列。
+    /// 每个光标都在连续输入 / 删除时，和单个光标一样合并进上一步
+    pub fn record_all(
+        &mut self,
+        ops: Vec<EditOp>,
+        cursor_before: Option<CCursorRange>,
+        cursor_after: Option<CCursorRange>,
+    ) {
+        let Some(first) = ops.first() else {
+            return;
+        };
+        let kind = EditKind::classify(first);
+        let kind = if ops.iter().all(|op| EditKind::classify(op) == kind) {
+            kind
+        } else {
+            EditKind::Other
+        };
+        self.redo.clear();
+        let id = self.fresh_id();
+        if let Some(group) = self.undo.back_mut() {
+            if group.try_merge(&ops, kind, cursor_before) {
+                group.id = id;
+                group.cursor_after = cursor_after;
+                group.last_edit = Instant::now();
+                self.used_bytes += ops.iter().map(EditOp::byte_size).sum::<usize>();
+                self.enforce_budget();
+                return;
+            }
+        }
+        self.push_group(kind, ops, cursor_before, cursor_after);
+    }
+
+    /// 把多次编辑记录为一个撤销步骤（例如全部替换），`ops` 需按应用顺序排列
+    pub fn record_group(
+        &mut self,
+        kind: EditKind,
+        ops: Vec<EditOp>,
+        cursor_before: Option<CCursorRange>,
+        cursor_after: Option<CCursorRange>,
+    ) {
+        if ops.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.push_group(kind, ops, cursor_before, cursor_after);
+    }
+
+    /// 下一次编辑不再和之前的输入合并
+    pub fn break_coalescing(&mut self) {
+        if let Some(group) = self.undo.back_mut() {
+            group.kind = EditKind::Other;
+        }
+    }
+
+    /// 撤销一步，返回撤销后应恢复的光标
+    pub fn undo(&mut self, buffer: &mut TextBuffer) -> Option<Option<CCursorRange>> {
+        let group = self.undo.pop_back()?;
+        for op in group.ops.iter().rev() {
+            op.revert(buffer);
+        }
+        self.used_bytes -= group.byte_size();
+        let cursor = group.cursor_before;
+        self.redo.push(group);
+        Some(cursor)
+    }
+
+    /// 重做一步，返回重做后应恢复的光标
+    pub fn redo(&mut self, buffer: &mut TextBuffer) -> Option<Option<CCursorRange>> {
+        let mut group = self.redo.pop()?;
+        for op in &group.ops {
+            op.apply(buffer);
+        }
+        group.kind = EditKind::Other;
+        self.used_bytes += group.byte_size();
+        let cursor = group.cursor_after;
+        self.undo.push_back(group);
+        // 和 record 一样，重做回来的步骤也计入预算
+        self.enforce_budget();
+        Some(cursor)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.used_bytes = 0;
+        self.base_id = self.fresh_id();
+    }
+
+    fn push_group(
+        &mut self,
+        kind: EditKind,
+        ops: Vec<EditOp>,
+        cursor_before: Option<CCursorRange>,
+        cursor_after: Option<CCursorRange>,
+    ) {
+        let group = Group {
+            id: self.fresh_id(),
+            kind,
+            ops,
+            cursor_before,
+            cursor_after,
+            last_edit: Instant::now(),
+        };
+        self.used_bytes += group.byte_size();
+        self.undo.push_back(group);
+        self.enforce_budget();
+    }
+
+    fn enforce_budget(&mut self) {
+        // 至少保留最近的一步，即使它本身就超出了预算
+        while self.used_bytes > self.max_bytes && self.undo.len() > 1 {
+            if let Some(group) = self.undo.pop_front() {
+                self.used_bytes -= group.byte_size();
+                self.base_id = group.id;
+            }
+        }
+    }
+
+    fn fresh_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(history: &mut History, buffer: &mut TextBuffer, start: usize, text: &str) {
+        buffer.insert(start, text);
+        let op = EditOp {
+            start,
+            removed: String::new(),
+            inserted: text.to_owned(),
+        };
+        history.record_group(EditKind::Paste, vec![op], None, None);
+    }
+
+    #[test]
+    fn undo_restores_initial_state() {
+        let mut history = History::default();
+        let mut buffer = TextBuffer::from_text("x");
+        let initial = history.state_id();
+        insert(&mut history, &mut buffer, 1, "abc");
+        assert_ne!(history.state_id(), initial);
+        history.undo(&mut buffer);
+        assert_eq!(history.state_id(), initial);
+        assert_eq!(buffer.to_string(), "x");
+    }
+
+    #[test]
+    fn undo_after_eviction_is_not_initial_state() {
+        let mut history = History::with_budget(4);
+        let mut buffer = TextBuffer::new();
+        let initial = history.state_id();
+        insert(&mut history, &mut buffer, 0, "abc");
+        let first = history.state_id();
+        insert(&mut history, &mut buffer, 3, "def");
+        // 第一步被丢弃，只能撤销到它之后的状态
+        while history.undo(&mut buffer).is_some() {}
+        assert_eq!(buffer.to_string(), "abc");
+        assert_ne!(history.state_id(), initial);
+        assert_eq!(history.state_id(), first);
+    }
+
+    #[test]
+    fn clear_starts_a_new_state() {
+        let mut history = History::default();
+        let initial = history.state_id();
+        history.clear();
+        assert_ne!(history.state_id(), initial);
+    }
+
+    fn op(start: usize, removed: &str, inserted: &str) -> EditOp {
+        EditOp {
+            start,
+            removed: removed.to_owned(),
+            inserted: inserted.to_owned(),
+        }
+    }
+
+    /// 像编辑器视图那样应用一帧内的修改并记录
+    fn frame(history: &mut History, buffer: &mut TextBuffer, ops: Vec<EditOp>) {
+        for op in &ops {
+            op.apply(buffer);
+        }
+        history.record_all(ops, None, None);
+    }
+
+    #[test]
+    fn multi_cursor_typing_coalesces() {
+        let mut history = History::default();
+        let mut buffer = TextBuffer::from_text("one\ntwo\n");
+        // 两个光标在两行的行首依次输入 "ab"
+        frame(
+            &mut history,
+            &mut buffer,
+            vec![op(0, "", "a"), op(5, "", "a")],
+        );
+        frame(
+            &mut history,
+            &mut buffer,
+            vec![op(1, "", "b"), op(7, "", "b")],
+        );
+        assert_eq!(buffer.to_string(), "abone\nabtwo\n");
+        // 单词结束后的空格另起一步
+        frame(
+            &mut history,
+            &mut buffer,
+            vec![op(2, "", " "), op(9, "", " ")],
+        );
+        assert_eq!(buffer.to_string(), "ab one\nab two\n");
+
+        history.undo(&mut buffer);
+        assert_eq!(buffer.to_string(), "abone\nabtwo\n");
+        history.undo(&mut buffer);
+        assert_eq!(buffer.to_string(), "one\ntwo\n");
+        assert!(!history.can_undo());
+        history.redo(&mut buffer);
+        assert_eq!(buffer.to_string(), "abone\nabtwo\n");
+    }
+
+    #[test]
+    fn multi_cursor_backspace_coalesces() {
+        let mut history = History::default();
+        let mut buffer = TextBuffer::from_text("abc\nabc\n");
+        // 两个光标在两行的行尾依次按 Backspace
+        frame(
+            &mut history,
+            &mut buffer,
+            vec![op(2, "c", ""), op(5, "c", "")],
+        );
+        frame(
+            &mut history,
+            &mut buffer,
+            vec![op(1, "b", ""), op(3, "b", "")],
+        );
+        assert_eq!(buffer.to_string(), "a\na\n");
+        history.undo(&mut buffer);
+        assert_eq!(buffer.to_string(), "abc\nabc\n");
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn frames_with_different_cursor_counts_do_not_merge() {
+        let mut history = History::default();
+        let mut buffer = TextBuffer::from_text("xy");
+        frame(
+            &mut history,
+            &mut buffer,
+            vec![op(0, "", "a"), op(2, "", "a")],
+        );
+        frame(&mut history, &mut buffer, vec![op(1, "", "b")]);
+        assert_eq!(buffer.to_string(), "abxay");
+        history.undo(&mut buffer);
+        assert_eq!(buffer.to_string(), "axay");
+    }
+}
@@ -0,0 +1,142 @@
+use crate::buffer::TextBuffer;
+use crate::history::EditOp;
+use std::fmt;
+
+// 检测缩进时最多看多少行
+const DETECT_LINES: usize = 1_000;
+
+/// 缩进方式：按 Tab 键时插入制表符还是若干空格
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Indent {
+    Tabs,
+    Spaces(usize),
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::Spaces(4)
+    }
+}
+
+impl fmt::Display for Indent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Indent::Tabs => f.write_str("Tabs"),
+            Indent::Spaces(n) => write!(f, "Spaces: {}", n),
+        }
+    }
+}
+
+impl Indent {
+    /// 状态栏菜单里可选的缩进方式
+    pub const CHOICES: [Indent; 4] = [
+        Indent::Spaces(2),
+        Indent::Spaces(4),
+        Indent::Spaces(8),
+        Indent::Tabs,
+    ];
+
+    /// 一级缩进的宽度（列数）
+    pub fn width(self) -> usize {
+        match self {
+            Indent::Tabs => 4,
+            Indent::Spaces(n) => n,
+        }
+    }
+
+    /// 根据文件开头的缩进推测：制表符缩进的行多就用 Tabs，
+    /// 否则取相邻两行缩进差值中最常见的一个。没有缩进时返回 `None`。
+    pub fn detect(text: &str) -> Option<Indent> {
+        let mut tab_lines = 0;
+        let mut space_lines = 0;
+        // 缩进差为 2 / 4 / 8 的次数
+        let mut deltas = [0usize; 3];
+        let mut previous = 0;
+        for line in text.lines().take(DETECT_LINES) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.starts_with('\t') {
+                tab_lines += 1;
+                continue;
+            }
+            let width = line.len() - line.trim_start_matches(' ').len();
+            if width > 0 {
+                space_lines += 1;
+            }
+            match width.abs_diff(previous) {
+                2 => deltas[0] += 1,
+                4 => deltas[1] += 1,
+                8 => deltas[2] += 1,
+                _ => {}
+            }
+            previous = width;
+        }
+        if tab_lines == 0 && space_lines == 0 {
+            return None;
+        }
+        if tab_lines > space_lines {
+            return Some(Indent::Tabs);
+        }
+        let (index, count) = deltas
+            .iter()
+            .enumerate()
+            .max_by_key(|&(i, &count)| (count, std::cmp::Reverse(i)))?;
+        if *count == 0 {
+            return None;
+        }
+        Some(Indent::Spaces([2, 4, 8][index]))
+    }
+
+    /// 光标在第 `col` 列时按一次 Tab 要插入的文本
+    pub fn text_at(self, col: usize) -> String {
+        match self {
+            Indent::Tabs => "\t".to_owned(),
+            Indent::Spaces(n) => " ".repeat(n - col % n),
+        }
+    }
+}
+
+/// 把每一行的行首缩进转换成 `to` 指定的方式，`from_width` 是原来一个制表符的宽度。
+/// 返回的修改按 `Document::apply_edits` 的约定排列。
+pub fn convert_indentation(buffer: &TextBuffer, to: Indent, from_width: usize) -> Vec<EditOp> {
+    let mut ops = Vec::new();
+    let mut delta: isize = 0;
+    for line in 0..buffer.len_lines() {
+        let text = buffer.line_text(line);
+        let leading: String = text
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        if leading.is_empty() {
+            continue;
+        }
+        // 行首缩进的显示宽度
+        let mut width = 0;
+        for c in leading.chars() {
+            width = if c == '\t' {
+                width + from_width - width % from_width
+            } else {
+                width + 1
+            };
+        }
+        let replaced = match to {
+            Indent::Tabs => {
+                let tab = from_width.max(1);
+                format!("{}{}", "\t".repeat(width / tab), " ".repeat(width % tab))
+            }
+            Indent::Spaces(_) => " ".repeat(width),
+        };
+        if replaced == leading {
+            continue;
+        }
+        let start = (buffer.line_to_char(line) as isize + delta) as usize;
+        delta += replaced.chars().count() as isize - leading.chars().count() as isize;
+        ops.push(EditOp {
+            start,
+            removed: leading,
+            inserted: replaced,
+        });
+    }
+    ops
+}
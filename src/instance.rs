@@ -0,0 +1,412 @@
+//! 单实例：已经有编辑器在运行时，新启动的进程把要打开的文件交给它，然后退出
+//! （`--wait` 时等到文件被关闭再退出）。
+//!
+//! 运行中的编辑器在 127.0.0.1 上监听一个随机端口，端口号和一个随机口令写在只有当前用户
+//! 能访问的目录里（`$XDG_RUNTIME_DIR` 或缓存目录）。
+//! 协议是逐行的文本，路径和标准输入的内容不限字符，写明字节数后紧跟原始内容：
+//!
+//! ```text
+//! token <口令>
+//! folder <字节数>            后面紧跟路径
+//! file <行> <列> <字节数>    行列从 0 开始，没有位置时写 `-`；后面紧跟路径
+//! stdin <字节数>             后面紧跟原始内容
+//! wait
+//! end
+//! ```
+//!
+//! 编辑器收到 `end` 后回复 `ok`；如果请求了 `wait`，文件都关闭后再回复 `closed`。
+//! 每个连接在单独的线程中处理，一个卡住的连接不会挡住其他进程。
+
+use crate::buffer::LineCol;
+use crate::cli::{CliArgs, FileTarget};
+use eframe::egui;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 另一个进程发来的打开请求
+pub struct OpenRequest {
+    pub args: CliArgs,
+    /// 请求了 `--wait` 时保留连接，文件关闭后通知对方
+    pub wait: Option<TcpStream>,
+}
+
+/// 等待文档关闭的一方
+pub enum Waiter {
+    /// 另一个进程在等待
+    Remote(TcpStream),
+    /// 本进程是以 `--wait` 启动的，文件关闭后退出
+    Exit,
+}
+
+/// `ids` 中的文档都关闭后通知 `waiter`
+pub struct PendingWait {
+    pub ids: Vec<usize>,
+    pub waiter: Waiter,
+}
+
+/// 接收其他进程打开请求的后台线程
+pub struct InstanceServer {
+    receiver: Receiver<OpenRequest>,
+    info_path: PathBuf,
+    token: String,
+}
+
+impl InstanceServer {
+    /// 开始监听，并把端口写到私有目录，之后启动的进程会连到这里
+    pub fn start(ctx: &egui::Context) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let port = listener.local_addr()?.port();
+        let token = random_token()?;
+        let info_path = info_path().ok_or_else(|| io::Error::other("找不到存放端口的目录"))?;
+        write_info(&info_path, &format!("{} {}", port, token))?;
+
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let expected = token.clone();
+        thread::Builder::new()
+            .name("instance-server".to_owned())
+            .spawn(move || accept_loop(&listener, &expected, &sender, &ctx))?;
+        Ok(Self {
+            receiver,
+            info_path,
+            token,
+        })
+    }
+
+    /// 取出收到的请求
+    pub fn poll(&self) -> Vec<OpenRequest> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+impl Drop for InstanceServer {
+    fn drop(&mut self) {
+        // 只删除自己写的文件；之后又启动了新窗口时文件已经属于它
+        let ours = fs::read_to_string(&self.info_path)
+            .is_ok_and(|info| info.split_whitespace().nth(1) == Some(self.token.as_str()));
+        if ours {
+            let _ = fs::remove_file(&self.info_path);
+        }
+    }
+}
+
+impl Waiter {
+    pub fn notify(self, ctx: &egui::Context) {
+        match self {
+            Waiter::Remote(mut stream) => {
+                let _ = stream.write_all(b"closed\n");
+            }
+            Waiter::Exit => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+        }
+    }
+}
+
+/// 把参数交给已经运行的编辑器。没有正在运行的编辑器时返回 `Ok(false)`。
+pub fn forward(args: &CliArgs) -> io::Result<bool> {
+    let Some(path) = info_path().filter(|path| is_private(path)) else {
+        return Ok(false);
+    };
+    let Ok(info) = fs::read_to_string(path) else {
+        return Ok(false);
+    };
+    let mut parts = info.split_whitespace();
+    let (Some(port), Some(token)) = (parts.next().and_then(|p| p.parse().ok()), parts.next())
+    else {
+        return Ok(false);
+    };
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let Ok(mut stream) = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) else {
+        // 上次的编辑器没有正常退出，留下了过期的文件
+        return Ok(false);
+    };
+
+    stream.write_all(&encode_request(args, token))?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim() != "ok" {
+        return Err(io::Error::other("编辑器拒绝了请求"));
+    }
+    if args.wait {
+        // 阻塞到文件被关闭（或者编辑器退出、连接断开）
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+    Ok(true)
+}
+
+fn accept_loop(
+    listener: &TcpListener,
+    token: &str,
+    sender: &Sender<OpenRequest>,
+    ctx: &egui::Context,
+) {
+    for stream in listener.incoming().flatten() {
+        let token = token.to_owned();
+        let sender = sender.clone();
+        let ctx = ctx.clone();
+        let spawned = thread::Builder::new()
+            .name("instance-client".to_owned())
+            .spawn(move || match read_request(stream, &token) {
+                Ok(request) => {
+                    // 发送失败说明编辑器已经退出
+                    if sender.send(request).is_ok() {
+                        ctx.request_repaint();
+                    }
+                }
+                Err(err) => log::warn!("无法处理打开请求：{}", err),
+            });
+        if let Err(err) = spawned {
+            log::warn!("无法处理打开请求：{}", err);
+        }
+    }
+}
+
+fn encode_request(args: &CliArgs, token: &str) -> Vec<u8> {
+    let mut request = format!("token {}\n", token).into_bytes();
+    for folder in &args.folders {
+        let path = path_to_bytes(folder);
+        request.extend(format!("folder {}\n", path.len()).bytes());
+        request.extend(path);
+    }
+    for file in &args.files {
+        let position = file
+            .position
+            .map_or("- -".to_owned(), |p| format!("{} {}", p.line, p.col));
+        let path = path_to_bytes(&file.path);
+        request.extend(format!("file {} {}\n", position, path.len()).bytes());
+        request.extend(path);
+    }
+    if let Some(bytes) = &args.stdin {
+        request.extend(format!("stdin {}\n", bytes.len()).bytes());
+        request.extend(bytes);
+    }
+    if args.wait {
+        request.extend(b"wait\n");
+    }
+    request.extend(b"end\n");
+    request
+}
+
+fn read_request(stream: TcpStream, token: &str) -> io::Result<OpenRequest> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let args = decode_request(&mut reader, token)?;
+
+    let mut stream = stream;
+    stream.set_read_timeout(None)?;
+    stream.write_all(b"ok\n")?;
+    let wait = args.wait.then_some(stream);
+    Ok(OpenRequest { args, wait })
+}
+
+fn decode_request(reader: &mut impl BufRead, token: &str) -> io::Result<CliArgs> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+    // 标明了字节数的内容
+    let read_bytes = |reader: &mut dyn BufRead, len: &str| -> io::Result<Vec<u8>> {
+        let len: usize = len.parse().map_err(|_| invalid("无效的长度"))?;
+        let mut bytes = Vec::new();
+        reader.take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() < len {
+            return Err(invalid("请求不完整"));
+        }
+        Ok(bytes)
+    };
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim_end().strip_prefix("token ") != Some(token) {
+        return Err(invalid("口令不正确"));
+    }
+
+    let mut args = CliArgs::default();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("请求不完整"));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "folder" => args
+                .folders
+                .push(path_from_bytes(read_bytes(reader, rest)?)),
+            "file" => {
+                let mut parts = rest.splitn(3, ' ');
+                let line = parts.next().and_then(|s| s.parse().ok());
+                let col = parts.next().and_then(|s| s.parse().ok());
+                let len = parts.next().ok_or_else(|| invalid("缺少路径"))?;
+                args.files.push(FileTarget {
+                    path: path_from_bytes(read_bytes(reader, len)?),
+                    position: line.map(|line| LineCol {
+                        line,
+                        col: col.unwrap_or(0),
+                    }),
+                });
+            }
+            "stdin" => args.stdin = Some(read_bytes(reader, rest)?),
+            "wait" => args.wait = true,
+            "end" => return Ok(args),
+            _ => return Err(invalid("未知的请求")),
+        }
+    }
+}
+
+/// 路径的原始字节，不是 UTF-8 的文件名也原样传过去
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    std::ffi::OsString::from_vec(bytes).into()
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    String::from_utf8_lossy(&bytes).into_owned().into()
+}
+
+/// 记录端口的文件，放在只有当前用户能访问的目录里
+fn info_path() -> Option<PathBuf> {
+    let dir = dirs::runtime_dir().or_else(dirs::cache_dir)?;
+    Some(dir.join("editor-rs").join("instance"))
+}
+
+/// 新建只有自己能读写的文件写入端口和口令；之前留下的文件先删掉
+fn write_info(path: &Path, info: &str) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| io::Error::other("无效的路径"))?;
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)?;
+    if !is_private(dir) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} 可以被其他用户访问", dir.display()),
+        ));
+    }
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(info.as_bytes())
+}
+
+/// 属于当前用户、其他用户无法读写、并且不是符号链接
+#[cfg(unix)]
+fn is_private(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    // SAFETY: getuid 总是成功，没有副作用
+    let uid = unsafe { libc::getuid() };
+    fs::symlink_metadata(path).is_ok_and(|metadata| {
+        !metadata.file_type().is_symlink() && metadata.uid() == uid && metadata.mode() & 0o077 == 0
+    })
+}
+
+#[cfg(not(unix))]
+fn is_private(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| !metadata.file_type().is_symlink())
+}
+
+/// 从操作系统的安全随机数源取 128 位口令
+fn random_token() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(io::Error::other)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(args: &CliArgs) -> io::Result<CliArgs> {
+        let request = encode_request(args, "secret");
+        decode_request(&mut &request[..], "secret")
+    }
+
+    #[test]
+    fn request_round_trip_keeps_unusual_paths() {
+        let args = CliArgs {
+            files: vec![
+                FileTarget {
+                    path: PathBuf::from("/tmp/a\nfile 0 0 3\nend.txt"),
+                    position: Some(LineCol { line: 4, col: 2 }),
+                },
+                FileTarget {
+                    path: PathBuf::from("/tmp/空格 文件.rs"),
+                    position: None,
+                },
+            ],
+            folders: vec![PathBuf::from("/tmp/dir\r\n")],
+            stdin: Some(b"line\nend\n".to_vec()),
+            wait: true,
+            ..CliArgs::default()
+        };
+        let decoded = round_trip(&args).unwrap();
+        assert_eq!(decoded.folders, args.folders);
+        assert_eq!(decoded.files.len(), 2);
+        for (a, b) in decoded.files.iter().zip(&args.files) {
+            assert_eq!(a.path, b.path);
+            assert_eq!(a.position, b.position);
+        }
+        assert_eq!(decoded.stdin, args.stdin);
+        assert!(decoded.wait);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn request_round_trip_keeps_non_utf8_paths() {
+        let path = path_from_bytes(b"/tmp/\xff\xfe".to_vec());
+        let args = CliArgs {
+            folders: vec![path.clone()],
+            ..CliArgs::default()
+        };
+        assert_eq!(round_trip(&args).unwrap().folders, vec![path]);
+    }
+
+    #[test]
+    fn request_rejects_wrong_token_and_truncated_input() {
+        let request = encode_request(&CliArgs::default(), "secret");
+        assert!(decode_request(&mut &request[..], "other").is_err());
+
+        let args = CliArgs {
+            folders: vec![PathBuf::from("/tmp/folder")],
+            ..CliArgs::default()
+        };
+        let request = encode_request(&args, "secret");
+        let truncated = &request[..request.len() - 10];
+        assert!(decode_request(&mut &truncated[..], "secret").is_err());
+    }
+
+    #[test]
+    fn tokens_are_random() {
+        let a = random_token().unwrap();
+        assert_eq!(a.len(), 32);
+        assert_ne!(a, random_token().unwrap());
+    }
+}
@@ -0,0 +1,698 @@
+//! 快捷键：默认绑定加上配置目录下 `keybindings.toml` 中的用户绑定。
+//!
+//! ```toml
+//! [[bindings]]
+//! key = "Ctrl+K Ctrl+C"      # 两个按键组成的组合键用空格分开
+//! command = "edit.find"
+//! when = "editorFocus"       # 可选：editorFocus、findBarOpen，前面加 ! 表示取反，多个条件用 && 连接
+//!
+//! [[bindings]]
+//! key = "Ctrl+F"
+//! command = "-edit.find"     # 去掉这个键原来对 edit.find 的绑定
+//! ```
+
+use crate::command::Command;
+use eframe::egui::{self, Key, KeyboardShortcut, ModifierNames, Modifiers};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// 快捷键生效的场景
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Context {
+    /// 焦点在编辑区
+    EditorFocus,
+    /// 查找栏打开着
+    FindBarOpen,
+}
+
+/// `when` 条件：某个场景成立（或不成立）时才生效
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Condition {
+    pub context: Context,
+    pub negated: bool,
+}
+
+/// 当前处于哪些场景，由应用每帧提供
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeyContext {
+    pub editor_focus: bool,
+    pub find_bar_open: bool,
+}
+
+impl Condition {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (negated, name) = match text.strip_prefix('!') {
+            Some(name) => (true, name.trim()),
+            None => (false, text),
+        };
+        let context = match name {
+            "editorFocus" => Context::EditorFocus,
+            "findBarOpen" => Context::FindBarOpen,
+            _ => return None,
+        };
+        Some(Self { context, negated })
+    }
+
+    /// 解析用 `&&` 连接的多个条件，排好序以便比较
+    fn parse_all(text: &str) -> Option<Vec<Self>> {
+        let mut conditions: Vec<Self> = text.split("&&").map(Self::parse).collect::<Option<_>>()?;
+        conditions.sort();
+        conditions.dedup();
+        Some(conditions)
+    }
+
+    fn holds(self, context: &KeyContext) -> bool {
+        let value = match self.context {
+            Context::EditorFocus => context.editor_focus,
+            Context::FindBarOpen => context.find_bar_open,
+        };
+        value != self.negated
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Binding {
+    /// 一个按键，或者两个按键组成的组合键（例如 Ctrl+K Ctrl+C）
+    pub keys: Vec<KeyboardShortcut>,
+    pub command: Command,
+    /// 全部成立时才生效；为空时总是生效
+    pub when: Vec<Condition>,
+}
+
+impl Binding {
+    fn new(keys: &[KeyboardShortcut], command: Command) -> Self {
+        Self {
+            keys: keys.to_vec(),
+            command,
+            when: Vec::new(),
+        }
+    }
+
+    fn when(mut self, condition: Condition) -> Self {
+        self.when.push(condition);
+        self.when.sort();
+        self
+    }
+
+    fn applies(&self, context: &KeyContext) -> bool {
+        self.when.iter().all(|condition| condition.holds(context))
+    }
+
+    /// 两个绑定的条件能否同时成立：只有某个场景一边要求成立、一边要求不成立时才不能
+    fn overlaps(&self, other: &Binding) -> bool {
+        !self.when.iter().any(|a| {
+            other
+                .when
+                .iter()
+                .any(|b| a.context == b.context && a.negated != b.negated)
+        })
+    }
+}
+
+/// 快捷键文件中的一项
+#[derive(Deserialize)]
+struct BindingEntry {
+    key: String,
+    command: String,
+    when: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct KeymapFile {
+    bindings: Vec<BindingEntry>,
+}
+
+fn default_bindings() -> Vec<Binding> {
+    let cmd = |key| KeyboardShortcut::new(Modifiers::COMMAND, key);
+    let cmd_shift = |key| KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, key);
+    let ctrl = |key| KeyboardShortcut::new(Modifiers::CTRL, key);
+    let ctrl_shift = |key| KeyboardShortcut::new(Modifiers::CTRL | Modifiers::SHIFT, key);
+    let plain = |key| KeyboardShortcut::new(Modifiers::NONE, key);
+    let shift = |key| KeyboardShortcut::new(Modifiers::SHIFT, key);
+    let cmd_alt = |key| KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::ALT, key);
+    let find_bar_open = Condition {
+        context: Context::FindBarOpen,
+        negated: false,
+    };
+    let find_bar_closed = Condition {
+        negated: true,
+        ..find_bar_open
+    };
+    let editor_focus = Condition {
+        context: Context::EditorFocus,
+        negated: false,
+    };
+    vec![
+        Binding::new(&[cmd(Key::N)], Command::NewFile),
+        Binding::new(&[cmd(Key::O)], Command::Open),
+        Binding::new(&[cmd(Key::S)], Command::Save),
+        Binding::new(&[cmd_shift(Key::S)], Command::SaveAs),
+        Binding::new(&[cmd(Key::K), plain(Key::S)], Command::SaveAll),
+        Binding::new(&[cmd(Key::W)], Command::Close),
+        Binding::new(&[cmd(Key::Comma)], Command::Settings),
+        Binding::new(&[cmd(Key::Z)], Command::Undo).when(editor_focus),
+        Binding::new(&[cmd_shift(Key::Z)], Command::Redo).when(editor_focus),
+        Binding::new(&[cmd(Key::Y)], Command::Redo).when(editor_focus),
+        Binding::new(&[cmd(Key::F)], Command::Find),
+        Binding::new(&[cmd(Key::H)], Command::Replace),
+        Binding::new(&[cmd_shift(Key::F)], Command::FindInFiles),
+        Binding::new(&[plain(Key::F3)], Command::FindNext),
+        Binding::new(&[shift(Key::F3)], Command::FindPrevious),
+        Binding::new(&[plain(Key::Escape)], Command::CloseFindBar).when(find_bar_open),
+        Binding::new(&[cmd(Key::D)], Command::AddNextOccurrence).when(editor_focus),
+        Binding::new(&[cmd_alt(Key::ArrowUp)], Command::AddCursorAbove).when(editor_focus),
+        Binding::new(&[cmd_alt(Key::ArrowDown)], Command::AddCursorBelow).when(editor_focus),
+        // 查找栏打开时 Escape 先关闭查找栏
+        Binding::new(&[plain(Key::Escape)], Command::SingleCursor)
+            .when(editor_focus)
+            .when(find_bar_closed),
+        Binding::new(&[ctrl(Key::Tab)], Command::NextTab),
+        Binding::new(&[ctrl_shift(Key::Tab)], Command::PreviousTab),
+        Binding::new(&[cmd(Key::Backslash)], Command::SplitRight),
+        Binding::new(&[cmd(Key::K), cmd(Key::Backslash)], Command::SplitDown),
+        Binding::new(&[cmd(Key::K), plain(Key::W)], Command::ClosePane),
+        Binding::new(&[cmd(Key::K), cmd(Key::ArrowLeft)], Command::FocusLeftPane),
+        Binding::new(
+            &[cmd(Key::K), cmd(Key::ArrowRight)],
+            Command::FocusRightPane,
+        ),
+        Binding::new(&[cmd(Key::K), cmd(Key::ArrowUp)], Command::FocusPaneAbove),
+        Binding::new(&[cmd(Key::K), cmd(Key::ArrowDown)], Command::FocusPaneBelow),
+        Binding::new(&[cmd_shift(Key::OpenBracket)], Command::Fold),
+        Binding::new(&[cmd_shift(Key::CloseBracket)], Command::Unfold),
+        // 美式键盘上按住 Shift 时得到的是 `{` 和 `}`
+        Binding::new(&[cmd_shift(Key::OpenCurlyBracket)], Command::Fold),
+        Binding::new(&[cmd_shift(Key::CloseCurlyBracket)], Command::Unfold),
+        Binding::new(
+            &[cmd(Key::K), cmd(Key::CloseBracket)],
+            Command::UnfoldRecursively,
+        ),
+        Binding::new(&[cmd(Key::K), cmd(Key::Num0)], Command::FoldAll),
+        Binding::new(&[cmd(Key::K), cmd(Key::J)], Command::UnfoldAll),
+        Binding::new(&[cmd(Key::K), cmd(Key::Num1)], Command::FoldLevel1),
+        Binding::new(&[cmd(Key::K), cmd(Key::Num2)], Command::FoldLevel2),
+        Binding::new(&[cmd(Key::K), cmd(Key::Num3)], Command::FoldLevel3),
+        Binding::new(&[cmd(Key::K), cmd(Key::Num4)], Command::FoldLevel4),
+        Binding::new(&[cmd(Key::K), cmd(Key::Num5)], Command::FoldLevel5),
+        Binding::new(&[cmd(Key::B)], Command::ToggleExplorer),
+        Binding::new(&[cmd_shift(Key::P)], Command::CommandPalette),
+        Binding::new(&[cmd(Key::P)], Command::GoToFile),
+        Binding::new(&[cmd(Key::G)], Command::GoToLine),
+        Binding::new(&[plain(Key::F1)], Command::About),
+    ]
+}
+
+/// 快捷键表：把按键分派成命令，并给菜单提供要显示的快捷键
+#[derive(Default)]
+pub struct Keymap {
+    bindings: Vec<Binding>,
+    /// 组合键已经按下了第一个键，等待第二个
+    pending: Option<KeyboardShortcut>,
+    /// 读取快捷键文件时的错误和冲突
+    pub load_error: Option<String>,
+    path: Option<PathBuf>,
+    watcher: Option<RecommendedWatcher>,
+    events: Option<Receiver<notify::Result<notify::Event>>>,
+}
+
+/// `<配置目录>/editor-rs/keybindings.toml`
+pub fn keymap_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("editor-rs").join("keybindings.toml"))
+}
+
+impl Keymap {
+    /// 默认绑定加上用户文件中的绑定
+    pub fn load() -> Self {
+        let path = keymap_path();
+        let mut keymap = Self {
+            bindings: default_bindings(),
+            path,
+            ..Self::default()
+        };
+        keymap.load_error = keymap.reload().err();
+        keymap
+    }
+
+    /// 重新读取用户文件，返回其中的错误和冲突
+    fn reload(&mut self) -> Result<(), String> {
+        let mut bindings = default_bindings();
+        let mut errors = Vec::new();
+        if let Some(path) = &self.path {
+            match read_keymap_file(path) {
+                Ok(entries) => apply_entries(&mut bindings, entries, &mut errors),
+                Err(err) => errors.push(err),
+            }
+        }
+        errors.extend(find_conflicts(&bindings));
+        self.bindings = bindings;
+        self.pending = None;
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// 开始监听快捷键文件所在的目录
+    pub fn watch(&mut self, ctx: &egui::Context) {
+        let Some(dir) = self.path.as_ref().and_then(|p| p.parent()) else {
+            return;
+        };
+        if fs::create_dir_all(dir).is_err() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+            ctx.request_repaint();
+        });
+        match watcher {
+            Ok(mut watcher) => {
+                if let Err(err) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    log::warn!("无法监听快捷键文件：{}", err);
+                    return;
+                }
+                self.watcher = Some(watcher);
+                self.events = Some(receiver);
+            }
+            Err(err) => log::warn!("无法监听快捷键文件：{}", err),
+        }
+    }
+
+    /// 快捷键文件变化时重新读取。读取后返回其中的错误和冲突
+    pub fn poll(&mut self) -> Result<(), String> {
+        let (Some(events), Some(path)) = (&self.events, &self.path) else {
+            return Ok(());
+        };
+        let changed = events
+            .try_iter()
+            .flatten()
+            .any(|event| !event.kind.is_access() && event.paths.iter().any(|p| p == path));
+        if !changed {
+            return Ok(());
+        }
+        self.reload()
+    }
+
+    /// 组合键等待第二个键时显示的提示，例如 `Ctrl+K`
+    pub fn pending_text(&self) -> Option<String> {
+        self.pending.map(|shortcut| format_keys(&[shortcut]))
+    }
+
+    /// 菜单中显示的快捷键（取第一个生效的绑定）
+    pub fn shortcut_text(&self, command: Command) -> Option<String> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.command == command)
+            .map(|binding| format_keys(&binding.keys))
+    }
+
+    /// 消费本帧按下的快捷键，返回要执行的命令
+    pub fn dispatch(&mut self, ctx: &egui::Context, context: &KeyContext) -> Option<Command> {
+        // 修饰键多的先匹配：consume_shortcut 会忽略多按的 Shift，
+        // 否则 Ctrl+Shift+S 会被 Ctrl+S 抢先消费
+        let mut active: Vec<&Binding> = self
+            .bindings
+            .iter()
+            .filter(|binding| binding.applies(context))
+            .collect();
+        active.sort_by_key(|binding| {
+            std::cmp::Reverse(modifier_count(
+                binding.keys[binding.keys.len() - 1].modifiers,
+            ))
+        });
+
+        if let Some(first) = self.pending {
+            for binding in active
+                .iter()
+                .filter(|b| b.keys.len() == 2 && b.keys[0] == first)
+            {
+                let consumed = ctx.input_mut(|i| {
+                    let consumed = i.consume_shortcut(&binding.keys[1]);
+                    if consumed {
+                        // 不带 Ctrl 的第二个键（例如 Ctrl+K S）同时产生了文字，不要输入到编辑区
+                        i.events
+                            .retain(|event| !matches!(event, egui::Event::Text(_)));
+                    }
+                    consumed
+                });
+                if consumed {
+                    self.pending = None;
+                    return Some(binding.command);
+                }
+            }
+            // 按了其他键就放弃这个组合键
+            let other_key = ctx.input(|i| {
+                i.events
+                    .iter()
+                    .any(|event| matches!(event, egui::Event::Key { pressed: true, .. }))
+            });
+            if other_key {
+                self.pending = None;
+            }
+            return None;
+        }
+
+        active.sort_by_key(|binding| std::cmp::Reverse(modifier_count(binding.keys[0].modifiers)));
+        for binding in active {
+            if ctx.input_mut(|i| i.consume_shortcut(&binding.keys[0])) {
+                if binding.keys.len() == 1 {
+                    return Some(binding.command);
+                }
+                self.pending = Some(binding.keys[0]);
+                return None;
+            }
+        }
+        None
+    }
+}
+
+fn read_keymap_file(path: &Path) -> Result<Vec<BindingEntry>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("读取快捷键失败：{}\n{}", path.display(), err)),
+    };
+    toml::from_str::<KeymapFile>(&text)
+        .map(|file| file.bindings)
+        .map_err(|err| format!("快捷键文件有误：{}\n{}", path.display(), err))
+}
+
+/// 把用户的绑定加到默认绑定上：同样的按键和条件会替换原来的绑定，
+/// `-command` 去掉这个按键对该命令的绑定
+fn apply_entries(
+    bindings: &mut Vec<Binding>,
+    entries: Vec<BindingEntry>,
+    errors: &mut Vec<String>,
+) {
+    for entry in entries {
+        let Some(keys) = parse_keys(&entry.key) else {
+            errors.push(format!("无法识别的按键：{}", entry.key));
+            continue;
+        };
+        let when = match entry.when.as_deref().map(Condition::parse_all) {
+            Some(None) => {
+                errors.push(format!(
+                    "无法识别的条件：{}",
+                    entry.when.unwrap_or_default()
+                ));
+                continue;
+            }
+            Some(Some(conditions)) => conditions,
+            None => Vec::new(),
+        };
+        let (remove, id) = match entry.command.strip_prefix('-') {
+            Some(id) => (true, id),
+            None => (false, entry.command.as_str()),
+        };
+        let Some(command) = Command::from_id(id) else {
+            errors.push(format!("未知的命令：{}", entry.command));
+            continue;
+        };
+        if remove {
+            bindings.retain(|b| !(b.keys == keys && b.command == command));
+            continue;
+        }
+        bindings.retain(|b| !(b.keys == keys && b.when == when));
+        // 用户的绑定放在前面，菜单优先显示它
+        bindings.insert(
+            0,
+            Binding {
+                keys,
+                command,
+                when,
+            },
+        );
+    }
+}
+
+/// 可能同时生效的两个绑定按同样的键会触发不同命令，或者单个按键挡住了以它开头的组合键
+fn find_conflicts(bindings: &[Binding]) -> Vec<String> {
+    let mut conflicts = Vec::new();
+    for (index, a) in bindings.iter().enumerate() {
+        for b in &bindings[index + 1..] {
+            if !a.overlaps(b) {
+                continue;
+            }
+            let same = a.keys == b.keys && a.command != b.command;
+            let (short, long) = if a.keys.len() <= b.keys.len() {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            let prefix = short.keys.len() < long.keys.len() && long.keys.starts_with(&short.keys);
+            if same || prefix {
+                conflicts.push(format!(
+                    "快捷键冲突：{}（{}）与 {}（{}）",
+                    format_keys(&a.keys),
+                    a.command.id(),
+                    format_keys(&b.keys),
+                    b.command.id()
+                ));
+            }
+        }
+    }
+    conflicts
+}
+
+/// 解析 `Ctrl+Shift+P`、`Ctrl+K Ctrl+C` 这样的按键，最多两个
+pub fn parse_keys(text: &str) -> Option<Vec<KeyboardShortcut>> {
+    let keys: Vec<KeyboardShortcut> = text
+        .split_whitespace()
+        .map(parse_shortcut)
+        .collect::<Option<_>>()?;
+    (1..=2).contains(&keys.len()).then_some(keys)
+}
+
+fn parse_shortcut(text: &str) -> Option<KeyboardShortcut> {
+    // 「Ctrl++」中最后一个 + 是按键本身
+    let (modifier_text, key_name) = match text.strip_suffix("++") {
+        Some(rest) => (rest, "+"),
+        None => text.rsplit_once('+').unwrap_or(("", text)),
+    };
+    let mut modifiers = Modifiers::NONE;
+    for name in modifier_text.split('+').filter(|n| !n.is_empty()) {
+        modifiers |= match name.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => Modifiers::COMMAND,
+            "shift" => Modifiers::SHIFT,
+            "alt" | "option" => Modifiers::ALT,
+            "cmd" | "command" | "meta" | "super" => Modifiers::MAC_CMD,
+            _ => return None,
+        };
+    }
+    let key = Key::ALL
+        .iter()
+        .copied()
+        .find(|key| key.name().eq_ignore_ascii_case(key_name))
+        .or_else(|| Key::from_name(key_name))?;
+    Some(KeyboardShortcut::new(modifiers, key))
+}
+
+fn format_keys(keys: &[KeyboardShortcut]) -> String {
+    // 修饰键用名字，标点用符号：Ctrl+, 而不是 Ctrl+Comma
+    let names = ModifierNames {
+        is_short: true,
+        ..ModifierNames::NAMES
+    };
+    let is_mac = cfg!(target_os = "macos");
+    keys.iter()
+        .map(|key| key.format(&names, is_mac))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn modifier_count(modifiers: Modifiers) -> u32 {
+    [
+        modifiers.alt,
+        modifiers.ctrl || modifiers.command,
+        modifiers.shift,
+        modifiers.mac_cmd,
+    ]
+    .into_iter()
+    .filter(|&m| m)
+    .count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_event(key: Key, modifiers: Modifiers) -> egui::Event {
+        egui::Event::Key {
+            key,
+            physical_key: None,
+            pressed: true,
+            repeat: false,
+            modifiers,
+        }
+    }
+
+    /// 在一帧中分派 `events`，返回命令和分派之后还剩下的事件
+    fn dispatch(
+        keymap: &mut Keymap,
+        ctx: &egui::Context,
+        context: &KeyContext,
+        events: Vec<egui::Event>,
+    ) -> (Option<Command>, Vec<egui::Event>) {
+        ctx.begin_pass(egui::RawInput {
+            events,
+            ..Default::default()
+        });
+        let command = keymap.dispatch(ctx, context);
+        let rest = ctx.input(|i| i.events.clone());
+        let _ = ctx.end_pass();
+        (command, rest)
+    }
+
+    #[test]
+    fn chord_second_key_does_not_type_text() {
+        let ctx = egui::Context::default();
+        let mut keymap = Keymap {
+            bindings: default_bindings(),
+            ..Keymap::default()
+        };
+        let context = KeyContext {
+            editor_focus: true,
+            find_bar_open: false,
+        };
+        let (command, _) = dispatch(
+            &mut keymap,
+            &ctx,
+            &context,
+            vec![key_event(Key::K, Modifiers::COMMAND)],
+        );
+        assert_eq!(command, None);
+        assert!(keymap.pending.is_some());
+
+        let (command, rest) = dispatch(
+            &mut keymap,
+            &ctx,
+            &context,
+            vec![
+                key_event(Key::S, Modifiers::NONE),
+                egui::Event::Text("s".to_owned()),
+            ],
+        );
+        assert_eq!(command, Some(Command::SaveAll));
+        assert!(!rest.iter().any(|e| matches!(e, egui::Event::Text(_))));
+        assert!(keymap.pending.is_none());
+    }
+
+    #[test]
+    fn escape_closes_find_bar_before_clearing_cursors() {
+        let ctx = egui::Context::default();
+        let mut keymap = Keymap {
+            bindings: default_bindings(),
+            ..Keymap::default()
+        };
+        let escape = || vec![key_event(Key::Escape, Modifiers::NONE)];
+        let mut context = KeyContext {
+            editor_focus: true,
+            find_bar_open: true,
+        };
+        let (command, _) = dispatch(&mut keymap, &ctx, &context, escape());
+        assert_eq!(command, Some(Command::CloseFindBar));
+        context.find_bar_open = false;
+        let (command, _) = dispatch(&mut keymap, &ctx, &context, escape());
+        assert_eq!(command, Some(Command::SingleCursor));
+    }
+
+    #[test]
+    fn default_bindings_have_no_conflicts() {
+        assert_eq!(find_conflicts(&default_bindings()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn conflicts_when_conditions_can_hold_together() {
+        let escape = [KeyboardShortcut::new(Modifiers::NONE, Key::Escape)];
+        let condition = |text| Condition::parse(text).unwrap();
+        let a = Binding::new(&escape, Command::CloseFindBar).when(condition("findBarOpen"));
+        let b = Binding::new(&escape, Command::SingleCursor).when(condition("editorFocus"));
+        assert_eq!(find_conflicts(&[a.clone(), b.clone()]).len(), 1);
+        // 不带条件的绑定和任何条件都可能同时生效
+        let c = Binding::new(&escape, Command::Find);
+        assert_eq!(find_conflicts(&[a.clone(), c]).len(), 1);
+        // 互斥的条件不冲突
+        let d = b.when(condition("!findBarOpen"));
+        assert!(find_conflicts(&[a, d]).is_empty());
+    }
+
+    #[test]
+    fn conflicts_with_chord_prefix() {
+        let ctrl_k = KeyboardShortcut::new(Modifiers::COMMAND, Key::K);
+        let s = KeyboardShortcut::new(Modifiers::NONE, Key::S);
+        let single = Binding::new(&[ctrl_k], Command::Find);
+        let chord = Binding::new(&[ctrl_k, s], Command::SaveAll);
+        assert_eq!(find_conflicts(&[single, chord]).len(), 1);
+    }
+
+    #[test]
+    fn parse_single_keys() {
+        let shortcut = |modifiers, key| vec![KeyboardShortcut::new(modifiers, key)];
+        assert_eq!(
+            parse_keys("Ctrl+Shift+P"),
+            Some(shortcut(Modifiers::COMMAND | Modifiers::SHIFT, Key::P))
+        );
+        assert_eq!(
+            parse_keys("alt+up"),
+            Some(shortcut(Modifiers::ALT, Key::ArrowUp))
+        );
+        assert_eq!(parse_keys("F1"), Some(shortcut(Modifiers::NONE, Key::F1)));
+        assert_eq!(
+            parse_keys("Ctrl++"),
+            Some(shortcut(Modifiers::COMMAND, Key::Plus))
+        );
+        assert_eq!(
+            parse_keys("Ctrl+,"),
+            Some(shortcut(Modifiers::COMMAND, Key::Comma))
+        );
+    }
+
+    #[test]
+    fn parse_chords() {
+        let keys = parse_keys("Ctrl+K  s").unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                KeyboardShortcut::new(Modifiers::COMMAND, Key::K),
+                KeyboardShortcut::new(Modifiers::NONE, Key::S),
+            ]
+        );
+        // 菜单中显示的文字可以原样写进快捷键文件
+        #[cfg(not(target_os = "macos"))]
+        assert_eq!(parse_keys(&format_keys(&keys)), Some(keys));
+    }
+
+    #[test]
+    fn parse_invalid_keys() {
+        for text in ["", "Ctrl+K Ctrl+C Ctrl+D", "Hyper+A", "Ctrl+Nope", "Ctrl+"] {
+            assert_eq!(parse_keys(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn parse_when_with_several_conditions() {
+        let conditions = Condition::parse_all("!findBarOpen && editorFocus").unwrap();
+        assert_eq!(
+            conditions,
+            vec![
+                Condition {
+                    context: Context::EditorFocus,
+                    negated: false,
+                },
+                Condition {
+                    context: Context::FindBarOpen,
+                    negated: true,
+                },
+            ]
+        );
+        assert!(Condition::parse_all("editorFocus && bogus").is_none());
+    }
+}
@@ -1,3 +1,35 @@
+pub mod buffer;
+pub mod cli;
+pub mod command;
+pub mod close_dialog;
+pub mod diff;
+pub mod document;
 pub mod menu;
+pub mod minimap;
 pub mod editor_app;
+pub mod editor_view;
+pub mod encoding;
+pub mod explorer;
+pub mod file_drop;
+pub mod file_watch;
+pub mod find;
+pub mod find_in_files;
+pub mod folding;
+pub mod git_diff;
+pub mod gutter;
+pub mod highlight;
+pub mod history;
+pub mod indent;
+pub mod instance;
+pub mod keymap;
+pub mod line_ending;
+pub mod multi_cursor;
+pub mod palette;
+pub mod panes;
+pub mod process_lock;
+pub mod recovery;
+pub mod session;
+pub mod settings;
+pub mod status_bar;
+pub mod tabs;
 mod title_bar;
\ No newline at end of file
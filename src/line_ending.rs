@@ -0,0 +1,168 @@
+use eframe::egui;
+use std::borrow::Cow;
+
+/// 换行符。缓冲区内部统一使用 `\n`，保存时再转换成文档选择的换行符。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl Default for LineEnding {
+    /// 新建的文档使用当前平台习惯的换行符
+    fn default() -> Self {
+        if cfg!(windows) { Self::CrLf } else { Self::Lf }
+    }
+}
+
+impl LineEnding {
+    pub const ALL: [LineEnding; 3] = [LineEnding::Lf, LineEnding::CrLf, LineEnding::Cr];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+            LineEnding::Cr => "CR",
+        }
+    }
+}
+
+/// 文本中各种换行符的数量
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LineEndingStats {
+    pub lf: usize,
+    pub crlf: usize,
+    pub cr: usize,
+}
+
+impl LineEndingStats {
+    pub fn count(text: &str) -> Self {
+        let mut stats = Self::default();
+        let mut bytes = text.bytes().peekable();
+        while let Some(b) = bytes.next() {
+            match b {
+                b'\n' => stats.lf += 1,
+                b'\r' if bytes.peek() == Some(&b'\n') => {
+                    bytes.next();
+                    stats.crlf += 1;
+                }
+                b'\r' => stats.cr += 1,
+                _ => {}
+            }
+        }
+        stats
+    }
+
+    /// 出现最多的换行符；没有换行时返回 `None`
+    pub fn dominant(&self) -> Option<LineEnding> {
+        [
+            (self.lf, LineEnding::Lf),
+            (self.crlf, LineEnding::CrLf),
+            (self.cr, LineEnding::Cr),
+        ]
+        .into_iter()
+        .filter(|&(count, _)| count > 0)
+        .max_by_key(|&(count, _)| count)
+        .map(|(_, ending)| ending)
+    }
+
+    /// 是否混用了多种换行符
+    pub fn is_mixed(&self) -> bool {
+        [self.lf, self.crlf, self.cr]
+            .iter()
+            .filter(|&&count| count > 0)
+            .count()
+            > 1
+    }
+}
+
+/// 把 `\r\n` 和单独的 `\r` 统一换成 `\n`
+pub fn normalize(text: &str) -> Cow<'_, str> {
+    if !text.contains('\r') {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+/// 把内部的 `\n` 换成指定的换行符
+pub fn convert(text: &str, ending: LineEnding) -> Cow<'_, str> {
+    match ending {
+        LineEnding::Lf => Cow::Borrowed(text),
+        _ => Cow::Owned(text.replace('\n', ending.as_str())),
+    }
+}
+
+/// 显示当前换行符的按钮，点开后可以转换；混用多种换行符时显示警告。
+/// 返回用户选择的换行符。
+pub fn line_ending_button(
+    ui: &mut egui::Ui,
+    current: LineEnding,
+    mixed: bool,
+) -> Option<LineEnding> {
+    let mut chosen = None;
+    ui.menu_button(current.name(), |ui| {
+        for ending in LineEnding::ALL {
+            if ui
+                .selectable_label(ending == current, ending.name())
+                .clicked()
+            {
+                chosen = Some(ending);
+                ui.close_menu();
+            }
+        }
+    });
+    if mixed {
+        ui.colored_label(ui.visuals().warn_fg_color, "⚠")
+            .on_hover_text(format!(
+                "文件中混用了多种换行符，保存时将统一为 {}",
+                current.name()
+            ));
+    }
+    chosen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_mixed_line_endings() {
+        let stats = LineEndingStats::count("a\r\nb\nc\r\nd\re\r\n");
+        assert_eq!((stats.lf, stats.crlf, stats.cr), (1, 3, 1));
+        assert!(stats.is_mixed());
+        assert_eq!(stats.dominant(), Some(LineEnding::CrLf));
+
+        let stats = LineEndingStats::count("a\nb\n");
+        assert!(!stats.is_mixed());
+        assert_eq!(stats.dominant(), Some(LineEnding::Lf));
+        assert_eq!(LineEndingStats::count("no newline").dominant(), None);
+    }
+
+    #[test]
+    fn normalize_mixed_line_endings() {
+        assert_eq!(normalize("a\r\nb\nc\rd\r\n"), "a\nb\nc\nd\n");
+        // 单独的 \r 后面紧跟 \r\n 是两个换行
+        assert_eq!(normalize("a\r\r\nb"), "a\n\nb");
+        assert!(matches!(normalize("a\nb"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn normalize_and_convert_round_trip() {
+        for ending in LineEnding::ALL {
+            let text = ["one", "two", "", "three", ""].join(ending.as_str());
+            let normalized = normalize(&text);
+            assert_eq!(normalized, "one\ntwo\n\nthree\n");
+            assert_eq!(convert(&normalized, ending), text);
+            assert_eq!(LineEndingStats::count(&text).dominant(), Some(ending));
+        }
+    }
+}
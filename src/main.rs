@@ -2,21 +2,70 @@
 // #![allow(rustdoc::missing_crate_level_docs)] // it's an example
 
 use eframe::egui;
+use editor_rs::cli::{self, Command};
 use editor_rs::editor_app::EditorApp;
+use editor_rs::instance;
+use editor_rs::session::{self, Session};
+use editor_rs::settings::SettingsStore;
 
 fn main() -> eframe::Result {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+    let args = match cli::parse_args(std::env::args_os().skip(1)) {
+        Ok(Command::Run(args)) => args,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(Command::Version) => {
+            println!("editor-rs {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    // 已经有编辑器在运行时把文件交给它；没有参数时让它显示到前面
+    if !args.new_window {
+        match instance::forward(&args) {
+            Ok(true) => return Ok(()),
+            Ok(false) => {}
+            Err(err) => log::warn!("无法交给正在运行的编辑器：{}", err),
+        }
+    }
+    let settings = SettingsStore::load();
+    // 新窗口不恢复也不保存会话，避免和已经打开的窗口重复或者互相覆盖
+    let session_lock = if args.new_window {
+        None
+    } else {
+        session::lock()
+    };
+    let session = if settings.settings.restore_session && session_lock.is_some() {
+        Session::load()
+    } else {
+        None
+    };
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_decorations(false)  // Hide the OS-specific "chrome" around the window
+        .with_inner_size([settings.settings.window_width, settings.settings.window_height])// Initial size of the window
+        .with_drag_and_drop(true)  // wide enough for the drag-drop overlay text
+        .with_resizable(true);  // Allow resizing the window
+    // 恢复上次的窗口位置和大小
+    if let Some(window) = session.as_ref().and_then(|s| s.window) {
+        viewport = viewport
+            .with_inner_size([window.width, window.height])
+            .with_maximized(window.maximized);
+        if let (Some(x), Some(y)) = (window.x, window.y) {
+            viewport = viewport.with_position([x, y]);
+        }
+    }
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_decorations(false)  // Hide the OS-specific "chrome" around the window
-            .with_inner_size([1280.0, 1024.0])// Initial size of the window
-            .with_drag_and_drop(true)  // wide enough for the drag-drop overlay text
-            .with_resizable(true),  // Allow resizing the window
+        viewport,
         ..Default::default()
     };
     eframe::run_native(
         "Editor-rs",
         options,
-        Box::new(|_cc| Ok(Box::new(EditorApp::new(_cc)))),
+        Box::new(|cc| Ok(Box::new(EditorApp::with_args(cc, args, settings, session, session_lock)))),
     )
 }
\ No newline at end of file
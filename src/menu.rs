@@ -1,5 +1,5 @@
-use std::fs;
-use eframe::egui::{self, Stroke, TopBottomPanel, ViewportCommand};
+use eframe::egui::{self, Stroke, TopBottomPanel};
+use crate::command::Command;
 use crate::editor_app::EditorApp;
 
 pub fn menu_example(editor: &mut EditorApp, ui: &mut egui::Ui) {
@@ -9,47 +9,109 @@ pub fn menu_example(editor: &mut EditorApp, ui: &mut egui::Ui) {
         egui::widgets::global_theme_preference_switch(ui);
         // File 菜单
         ui.menu_button("File", |ui| {
-            if ui.button("New").clicked() {
-                // 处理 New
-            }
-            if ui.button("Open…").clicked() {
-                if let Some(path) = rfd::FileDialog::new().pick_file() {
-                    // 读取文件内容到字符串
-                    match fs::read_to_string(&path) {
-                        Ok(text) => editor.file_content = text.to_string(),
-                        Err(err) => editor.file_content = format!("读取失败：{}", &err),
-                    }
-                }
-            }
+            command_item(editor, ui, Command::NewFile);
+            command_item(editor, ui, Command::Open);
+            command_item(editor, ui, Command::OpenFolder);
 
+            ui.separator();
+            command_item(editor, ui, Command::Save);
+            command_item(editor, ui, Command::SaveAs);
+            command_item(editor, ui, Command::SaveAll);
+            command_item(editor, ui, Command::Close);
+
+            ui.separator();
+            command_item(editor, ui, Command::Settings);
 
             ui.separator();
-            if ui.button("Quit").clicked() {
-                // 退出应用
-                ui.ctx().send_viewport_cmd(ViewportCommand::Close);
-            }
+            command_item(editor, ui, Command::Quit);
         });
 
         // Edit 菜单
         ui.menu_button("Edit", |ui| {
-            if ui.button("Undo").clicked() {
-                // 处理 Undo
-            }
-            if ui.button("Redo").clicked() {
-                // 处理 Redo
-            }
+            command_item(editor, ui, Command::Undo);
+            command_item(editor, ui, Command::Redo);
+
+            ui.separator();
+            command_item(editor, ui, Command::Find);
+            command_item(editor, ui, Command::Replace);
+            command_item(editor, ui, Command::FindInFiles);
+            command_item(editor, ui, Command::FindNext);
+            command_item(editor, ui, Command::FindPrevious);
+
+            ui.separator();
+            command_item(editor, ui, Command::AddNextOccurrence);
+            command_item(editor, ui, Command::AddCursorAbove);
+            command_item(editor, ui, Command::AddCursorBelow);
+        });
+
+        // View 菜单
+        ui.menu_button("View", |ui| {
+            command_item(editor, ui, Command::ToggleExplorer);
+            command_item(editor, ui, Command::ToggleWordWrap);
+            command_item(editor, ui, Command::ToggleMinimap);
+
+            ui.separator();
+            command_item(editor, ui, Command::CommandPalette);
+            command_item(editor, ui, Command::GoToFile);
+            command_item(editor, ui, Command::GoToLine);
+
+            ui.separator();
+            command_item(editor, ui, Command::NextTab);
+            command_item(editor, ui, Command::PreviousTab);
+
+            ui.separator();
+            command_item(editor, ui, Command::SplitRight);
+            command_item(editor, ui, Command::SplitDown);
+            command_item(editor, ui, Command::ClosePane);
+
+            ui.separator();
+            ui.menu_button("Folding", |ui| {
+                command_item(editor, ui, Command::Fold);
+                command_item(editor, ui, Command::Unfold);
+                command_item(editor, ui, Command::UnfoldRecursively);
+
+                ui.separator();
+                command_item(editor, ui, Command::FoldAll);
+                command_item(editor, ui, Command::UnfoldAll);
+
+                ui.separator();
+                command_item(editor, ui, Command::FoldLevel1);
+                command_item(editor, ui, Command::FoldLevel2);
+                command_item(editor, ui, Command::FoldLevel3);
+                command_item(editor, ui, Command::FoldLevel4);
+                command_item(editor, ui, Command::FoldLevel5);
+            });
         });
 
         // Help 菜单
         ui.menu_button("Help", |ui| {
-            if ui.button("About…").clicked() {
-                // 弹出 About 对话框
-               
-            }
+            command_item(editor, ui, Command::About);
         });
     });
 }
 
+/// 一个菜单项：文字和快捷键都来自命令表，点击后执行命令并关闭菜单
+fn command_item(editor: &mut EditorApp, ui: &mut egui::Ui, command: Command) {
+    let history = editor.tabs.active().map(|doc| &doc.history);
+    let (enabled, selected) = match command {
+        Command::Undo => (history.is_some_and(|h| h.can_undo()), false),
+        Command::Redo => (history.is_some_and(|h| h.can_redo()), false),
+        Command::ToggleExplorer => (true, editor.explorer.open),
+        Command::ToggleWordWrap => (true, editor.settings.settings.word_wrap),
+        Command::ToggleMinimap => (true, editor.settings.settings.minimap),
+        Command::ClosePane => (editor.tabs.pane_count() > 1, false),
+        _ => (true, false),
+    };
+    let mut button = egui::Button::new(command.title()).selected(selected);
+    if let Some(keys) = editor.keymap.shortcut_text(command) {
+        button = button.shortcut_text(keys);
+    }
+    if ui.add_enabled(enabled, button).clicked() {
+        editor.run_command(ui.ctx(), command);
+        ui.close_menu();
+    }
+}
+
 pub fn build_menu_bar(app: &mut EditorApp ,ctx: &egui::Context) {
     // 顶部 menu_bar，紧跟 title_bar 之下
     TopBottomPanel::top("menu_bar_panel").exact_height(24.0).show(ctx, |ui| {
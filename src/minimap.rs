@@ -0,0 +1,229 @@
+//! 缩略图：文本视图右侧按比例缩小的整篇文档。
+//!
+//! 每行画成两个像素高、每个字符一个像素宽的色块，颜色来自语法高亮；
+//! 上面叠加查找结果、诊断和未提交修改的标记，以及可以拖动的视口指示框。
+//! 文档比缩略图高时，缩略图跟着视口按比例滚动。
+
+use crate::buffer::TextBuffer;
+use crate::editor_view::Rows;
+use crate::gutter::{DecorationKind, Decorations};
+use crate::highlight::{Highlighter, SyntaxTheme, layout_job};
+use eframe::egui::{self, Color32, FontId, Id, Rangef, Rect, Sense, pos2};
+use std::ops::Range;
+
+/// 缩略图的宽度
+pub(crate) const WIDTH: f32 = 90.0;
+/// 每行的高度和每个字符的宽度
+const ROW_HEIGHT: f32 = 2.0;
+const CHAR_WIDTH: f32 = 1.0;
+/// 计算列时一个 Tab 算几列
+const TAB_WIDTH: usize = 4;
+
+/// 缩略图的内容，通过 `TextView::minimap` 传入
+pub struct Minimap<'a> {
+    pub decorations: &'a Decorations,
+}
+
+/// 绘制缩略图需要的文本视图状态
+pub(crate) struct MinimapView<'a> {
+    pub buffer: &'a TextBuffer,
+    pub highlighter: Option<&'a mut Highlighter>,
+    pub theme: &'a SyntaxTheme,
+    pub font_id: &'a FontId,
+    pub text_color: Color32,
+    /// 查找结果和当前匹配，按起始位置排序
+    pub highlights: &'a [Range<usize>],
+    pub current_highlight: Option<Range<usize>>,
+    /// 不考虑自动换行时缓冲区行和显示行的换算，用来跳过折叠的行
+    pub rows: &'a Rows<'a>,
+    /// 视口中第一行和最后一行
+    pub top_line: usize,
+    pub bottom_line: usize,
+}
+
+impl Minimap<'_> {
+    /// 在 `rect` 中绘制缩略图；点击或拖动后返回应该滚动到视口顶部的行
+    pub(crate) fn show(
+        &self,
+        ui: &mut egui::Ui,
+        rect: Rect,
+        id: Id,
+        view: MinimapView,
+    ) -> Option<usize> {
+        let MinimapView {
+            buffer,
+            mut highlighter,
+            theme,
+            font_id,
+            text_color,
+            highlights,
+            current_highlight,
+            rows,
+            top_line,
+            bottom_line,
+        } = view;
+        let dark_mode = ui.visuals().dark_mode;
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+        // 视口和缩略图各自的位置都按比例换算：视口滚到底时缩略图也滚到底
+        let total = rows.total_rows();
+        let top = rows.line_to_row(top_line);
+        let view_rows = rows.line_to_row(bottom_line).saturating_sub(top).max(1);
+        let content_height = total as f32 * ROW_HEIGHT;
+        let slider_height = view_rows as f32 * ROW_HEIGHT;
+        let track = (content_height.min(rect.height()) - slider_height).max(0.0);
+        let max_top = total.saturating_sub(view_rows);
+        let fraction = if max_top == 0 {
+            0.0
+        } else {
+            (top as f32 / max_top as f32).min(1.0)
+        };
+        let scroll = fraction * (content_height - rect.height()).max(0.0);
+        let slider = Rect::from_min_size(
+            pos2(rect.left(), rect.top() + fraction * track),
+            egui::vec2(rect.width(), slider_height),
+        );
+
+        // ---- 各行 ----
+        let match_color = Color32::from_rgb(230, 170, 0);
+        let current_match_color = Color32::from_rgb(255, 120, 0);
+        let first_row = (scroll / ROW_HEIGHT).floor() as usize;
+        let last_row = (((scroll + rect.height()) / ROW_HEIGHT).ceil() as usize).min(total);
+        for row in first_row..last_row {
+            let line = rows.row_to_line(row);
+            let y = rect.top() + row as f32 * ROW_HEIGHT - scroll;
+            let text = buffer.line_text(line);
+
+            // 诊断标成整行的底色，git 修改标在左边
+            for decoration in self.decorations.on_line(line) {
+                let color = decoration.kind.color(dark_mode);
+                let change = matches!(
+                    decoration.kind,
+                    DecorationKind::Added | DecorationKind::Modified | DecorationKind::Deleted
+                );
+                let (x_range, color) = if change {
+                    (Rangef::new(rect.left(), rect.left() + 2.0), color)
+                } else {
+                    (rect.x_range(), color.gamma_multiply(0.35))
+                };
+                painter.rect_filled(
+                    Rect::from_x_y_ranges(x_range, y..=y + ROW_HEIGHT),
+                    0.0,
+                    color,
+                );
+            }
+
+            let spans: Vec<(Range<usize>, Color32)> = match highlighter.as_deref_mut() {
+                Some(h) => {
+                    let state = h.state_at_line(buffer, line);
+                    let job = layout_job(h.language(), state, &text, font_id, theme);
+                    job.sections
+                        .iter()
+                        .map(|s| (s.byte_range.clone(), s.format.color))
+                        .collect()
+                }
+                None => vec![(0..text.len(), text_color)],
+            };
+            let mut col = 0;
+            for (range, color) in spans {
+                let color = color.gamma_multiply(0.7);
+                let mut run: Option<usize> = None;
+                for c in text[range].chars() {
+                    let width = if c == '\t' { TAB_WIDTH } else { 1 };
+                    if c.is_whitespace() {
+                        if let Some(start) = run.take() {
+                            paint_run(&painter, rect, y, start..col, color);
+                        }
+                    } else if run.is_none() {
+                        run = Some(col);
+                    }
+                    col += width;
+                }
+                if let Some(start) = run {
+                    paint_run(&painter, rect, y, start..col, color);
+                }
+                if col as f32 * CHAR_WIDTH > rect.width() {
+                    break;
+                }
+            }
+
+            // 查找结果
+            let line_start = buffer.line_to_char(line);
+            let line_end = line_start + text.chars().count();
+            let column = |index: usize| {
+                text.chars()
+                    .take(index.clamp(line_start, line_end) - line_start)
+                    .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+                    .sum::<usize>()
+            };
+            let first = highlights.partition_point(|r| r.end <= line_start);
+            let marks = highlights[first..]
+                .iter()
+                .take_while(|r| r.start < line_end.max(line_start + 1))
+                .map(|r| (r, match_color))
+                .chain(
+                    current_highlight
+                        .as_ref()
+                        .filter(|r| r.start < line_end.max(line_start + 1) && r.end > line_start)
+                        .map(|r| (r, current_match_color)),
+                );
+            for (r, color) in marks {
+                let cols = column(r.start)..column(r.end).max(column(r.start) + 2);
+                paint_run(&painter, rect, y, cols, color);
+            }
+        }
+
+        // ---- 视口指示框 ----
+        let response = ui.interact(rect, id, Sense::click_and_drag());
+        let active = response.hovered() || response.dragged();
+        let fill = ui
+            .visuals()
+            .widgets
+            .hovered
+            .bg_fill
+            .gamma_multiply(if active { 0.5 } else { 0.3 });
+        painter.rect_filled(slider, 0.0, fill);
+
+        // 按住指示框拖动时保持按下的位置；点在框外时把框的中心移到那里
+        let grab_id = id.with("grab");
+        let pointer = response.interact_pointer_pos()?;
+        if response.drag_started() || response.clicked() {
+            // 开始拖动时鼠标已经离开了按下的位置
+            let origin = ui.input(|i| i.pointer.press_origin()).unwrap_or(pointer);
+            let grab = if slider.contains(origin) {
+                origin.y - slider.top()
+            } else {
+                slider_height / 2.0
+            };
+            ui.data_mut(|d| d.insert_temp(grab_id, grab));
+        }
+        if !(response.dragged() || response.clicked()) {
+            return None;
+        }
+        let grab: f32 = ui
+            .data(|d| d.get_temp(grab_id))
+            .unwrap_or(slider_height / 2.0);
+        let fraction = if track > 0.0 {
+            ((pointer.y - grab - rect.top()) / track).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let row = (fraction * max_top as f32).round() as usize;
+        Some(rows.row_to_line(row))
+    }
+}
+
+/// 在第 `y` 行画出 `cols` 列的色块
+fn paint_run(painter: &egui::Painter, rect: Rect, y: f32, cols: Range<usize>, color: Color32) {
+    let left = rect.left() + 2.0 + cols.start as f32 * CHAR_WIDTH;
+    let right = (rect.left() + 2.0 + cols.end as f32 * CHAR_WIDTH).min(rect.right());
+    if left >= right {
+        return;
+    }
+    painter.rect_filled(
+        Rect::from_min_max(pos2(left, y), pos2(right, y + ROW_HEIGHT - 0.5)),
+        0.0,
+        color,
+    );
+}
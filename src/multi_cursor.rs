@@ -0,0 +1,417 @@
+//! 多光标编辑。每个光标是一个 `CCursorRange`（`primary` 是光标所在处，`secondary` 是选区的另一端），
+//! 列表中的第 0 个是主光标：状态栏、查找、撤销历史只看主光标。
+
+use crate::buffer::{LineCol, TextBuffer};
+use crate::history::EditOp;
+use eframe::egui::text::{CCursor, CCursorRange};
+use std::ops::Range;
+
+pub fn range_of(cursor: &CCursorRange) -> Range<usize> {
+    let (a, b) = (cursor.primary.index, cursor.secondary.index);
+    a.min(b)..a.max(b)
+}
+
+pub fn caret(index: usize) -> CCursorRange {
+    CCursorRange::one(CCursor::new(index))
+}
+
+/// 选区 `range`，光标在 `range.end`
+pub fn selection(range: Range<usize>) -> CCursorRange {
+    CCursorRange::two(CCursor::new(range.start), CCursor::new(range.end))
+}
+
+/// 合并重叠或位置相同的光标，主光标仍然排在第 0 个，其余按位置排列
+pub fn merge(cursors: &mut Vec<CCursorRange>) {
+    if cursors.len() < 2 {
+        return;
+    }
+    let mut order: Vec<(usize, CCursorRange)> = cursors.drain(..).enumerate().collect();
+    order.sort_by_key(|(index, cursor)| (range_of(cursor).start, *index));
+    let mut merged: Vec<(bool, CCursorRange)> = Vec::with_capacity(order.len());
+    for (index, cursor) in order {
+        let range = range_of(&cursor);
+        if let Some((primary, last)) = merged.last_mut() {
+            let last_range = range_of(last);
+            if range.start < last_range.end || range.start == last_range.start {
+                let union = last_range.start..last_range.end.max(range.end);
+                // 保留主光标的方向
+                *last = if *primary {
+                    stretch(last, union)
+                } else {
+                    stretch(&cursor, union)
+                };
+                *primary |= index == 0;
+                continue;
+            }
+        }
+        merged.push((index == 0, cursor));
+    }
+    let primary = merged.iter().position(|(p, _)| *p).unwrap_or(0);
+    let first = merged.remove(primary).1;
+    cursors.push(first);
+    cursors.extend(merged.into_iter().map(|(_, cursor)| cursor));
+}
+
+/// 把光标的选区扩大到 `range`，保持光标在选区的哪一端
+fn stretch(cursor: &CCursorRange, range: Range<usize>) -> CCursorRange {
+    if cursor.primary.index < cursor.secondary.index {
+        CCursorRange::two(CCursor::new(range.end), CCursor::new(range.start))
+    } else {
+        selection(range)
+    }
+}
+
+/// 一处替换，坐标是替换前的缓冲区
+pub struct Change {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+/// 依次应用按位置排列、互不重叠的替换，返回按应用顺序排列的修改
+fn apply_changes(buffer: &mut TextBuffer, changes: &[Change]) -> Vec<EditOp> {
+    let mut ops = Vec::with_capacity(changes.len());
+    let mut delta = 0isize;
+    for change in changes {
+        let start = (change.range.start as isize + delta) as usize;
+        let end = (change.range.end as isize + delta) as usize;
+        let removed = buffer.slice_to_string(start..end);
+        buffer.replace(start..end, &change.text);
+        delta += change.text.chars().count() as isize - change.range.len() as isize;
+        ops.push(EditOp {
+            start,
+            removed,
+            inserted: change.text.clone(),
+        });
+    }
+    ops
+}
+
+/// 替换后 `pos` 的新位置。`pos` 恰好在插入点上时，`after` 决定它留在插入文本之前还是之后
+fn map_pos(pos: usize, changes: &[Change], after: bool) -> usize {
+    let mut delta = 0isize;
+    for change in changes {
+        let Range { start, end } = change.range;
+        let inserted = change.text.chars().count() as isize;
+        if pos > end || (pos == end && (start != end || after)) {
+            delta += inserted - (end - start) as isize;
+        } else if pos > start {
+            // 在被删除的范围内，落到替换文本的开头
+            return (start as isize + delta) as usize;
+        }
+    }
+    (pos as isize + delta) as usize
+}
+
+/// 对每个光标做一次替换，作为一次编辑返回修改。
+///
+/// `edit` 按位置顺序对每个光标调用一次，参数是序号和光标（坐标都以编辑前的缓冲区为准），
+/// 返回要替换的范围和新文本，`None` 表示这个光标不修改。修改后光标落在插入文本的末尾。
+pub fn edit_each(
+    buffer: &mut TextBuffer,
+    cursors: &mut [CCursorRange],
+    mut edit: impl FnMut(&TextBuffer, usize, &CCursorRange) -> Option<(Range<usize>, String)>,
+) -> Vec<EditOp> {
+    let mut order: Vec<usize> = (0..cursors.len()).collect();
+    order.sort_by_key(|&i| range_of(&cursors[i]).start);
+    let mut changes: Vec<Change> = Vec::new();
+    // 每个光标对应的替换在 changes 中的下标
+    let mut owner = vec![None; cursors.len()];
+    for (n, &i) in order.iter().enumerate() {
+        let Some((mut range, text)) = edit(buffer, n, &cursors[i]) else {
+            continue;
+        };
+        // 不能和前一个光标的修改重叠
+        let floor = changes.last().map_or(0, |c| c.range.end);
+        range.start = range.start.max(floor);
+        range.end = range.end.max(range.start);
+        if range.is_empty() && text.is_empty() {
+            continue;
+        }
+        owner[i] = Some(changes.len());
+        changes.push(Change { range, text });
+    }
+    if changes.is_empty() {
+        return Vec::new();
+    }
+    for (i, cursor) in cursors.iter_mut().enumerate() {
+        *cursor = match owner[i] {
+            Some(c) => {
+                let change = &changes[c];
+                let start = map_pos(change.range.start, &changes[..c], true);
+                caret(start + change.text.chars().count())
+            }
+            None => CCursorRange::two(
+                CCursor::new(map_pos(cursor.secondary.index, &changes, true)),
+                CCursor::new(map_pos(cursor.primary.index, &changes, true)),
+            ),
+        };
+    }
+    apply_changes(buffer, &changes)
+}
+
+/// 光标和选区覆盖的行（选区结束在行首时不算那一行）
+fn covered_lines(buffer: &TextBuffer, cursors: &[CCursorRange]) -> Vec<usize> {
+    let mut lines = Vec::new();
+    for cursor in cursors {
+        let range = range_of(cursor);
+        let first = buffer.char_to_line(range.start);
+        let mut last = buffer.char_to_line(range.end);
+        if last > first && buffer.line_to_char(last) == range.end {
+            last -= 1;
+        }
+        lines.extend(first..=last);
+    }
+    lines.sort_unstable();
+    lines.dedup();
+    lines
+}
+
+/// 选区跨行时按 Tab：给每一行加一级缩进
+pub fn indent_lines(
+    buffer: &mut TextBuffer,
+    cursors: &mut [CCursorRange],
+    unit: &str,
+) -> Vec<EditOp> {
+    let changes: Vec<Change> = covered_lines(buffer, cursors)
+        .into_iter()
+        .filter(|&line| buffer.line_len_chars(line) > 0)
+        .map(|line| {
+            let start = buffer.line_to_char(line);
+            Change {
+                range: start..start,
+                text: unit.to_owned(),
+            }
+        })
+        .collect();
+    remap_and_apply(buffer, cursors, changes)
+}
+
+/// Shift+Tab：每一行去掉一级缩进（一个制表符，或者最多 `width` 个空格）
+pub fn outdent_lines(
+    buffer: &mut TextBuffer,
+    cursors: &mut [CCursorRange],
+    width: usize,
+) -> Vec<EditOp> {
+    let changes: Vec<Change> = covered_lines(buffer, cursors)
+        .into_iter()
+        .filter_map(|line| {
+            let start = buffer.line_to_char(line);
+            let text = buffer.line_text(line);
+            let remove = if text.starts_with('\t') {
+                1
+            } else {
+                text.chars().take(width).take_while(|&c| c == ' ').count()
+            };
+            (remove > 0).then(|| Change {
+                range: start..start + remove,
+                text: String::new(),
+            })
+        })
+        .collect();
+    remap_and_apply(buffer, cursors, changes)
+}
+
+fn remap_and_apply(
+    buffer: &mut TextBuffer,
+    cursors: &mut [CCursorRange],
+    changes: Vec<Change>,
+) -> Vec<EditOp> {
+    if changes.is_empty() {
+        return Vec::new();
+    }
+    for cursor in cursors.iter_mut() {
+        // 选区从行首开始时把新加的缩进也包含进去
+        let empty = cursor.primary.index == cursor.secondary.index;
+        let primary_first = cursor.primary.index < cursor.secondary.index;
+        cursor.primary.index = map_pos(cursor.primary.index, &changes, empty || !primary_first);
+        cursor.secondary.index = map_pos(cursor.secondary.index, &changes, empty || primary_first);
+    }
+    apply_changes(buffer, &changes)
+}
+
+/// 把选中的文本按光标顺序用换行连起来，复制时使用
+pub fn selected_text(buffer: &TextBuffer, cursors: &[CCursorRange]) -> String {
+    let mut ranges: Vec<Range<usize>> = cursors
+        .iter()
+        .map(range_of)
+        .filter(|r| !r.is_empty())
+        .collect();
+    ranges.sort_by_key(|r| r.start);
+    ranges
+        .into_iter()
+        .map(|r| buffer.slice_to_string(r))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 光标处的单词范围；不在单词上时返回 `None`
+pub fn word_at(buffer: &TextBuffer, index: usize) -> Option<Range<usize>> {
+    let line = buffer.char_to_line(index);
+    let line_start = buffer.line_to_char(line);
+    let chars: Vec<char> = buffer.line_text(line).chars().collect();
+    let col = index - line_start;
+    let on_word = |i: usize| chars.get(i).is_some_and(|&c| is_word_char(c));
+    let col = if on_word(col) {
+        col
+    } else if col > 0 && on_word(col - 1) {
+        col - 1
+    } else {
+        return None;
+    };
+    let start = (0..col)
+        .rev()
+        .take_while(|&i| on_word(i))
+        .last()
+        .unwrap_or(col);
+    let end = (col..chars.len())
+        .take_while(|&i| on_word(i))
+        .last()
+        .unwrap_or(col)
+        + 1;
+    Some(line_start + start..line_start + end)
+}
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Ctrl+D：主光标没有选中内容时选中光标处的单词，
+/// 否则在最后添加的光标之后找下一处相同的文本，并在那里添加一个光标
+pub fn add_next_occurrence(buffer: &TextBuffer, cursors: &mut Vec<CCursorRange>) -> Option<usize> {
+    let primary = *cursors.first()?;
+    let range = range_of(&primary);
+    if range.is_empty() {
+        let word = word_at(buffer, range.start)?;
+        cursors[0] = selection(word.clone());
+        return Some(word.start);
+    }
+    let needle = buffer.slice_to_string(range.clone());
+    let needle_len = range.len();
+    let text = buffer.to_string();
+    // 从最后一个光标之后开始找，到末尾后从头再找
+    let after = cursors
+        .iter()
+        .map(|c| range_of(c).end)
+        .max()
+        .unwrap_or(range.end);
+    let after_byte = buffer.char_to_byte(after);
+    let found = text[after_byte..]
+        .find(&needle)
+        .map(|b| after_byte + b)
+        .or_else(|| text.find(&needle))?;
+    let start = buffer.byte_to_char(found);
+    let new = start..start + needle_len;
+    if cursors.iter().any(|c| range_of(c) == new) {
+        return None;
+    }
+    // 新光标成为主光标，视图跟着它滚动
+    cursors.insert(0, selection(new));
+    Some(start)
+}
+
+/// Ctrl+Alt+Up / Down：在最上面（最下面）的光标的上一行（下一行）同一列添加光标
+pub fn add_cursor_vertical(
+    buffer: &TextBuffer,
+    cursors: &mut Vec<CCursorRange>,
+    up: bool,
+) -> Option<usize> {
+    let positions = cursors
+        .iter()
+        .map(|c| buffer.char_to_line_col(c.primary.index));
+    let edge = if up {
+        positions.min()?
+    } else {
+        positions.max()?
+    };
+    let line = if up {
+        edge.line.checked_sub(1)?
+    } else if edge.line + 1 < buffer.len_lines() {
+        edge.line + 1
+    } else {
+        return None;
+    };
+    let index = buffer.line_col_to_char(LineCol {
+        line,
+        col: edge.col.min(buffer.line_len_chars(line)),
+    });
+    cursors.insert(0, caret(index));
+    merge(cursors);
+    Some(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(cursors: &[CCursorRange]) -> Vec<Range<usize>> {
+        cursors.iter().map(range_of).collect()
+    }
+
+    #[test]
+    fn merge_keeps_primary_first_and_joins_overlaps() {
+        let mut cursors = vec![
+            caret(8),
+            selection(0..3),
+            selection(2..5),
+            caret(8),
+            caret(6),
+        ];
+        merge(&mut cursors);
+        assert_eq!(ranges(&cursors), vec![8..8, 0..5, 6..6]);
+
+        // 主光标被合并时，合并后的光标仍然是主光标，方向也保持不变
+        let reversed = CCursorRange::two(CCursor::new(4), CCursor::new(2));
+        let mut cursors = vec![reversed, caret(0), selection(3..6)];
+        merge(&mut cursors);
+        assert_eq!(ranges(&cursors), vec![2..6, 0..0]);
+        assert_eq!(cursors[0].primary.index, 2);
+    }
+
+    #[test]
+    fn map_pos_around_changes() {
+        let changes = [
+            Change {
+                range: 2..2,
+                text: "ab".to_owned(),
+            },
+            Change {
+                range: 5..8,
+                text: "x".to_owned(),
+            },
+        ];
+        assert_eq!(map_pos(1, &changes, false), 1);
+        // 恰好在插入点上
+        assert_eq!(map_pos(2, &changes, false), 2);
+        assert_eq!(map_pos(2, &changes, true), 4);
+        // 在被替换的范围内落到替换文本的开头，之后的位置跟着移动
+        assert_eq!(map_pos(6, &changes, true), 7);
+        assert_eq!(map_pos(8, &changes, false), 8);
+        assert_eq!(map_pos(10, &changes, false), 10);
+    }
+
+    #[test]
+    fn edit_each_types_at_every_cursor() {
+        let mut buffer = TextBuffer::from_text("one two three");
+        let mut cursors = vec![caret(7), caret(3), selection(8..13)];
+        let ops = edit_each(&mut buffer, &mut cursors, |_, _, cursor| {
+            Some((range_of(cursor), "!".to_owned()))
+        });
+        assert_eq!(buffer.to_string(), "one! two! !");
+        assert_eq!(ranges(&cursors), vec![9..9, 4..4, 11..11]);
+        assert_eq!(ops.len(), 3);
+    }
+
+    #[test]
+    fn edit_each_clips_overlapping_changes_and_moves_other_cursors() {
+        let mut buffer = TextBuffer::from_text("abcdef");
+        let mut cursors = vec![caret(2), caret(3), caret(6)];
+        // 前两个光标都删除前面两个字符，第二个不能删到第一个删过的范围；最后一个不修改
+        let ops = edit_each(&mut buffer, &mut cursors, |_, n, cursor| {
+            let index = cursor.primary.index;
+            (n < 2).then(|| (index.saturating_sub(2)..index, String::new()))
+        });
+        assert_eq!(buffer.to_string(), "def");
+        assert_eq!(ranges(&cursors), vec![0..0, 0..0, 3..3]);
+        assert_eq!(ops.len(), 2);
+    }
+}
@@ -0,0 +1,520 @@
+//! 命令面板：Ctrl+Shift+P 搜索命令，Ctrl+P 打开文件，Ctrl+G 跳转到行。
+//!
+//! 三种模式共用一个输入框，由开头的字符区分（与 VS Code 相同）：
+//! `>` 开头是命令，`:` 开头是行号，其余是文件名。
+
+use crate::buffer::LineCol;
+use crate::command::Command;
+use crate::keymap::Keymap;
+use crate::status_bar::parse_line_col;
+use crate::tabs::Tabs;
+use eframe::egui::{self, Color32, Key, Modifiers, TextFormat, text::LayoutJob};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+// 工作区中最多索引的文件数
+const MAX_INDEXED_FILES: usize = 50_000;
+// 列表中最多显示的条目数
+const MAX_ITEMS: usize = 200;
+// 记住最近使用的命令数
+const MAX_RECENT: usize = 10;
+const WIDTH: f32 = 560.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteMode {
+    Commands,
+    Files,
+    Line,
+}
+
+impl PaletteMode {
+    fn prefix(self) -> &'static str {
+        match self {
+            PaletteMode::Commands => ">",
+            PaletteMode::Files => "",
+            PaletteMode::Line => ":",
+        }
+    }
+
+    /// 按输入的第一个字符判断模式，返回模式和去掉前缀后的内容
+    fn of(query: &str) -> (Self, &str) {
+        if let Some(rest) = query.strip_prefix('>') {
+            (PaletteMode::Commands, rest)
+        } else if let Some(rest) = query.strip_prefix(':') {
+            (PaletteMode::Line, rest)
+        } else {
+            (PaletteMode::Files, query)
+        }
+    }
+}
+
+/// 在面板中选中一项后要执行的操作
+#[derive(Clone, Debug)]
+pub enum PaletteAction {
+    Run(Command),
+    SwitchTab(usize),
+    OpenFile(PathBuf),
+    GoToLine(LineCol),
+}
+
+/// 列表中的一项
+#[derive(Clone)]
+struct Item {
+    text: String,
+    /// `text` 中与输入匹配的字符下标，用来高亮
+    matched: Vec<usize>,
+    detail: String,
+    action: PaletteAction,
+}
+
+/// 工作区文件的匹配结果；输入、索引和打开的文件都没变时直接使用
+struct FileMatches {
+    query: String,
+    open_paths: Vec<PathBuf>,
+    items: Vec<(i32, Item)>,
+}
+
+#[derive(Default)]
+pub struct CommandPalette {
+    pub open: bool,
+    query: String,
+    selected: usize,
+    focus_query: bool,
+    /// 最近使用的命令，最近的在前
+    recent: Vec<Command>,
+    /// 工作区中的文件（相对 `files_root` 的路径）
+    files: Vec<PathBuf>,
+    files_root: Option<PathBuf>,
+    indexing: Option<Receiver<Vec<PathBuf>>>,
+    file_matches: Option<FileMatches>,
+}
+
+impl CommandPalette {
+    /// 以指定模式打开面板。文件模式下重新索引工作区，列出新建的文件
+    pub fn show(&mut self, ctx: &egui::Context, mode: PaletteMode, workspace: Option<&Path>) {
+        self.open = true;
+        self.query = mode.prefix().to_owned();
+        self.selected = 0;
+        self.focus_query = true;
+        if mode == PaletteMode::Files {
+            self.index_files(ctx, workspace);
+        }
+    }
+
+    pub fn recent_commands(&self) -> &[Command] {
+        &self.recent
+    }
+
+    pub fn set_recent_commands(&mut self, commands: Vec<Command>) {
+        self.recent = commands;
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// 记下执行过的命令，菜单和快捷键执行的也算
+    pub fn record(&mut self, command: Command) {
+        self.recent.retain(|&c| c != command);
+        self.recent.insert(0, command);
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// 在后台线程中列出工作区的文件（遵守 .gitignore）
+    fn index_files(&mut self, ctx: &egui::Context, workspace: Option<&Path>) {
+        let Some(root) = workspace.map(Path::to_path_buf) else {
+            self.files.clear();
+            self.files_root = None;
+            self.file_matches = None;
+            return;
+        };
+        if self.files_root.as_ref() != Some(&root) {
+            self.files.clear();
+            self.files_root = Some(root.clone());
+            self.file_matches = None;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let spawned = thread::Builder::new()
+            .name("palette-files".to_owned())
+            .spawn(move || {
+                let files: Vec<PathBuf> = ignore::WalkBuilder::new(&root)
+                    .build()
+                    .flatten()
+                    .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+                    .filter_map(|entry| {
+                        entry.path().strip_prefix(&root).ok().map(Path::to_path_buf)
+                    })
+                    .take(MAX_INDEXED_FILES)
+                    .collect();
+                let _ = sender.send(files);
+                ctx.request_repaint();
+            });
+        match spawned {
+            Ok(_) => self.indexing = Some(receiver),
+            Err(err) => log::warn!("无法索引工作区文件：{}", err),
+        }
+    }
+
+    fn poll_index(&mut self) {
+        let Some(receiver) = &self.indexing else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(files) => {
+                self.files = files;
+                self.indexing = None;
+                self.file_matches = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => self.indexing = None,
+        }
+    }
+
+    /// 显示面板，返回选中的一项。选中或者按 Escape、点击外面时关闭
+    pub fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        keymap: &Keymap,
+        tabs: &Tabs,
+    ) -> Option<PaletteAction> {
+        if !self.open {
+            return None;
+        }
+        self.poll_index();
+        let items = self.items(keymap, tabs);
+        if items.is_empty() {
+            self.selected = 0;
+        } else {
+            self.selected = self.selected.min(items.len() - 1);
+        }
+
+        let mut chosen = None;
+        let area = egui::Modal::default_area(egui::Id::new("command_palette"))
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 48.0]);
+        let modal = egui::Modal::new(egui::Id::new("command_palette"))
+            .area(area)
+            .backdrop_color(Color32::from_black_alpha(40))
+            .show(ctx, |ui| {
+                ui.set_width(WIDTH);
+                // 方向键和回车要在输入框之前消费，否则会移动输入框里的光标
+                let (up, down, enter) = ui.input_mut(|i| {
+                    (
+                        i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                        i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                        i.consume_key(Modifiers::NONE, Key::Enter),
+                    )
+                });
+                if up {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                if down && self.selected + 1 < items.len() {
+                    self.selected += 1;
+                }
+                if enter {
+                    chosen = items.get(self.selected).map(|item| item.action.clone());
+                }
+
+                let edit = ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text("输入 > 搜索命令，: 跳转到行，其他内容搜索文件")
+                        .desired_width(f32::INFINITY),
+                );
+                if std::mem::take(&mut self.focus_query) {
+                    edit.request_focus();
+                    // 光标放到前缀之后
+                    let end = egui::text::CCursor::new(self.query.chars().count());
+                    if let Some(mut state) = egui::TextEdit::load_state(ui.ctx(), edit.id) {
+                        state
+                            .cursor
+                            .set_char_range(Some(egui::text::CCursorRange::one(end)));
+                        state.store(ui.ctx(), edit.id);
+                    }
+                }
+                if edit.changed() {
+                    self.selected = 0;
+                }
+                ui.separator();
+
+                let (mode, _) = PaletteMode::of(&self.query);
+                if items.is_empty() {
+                    ui.weak(match mode {
+                        PaletteMode::Commands => "没有匹配的命令",
+                        PaletteMode::Files if self.indexing.is_some() => "正在索引工作区…",
+                        PaletteMode::Files => "没有匹配的文件",
+                        PaletteMode::Line => "输入行号，或者「行:列」",
+                    });
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for (index, item) in items.iter().enumerate() {
+                            let selected = index == self.selected;
+                            let response = item_row(ui, item, selected);
+                            if selected && (up || down) {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                chosen = Some(item.action.clone());
+                            }
+                        }
+                    });
+            });
+        if chosen.is_some() || modal.should_close() {
+            self.open = false;
+        }
+        chosen
+    }
+
+    fn items(&mut self, keymap: &Keymap, tabs: &Tabs) -> Vec<Item> {
+        let (mode, query) = PaletteMode::of(&self.query);
+        let query = query.trim().to_owned();
+        match mode {
+            PaletteMode::Commands => self.command_items(keymap, &query),
+            PaletteMode::Files => self.file_items(tabs, &query),
+            PaletteMode::Line => line_items(tabs, &query),
+        }
+    }
+
+    /// 所有命令，没有输入时最近使用的排在前面
+    fn command_items(&self, keymap: &Keymap, query: &str) -> Vec<Item> {
+        let recent_rank = |command: Command| {
+            self.recent
+                .iter()
+                .position(|&c| c == command)
+                .unwrap_or(usize::MAX)
+        };
+        let mut scored: Vec<(i32, usize, Item)> = Command::ALL
+            .into_iter()
+            .filter_map(|command| {
+                let (score, matched) = fuzzy_match(query, command.title())?;
+                let item = Item {
+                    text: command.title().to_owned(),
+                    matched,
+                    detail: keymap.shortcut_text(command).unwrap_or_default(),
+                    action: PaletteAction::Run(command),
+                };
+                Some((score, recent_rank(command), item))
+            })
+            .collect();
+        // sort_by 是稳定排序，分数和使用记录相同的保持命令表中的顺序
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        scored.into_iter().map(|(_, _, item)| item).collect()
+    }
+
+    /// 打开的标签在前，然后是工作区中的其他文件
+    fn file_items(&mut self, tabs: &Tabs, query: &str) -> Vec<Item> {
+        let mut scored: Vec<(i32, Item)> = Vec::new();
+        for (id, document) in tabs.iter() {
+            let title = document.title();
+            let Some((score, matched)) = fuzzy_match(query, &title) else {
+                continue;
+            };
+            let detail = document
+                .path
+                .as_ref()
+                .map_or_else(|| "未保存".to_owned(), |p| p.display().to_string());
+            let item = Item {
+                text: title,
+                matched,
+                detail,
+                action: PaletteAction::SwitchTab(id),
+            };
+            // 已经打开的文件加一点分，同样匹配时排在前面
+            scored.push((score + 1, item));
+        }
+        // 工作区可能有几万个文件，每帧都重新匹配太慢
+        let open_paths: Vec<PathBuf> = tabs
+            .iter()
+            .filter_map(|(_, document)| document.path.clone())
+            .collect();
+        let cached = self
+            .file_matches
+            .as_ref()
+            .is_some_and(|m| m.query == query && m.open_paths == open_paths);
+        if !cached {
+            let items = self.workspace_items(&open_paths, query);
+            self.file_matches = Some(FileMatches {
+                query: query.to_owned(),
+                open_paths,
+                items,
+            });
+        }
+        if let Some(matches) = &self.file_matches {
+            scored.extend(matches.items.iter().cloned());
+        }
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.truncate(MAX_ITEMS);
+        scored.into_iter().map(|(_, item)| item).collect()
+    }
+
+    /// 工作区中没有打开的文件，按分数排序，最多 `MAX_ITEMS` 项
+    fn workspace_items(&self, open_paths: &[PathBuf], query: &str) -> Vec<(i32, Item)> {
+        let Some(root) = &self.files_root else {
+            return Vec::new();
+        };
+        // 只对打开的文件和工作区根目录取规范路径，不用逐个处理工作区中的文件
+        let open: HashSet<PathBuf> = open_paths
+            .iter()
+            .map(|path| path.canonicalize().unwrap_or_else(|_| path.clone()))
+            .collect();
+        let canonical_root = root.canonicalize().unwrap_or_else(|_| root.clone());
+        let mut scored = Vec::new();
+        for relative in &self.files {
+            if open.contains(&canonical_root.join(relative)) {
+                continue;
+            }
+            let text = relative.display().to_string();
+            let Some((score, matched)) = fuzzy_match(query, &text) else {
+                continue;
+            };
+            scored.push((
+                score,
+                Item {
+                    text,
+                    matched,
+                    detail: String::new(),
+                    action: PaletteAction::OpenFile(root.join(relative)),
+                },
+            ));
+        }
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.truncate(MAX_ITEMS);
+        scored
+    }
+}
+
+/// 「:行」或者「:行:列」只有一项
+fn line_items(tabs: &Tabs, query: &str) -> Vec<Item> {
+    let Some(document) = tabs.active() else {
+        return Vec::new();
+    };
+    let Some(position) = parse_line_col(query) else {
+        return Vec::new();
+    };
+    let lines = document.buffer.len_lines();
+    let position = LineCol {
+        line: position.line.min(lines.saturating_sub(1)),
+        col: position.col,
+    };
+    let text = if query.contains([':', ',']) {
+        format!(
+            "跳转到第 {} 行，第 {} 列",
+            position.line + 1,
+            position.col + 1
+        )
+    } else {
+        format!("跳转到第 {} 行", position.line + 1)
+    };
+    vec![Item {
+        text,
+        matched: Vec::new(),
+        detail: format!("共 {} 行", lines),
+        action: PaletteAction::GoToLine(position),
+    }]
+}
+
+fn item_row(ui: &mut egui::Ui, item: &Item, selected: bool) -> egui::Response {
+    let text_color = ui.visuals().text_color();
+    let match_color = ui.visuals().hyperlink_color;
+    let font_id = egui::TextStyle::Button.resolve(ui.style());
+    let mut job = LayoutJob::default();
+    let mut matched = item.matched.iter().peekable();
+    for (index, c) in item.text.chars().enumerate() {
+        let is_match = matched.next_if_eq(&&index).is_some();
+        let format = TextFormat {
+            font_id: font_id.clone(),
+            color: if is_match { match_color } else { text_color },
+            ..Default::default()
+        };
+        job.append(c.encode_utf8(&mut [0; 4]), 0.0, format);
+    }
+    ui.horizontal(|ui| {
+        ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+        let response = ui.selectable_label(selected, job);
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.add(egui::Label::new(egui::RichText::new(&item.detail).weak()).truncate());
+        });
+        response
+    })
+    .inner
+}
+
+/// 模糊匹配：`query` 中的字符按顺序出现在 `text` 中（忽略大小写）。
+/// 返回分数和匹配到的字符下标；连续匹配和单词开头的匹配分数更高。
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i32, Vec<usize>)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut matched = Vec::new();
+    let mut score = 0;
+    let mut start = 0;
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = (start..chars.len()).find(|&i| eq_ignore_case(chars[i], q))?;
+        score += 1;
+        if matched.last() == Some(&found.wrapping_sub(1)) {
+            score += 5;
+        }
+        if is_word_start(&chars, found) {
+            score += 8;
+        }
+        // 跳过的字符越多分数越低
+        score -= (found - start).min(10) as i32;
+        matched.push(found);
+        start = found + 1;
+    }
+    Some((score, matched))
+}
+
+fn eq_ignore_case(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+/// 第一个字符、分隔符后面的字符、驼峰命名中的大写字母
+fn is_word_start(chars: &[char], index: usize) -> bool {
+    let Some(&prev) = index.checked_sub(1).and_then(|i| chars.get(i)) else {
+        return true;
+    };
+    let c = chars[index];
+    !prev.is_alphanumeric() || (prev.is_lowercase() && c.is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_match_in_order_ignoring_case() {
+        assert_eq!(fuzzy_match("ed", "Editor").map(|m| m.1), Some(vec![0, 1]));
+        assert_eq!(
+            fuzzy_match("mr", "src/main.rs").map(|m| m.1),
+            Some(vec![4, 9])
+        );
+        assert!(fuzzy_match("rm", "main").is_none());
+        assert!(fuzzy_match("x", "main").is_none());
+    }
+
+    #[test]
+    fn fuzzy_match_empty_query_matches_everything() {
+        assert_eq!(fuzzy_match("", "main.rs"), Some((0, Vec::new())));
+        assert_eq!(fuzzy_match(" ", "main.rs"), Some((0, Vec::new())));
+    }
+
+    #[test]
+    fn fuzzy_match_prefers_consecutive_and_word_starts() {
+        let score = |query, text| fuzzy_match(query, text).unwrap().0;
+        assert!(score("ma", "xmain") > score("ma", "xmxa"));
+        // 单词开头（分隔符后、驼峰）优先
+        assert!(score("fb", "foo_bar") > score("fb", "fooxbar"));
+        assert!(score("fb", "fooBar") > score("fb", "foobar"));
+        // 越早匹配分数越高
+        assert!(score("r", "r.txt") > score("r", "xxxxr.txt"));
+    }
+
+    #[test]
+    fn fuzzy_match_multibyte() {
+        assert_eq!(
+            fuzzy_match("文件", "打开文件").map(|m| m.1),
+            Some(vec![2, 3])
+        );
+    }
+}
@@ -0,0 +1,707 @@
+//! 编辑区分屏：窗格按二叉树排列，可以左右或上下嵌套拆分。
+//!
+//! 每个窗格有自己的标签栏。同一个文档可以同时显示在几个窗格中，共用缓冲区，
+//! 各自保留光标和滚动位置（`View`）。有焦点的窗格中正在显示的文档，
+//! 这些状态直接放在 `Document` 的字段里，查找、状态栏等只需要看活动文档。
+
+use crate::buffer::Splice;
+use crate::document::Document;
+use crate::editor_view::WrapLayout;
+use eframe::egui::{
+    self, CursorIcon, Id, Rect, Sense, Stroke, StrokeKind,
+    text::{CCursor, CCursorRange},
+};
+use std::collections::HashMap;
+
+/// 拆分方向
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitDir {
+    /// 左右并排
+    Horizontal,
+    /// 上下排列
+    Vertical,
+}
+
+/// 在窗格之间移动焦点的方向
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// 一个窗格中某个文档的光标和滚动位置
+#[derive(Default)]
+pub struct View {
+    cursor: Option<CCursorRange>,
+    extra_cursors: Vec<CCursorRange>,
+    scroll_request: Option<usize>,
+    scroll_top_request: Option<usize>,
+    top_line: usize,
+    wrap_layout: WrapLayout,
+}
+
+impl View {
+    /// 从文档取走当前的视图状态（光标保留一份，保存会话时使用）
+    fn take_from(document: &mut Document) -> Self {
+        Self {
+            cursor: document.cursor,
+            extra_cursors: std::mem::take(&mut document.extra_cursors),
+            scroll_request: document.scroll_request.take(),
+            scroll_top_request: document.scroll_top_request.take(),
+            top_line: document.top_line,
+            wrap_layout: std::mem::take(&mut document.wrap_layout),
+        }
+    }
+
+    fn put_into(self, document: &mut Document) {
+        document.cursor = self.cursor;
+        document.extra_cursors = self.extra_cursors;
+        document.scroll_request = self.scroll_request;
+        document.scroll_top_request = self.scroll_top_request;
+        document.top_line = self.top_line;
+        document.wrap_layout = self.wrap_layout;
+    }
+
+    /// 和文档字段中的视图状态互换，用来临时显示没有焦点的窗格
+    fn swap(&mut self, document: &mut Document) {
+        std::mem::swap(&mut self.cursor, &mut document.cursor);
+        std::mem::swap(&mut self.extra_cursors, &mut document.extra_cursors);
+        std::mem::swap(&mut self.scroll_request, &mut document.scroll_request);
+        std::mem::swap(
+            &mut self.scroll_top_request,
+            &mut document.scroll_top_request,
+        );
+        std::mem::swap(&mut self.top_line, &mut document.top_line);
+        std::mem::swap(&mut self.wrap_layout, &mut document.wrap_layout);
+    }
+
+    /// 新窗格从原来的窗格复制光标和滚动位置
+    fn duplicate(&self) -> Self {
+        Self {
+            cursor: self.cursor,
+            extra_cursors: self.extra_cursors.clone(),
+            scroll_request: None,
+            scroll_top_request: Some(self.top_line),
+            top_line: self.top_line,
+            wrap_layout: WrapLayout::default(),
+        }
+    }
+
+    /// 其他窗格修改了同一个文档，调整光标位置
+    fn shift(&mut self, splices: &[Splice]) {
+        shift_cursor(&mut self.cursor, splices);
+        for cursor in &mut self.extra_cursors {
+            *cursor = shift_range(*cursor, splices);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Pane {
+    /// 标签栏上的文档，按显示顺序
+    pub tabs: Vec<usize>,
+    pub active: Option<usize>,
+    /// 除了有焦点窗格的活动文档之外，各文档在这个窗格中的视图状态
+    views: HashMap<usize, View>,
+}
+
+enum Node {
+    Pane(usize),
+    Split {
+        dir: SplitDir,
+        /// 第一个子节点占的比例
+        ratio: f32,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    fn first_pane(&self) -> usize {
+        match self {
+            Node::Pane(id) => *id,
+            Node::Split { first, .. } => first.first_pane(),
+        }
+    }
+
+    /// 把窗格 `target` 换成 `target` 和 `new` 的拆分
+    fn split(&mut self, target: usize, new: usize, dir: SplitDir) -> bool {
+        match self {
+            Node::Pane(id) if *id == target => {
+                *self = Node::Split {
+                    dir,
+                    ratio: 0.5,
+                    first: Box::new(Node::Pane(target)),
+                    second: Box::new(Node::Pane(new)),
+                };
+                true
+            }
+            Node::Pane(_) => false,
+            Node::Split { first, second, .. } => {
+                first.split(target, new, dir) || second.split(target, new, dir)
+            }
+        }
+    }
+
+    /// 去掉窗格 `target`，它所在的拆分由另一半取代。返回取代它的子树中的第一个窗格
+    fn remove(&mut self, target: usize) -> Option<usize> {
+        let Node::Split { first, second, .. } = self else {
+            return None;
+        };
+        let keep = if matches!(**first, Node::Pane(id) if id == target) {
+            Some(std::mem::replace(&mut **second, Node::Pane(target)))
+        } else if matches!(**second, Node::Pane(id) if id == target) {
+            Some(std::mem::replace(&mut **first, Node::Pane(target)))
+        } else {
+            None
+        };
+        match keep {
+            Some(node) => {
+                *self = node;
+                Some(self.first_pane())
+            }
+            None => first.remove(target).or_else(|| second.remove(target)),
+        }
+    }
+}
+
+pub struct Panes {
+    panes: HashMap<usize, Pane>,
+    layout: Node,
+    focused: usize,
+    next_id: usize,
+    /// 上一帧各窗格的位置，按方向移动焦点时使用
+    rects: HashMap<usize, Rect>,
+}
+
+impl Default for Panes {
+    fn default() -> Self {
+        Self {
+            panes: HashMap::from([(0, Pane::default())]),
+            layout: Node::Pane(0),
+            focused: 0,
+            next_id: 1,
+            rects: HashMap::new(),
+        }
+    }
+}
+
+impl Panes {
+    pub fn count(&self) -> usize {
+        self.panes.len()
+    }
+
+    pub fn focused_id(&self) -> usize {
+        self.focused
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Pane> {
+        self.panes.get(&id)
+    }
+
+    pub fn focused(&self) -> &Pane {
+        &self.panes[&self.focused]
+    }
+
+    pub fn focused_mut(&mut self) -> &mut Pane {
+        self.panes
+            .get_mut(&self.focused)
+            .expect("focused pane exists")
+    }
+
+    /// 有焦点的窗格把活动文档的视图状态从文档中收回
+    pub fn check_in(&mut self, documents: &mut HashMap<usize, Document>) {
+        let pane = self.focused_mut();
+        if let Some(document) = pane.active.and_then(|id| documents.get_mut(&id)) {
+            let id = pane.active.expect("active document");
+            pane.views.insert(id, View::take_from(document));
+        }
+    }
+
+    /// 有焦点的窗格把活动文档的视图状态放回文档
+    pub fn check_out(&mut self, documents: &mut HashMap<usize, Document>) {
+        let pane = self.focused_mut();
+        let Some(id) = pane.active else {
+            return;
+        };
+        let Some(document) = documents.get_mut(&id) else {
+            return;
+        };
+        match pane.views.remove(&id) {
+            Some(view) => view.put_into(document),
+            // 第一次在这个窗格中显示，沿用文档原来的位置
+            None => {
+                document.scroll_top_request.get_or_insert(document.top_line);
+            }
+        }
+    }
+
+    /// 把有焦点的窗格拆成两半，新窗格显示同一个文档并获得焦点。
+    /// 调用前需要先 `check_in`，之后再 `check_out`
+    pub fn split(&mut self, dir: SplitDir) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let old = self.focused();
+        let mut pane = Pane {
+            tabs: old.active.into_iter().collect(),
+            active: old.active,
+            views: HashMap::new(),
+        };
+        if let Some(view) = old.active.and_then(|doc| old.views.get(&doc)) {
+            pane.views
+                .insert(old.active.expect("active document"), view.duplicate());
+        }
+        self.panes.insert(id, pane);
+        self.layout.split(self.focused, id, dir);
+        self.focused = id;
+    }
+
+    /// 关闭窗格（至少保留一个）。调用前需要先 `check_in`，之后再 `check_out`
+    pub fn close(&mut self, id: usize) {
+        if self.panes.len() < 2 || !self.panes.contains_key(&id) {
+            return;
+        }
+        let Some(next) = self.layout.remove(id) else {
+            return;
+        };
+        self.panes.remove(&id);
+        self.rects.remove(&id);
+        if self.focused == id {
+            self.focused = next;
+        }
+    }
+
+    pub fn set_focused(&mut self, id: usize) {
+        if self.panes.contains_key(&id) {
+            self.focused = id;
+        }
+    }
+
+    /// 有焦点的窗格在 `direction` 方向上最近的窗格
+    pub fn neighbor(&self, direction: Direction) -> Option<usize> {
+        let from = self.rects.get(&self.focused)?;
+        self.rects
+            .iter()
+            .filter(|&(&id, _)| id != self.focused)
+            .filter_map(|(&id, rect)| {
+                // 主方向上的距离，要求两个窗格在另一个方向上有重叠
+                let (distance, overlap) = match direction {
+                    Direction::Left => (
+                        from.left() - rect.right(),
+                        from.y_range().intersects(rect.y_range()),
+                    ),
+                    Direction::Right => (
+                        rect.left() - from.right(),
+                        from.y_range().intersects(rect.y_range()),
+                    ),
+                    Direction::Up => (
+                        from.top() - rect.bottom(),
+                        from.x_range().intersects(rect.x_range()),
+                    ),
+                    Direction::Down => (
+                        rect.top() - from.bottom(),
+                        from.x_range().intersects(rect.x_range()),
+                    ),
+                };
+                let offset = (rect.center() - from.center()).length();
+                (distance >= -1.0 && overlap).then_some((distance, offset, id))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .map(|(_, _, id)| id)
+    }
+
+    /// 从所有窗格中去掉文档 `doc`；空了的窗格随之关闭（至少保留一个）。
+    /// 调用前需要先 `check_in`，之后再 `check_out`
+    pub fn remove_document(&mut self, doc: usize) {
+        let ids: Vec<usize> = self.panes.keys().copied().collect();
+        for id in ids {
+            self.remove_from(id, doc);
+        }
+    }
+
+    /// 从窗格 `id` 的标签栏去掉文档 `doc`，优先激活右边的标签
+    pub fn remove_from(&mut self, id: usize, doc: usize) {
+        let Some(pane) = self.panes.get_mut(&id) else {
+            return;
+        };
+        let Some(index) = pane.tabs.iter().position(|&x| x == doc) else {
+            return;
+        };
+        pane.tabs.remove(index);
+        pane.views.remove(&doc);
+        if pane.active == Some(doc) {
+            pane.active = pane.tabs.get(index).or_else(|| pane.tabs.last()).copied();
+        }
+        if pane.tabs.is_empty() {
+            self.close(id);
+        }
+    }
+
+    /// 把文档 `doc` 的标签从窗格 `from` 移到窗格 `to` 的第 `index` 个位置。
+    /// 移到其他窗格时视图状态跟着过去，`to` 获得焦点并显示这个文档；原来的窗格空了就关闭。
+    /// 调用前需要先 `check_in`，之后再 `check_out`
+    pub fn move_tab(&mut self, doc: usize, from: usize, to: usize, index: usize) {
+        let contains = |id: usize| self.panes.get(&id).is_some_and(|p| p.tabs.contains(&doc));
+        if !contains(from) || !self.panes.contains_key(&to) {
+            return;
+        }
+        let view = if from == to {
+            None
+        } else {
+            let view = self.panes.get_mut(&from).and_then(|p| p.views.remove(&doc));
+            self.remove_from(from, doc);
+            view
+        };
+        let Some(pane) = self.panes.get_mut(&to) else {
+            return;
+        };
+        if let Some(i) = pane.tabs.iter().position(|&x| x == doc) {
+            pane.tabs.remove(i);
+        }
+        pane.tabs.insert(index.min(pane.tabs.len()), doc);
+        if from != to {
+            if let Some(view) = view {
+                pane.views.insert(doc, view);
+            }
+            pane.active = Some(doc);
+            self.focused = to;
+        }
+    }
+
+    /// 文档 `doc` 是否显示在窗格 `except` 以外的窗格中
+    pub fn shown_elsewhere(&self, doc: usize, except: usize) -> bool {
+        self.panes
+            .iter()
+            .any(|(&id, pane)| id != except && pane.tabs.contains(&doc))
+    }
+
+    /// 在窗格 `id` 中显示文档时使用它自己的视图状态。
+    /// 有焦点窗格的活动文档已经在文档字段里，直接调用 `f`
+    pub fn with_view<R>(
+        &mut self,
+        id: usize,
+        doc: usize,
+        document: &mut Document,
+        f: impl FnOnce(&mut Document) -> R,
+    ) -> R {
+        let focused = id == self.focused && self.focused().active == Some(doc);
+        let Some(pane) = self.panes.get_mut(&id).filter(|_| !focused) else {
+            return f(document);
+        };
+        let view = pane.views.entry(doc).or_default();
+        view.swap(document);
+        let result = f(document);
+        view.swap(document);
+        result
+    }
+
+    /// 文档 `doc` 被修改了，其他视图中的光标跟着移动。`source` 是修改时正在显示
+    /// 这个文档的窗格，它的视图已经调整过；`None` 表示在编辑器视图之外修改
+    /// （撤销、替换、重新加载等），修改的是文档字段中的视图
+    pub fn sync_views(
+        &mut self,
+        source: Option<usize>,
+        doc: usize,
+        document: &mut Document,
+        splices: &[Splice],
+    ) {
+        if splices.is_empty() {
+            return;
+        }
+        for (&id, pane) in &mut self.panes {
+            if Some(id) != source {
+                if let Some(view) = pane.views.get_mut(&doc) {
+                    view.shift(splices);
+                }
+            }
+        }
+        // 有焦点窗格的视图在文档字段里
+        if source.is_some_and(|id| id != self.focused) && self.focused().active == Some(doc) {
+            shift_cursor(&mut document.cursor, splices);
+            for cursor in &mut document.extra_cursors {
+                *cursor = shift_range(*cursor, splices);
+            }
+        }
+    }
+
+    /// 按布局划分 `rect`，处理分隔条的拖动，返回每个窗格的位置
+    pub fn layout(&mut self, ui: &mut egui::Ui, rect: Rect) -> Vec<(usize, Rect)> {
+        let mut rects = Vec::new();
+        layout_node(
+            ui,
+            &mut self.layout,
+            rect,
+            Id::new("pane_split"),
+            &mut rects,
+        );
+        self.rects = rects.iter().copied().collect();
+        rects
+    }
+}
+
+fn layout_node(
+    ui: &mut egui::Ui,
+    node: &mut Node,
+    rect: Rect,
+    id: Id,
+    out: &mut Vec<(usize, Rect)>,
+) {
+    const GAP: f32 = 6.0;
+    let Node::Split {
+        dir,
+        ratio,
+        first,
+        second,
+    } = node
+    else {
+        if let Node::Pane(pane) = node {
+            out.push((*pane, rect));
+        }
+        return;
+    };
+    let (first_rect, divider, second_rect) = match dir {
+        SplitDir::Horizontal => {
+            let x = rect.left() + rect.width() * *ratio;
+            let (left, rest) = rect.split_left_right_at_x(x - GAP / 2.0);
+            let (divider, right) = rest.split_left_right_at_x(x + GAP / 2.0);
+            (left, divider, right)
+        }
+        SplitDir::Vertical => {
+            let y = rect.top() + rect.height() * *ratio;
+            let (top, rest) = rect.split_top_bottom_at_y(y - GAP / 2.0);
+            let (divider, bottom) = rest.split_top_bottom_at_y(y + GAP / 2.0);
+            (top, divider, bottom)
+        }
+    };
+
+    // 拖动分隔条调整两边的大小
+    let response = ui.interact(divider, id, Sense::drag());
+    if response.hovered() || response.dragged() {
+        ui.ctx().set_cursor_icon(match dir {
+            SplitDir::Horizontal => CursorIcon::ResizeHorizontal,
+            SplitDir::Vertical => CursorIcon::ResizeVertical,
+        });
+    }
+    if response.dragged() {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let value = match dir {
+                SplitDir::Horizontal => (pos.x - rect.left()) / rect.width(),
+                SplitDir::Vertical => (pos.y - rect.top()) / rect.height(),
+            };
+            *ratio = value.clamp(0.1, 0.9);
+        }
+    }
+    let stroke = if response.hovered() || response.dragged() {
+        ui.visuals().selection.stroke
+    } else {
+        ui.visuals().widgets.noninteractive.bg_stroke
+    };
+    let painter = ui.painter();
+    match dir {
+        SplitDir::Horizontal => {
+            painter.vline(divider.center().x, divider.y_range(), stroke);
+        }
+        SplitDir::Vertical => {
+            painter.hline(divider.x_range(), divider.center().y, stroke);
+        }
+    }
+
+    layout_node(ui, first, first_rect, id.with(0), out);
+    layout_node(ui, second, second_rect, id.with(1), out);
+}
+
+/// 在有焦点的窗格周围画一圈边框，有多个窗格时使用
+pub fn paint_focus(ui: &egui::Ui, rect: Rect) {
+    let stroke = Stroke::new(1.0, ui.visuals().selection.stroke.color);
+    ui.painter()
+        .rect_stroke(rect, 0.0, stroke, StrokeKind::Inside);
+}
+
+/// 编辑后字符下标的新位置：落在被替换的范围内时移到替换文本的开头
+fn shift_index(mut index: usize, splices: &[Splice]) -> usize {
+    for splice in splices {
+        if index >= splice.start + splice.removed {
+            index = index - splice.removed + splice.inserted;
+        } else if index > splice.start {
+            index = splice.start;
+        }
+    }
+    index
+}
+
+fn shift_range(cursor: CCursorRange, splices: &[Splice]) -> CCursorRange {
+    CCursorRange::two(
+        CCursor::new(shift_index(cursor.secondary.index, splices)),
+        CCursor::new(shift_index(cursor.primary.index, splices)),
+    )
+}
+
+fn shift_cursor(cursor: &mut Option<CCursorRange>, splices: &[Splice]) {
+    if let Some(cursor) = cursor {
+        *cursor = shift_range(*cursor, splices);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::pos2;
+
+    fn caret(index: usize) -> Option<CCursorRange> {
+        Some(CCursorRange::one(CCursor::new(index)))
+    }
+
+    fn insert(start: usize, inserted: usize) -> Splice {
+        Splice {
+            start,
+            removed: 0,
+            inserted,
+        }
+    }
+
+    /// 窗格 0 和拆分出来的窗格 1 都显示文档 1，窗格 1 有焦点
+    fn split_document() -> (Panes, HashMap<usize, Document>) {
+        let mut documents = HashMap::from([(1, Document::new())]);
+        let document = documents.get_mut(&1).unwrap();
+        document.buffer.set_text("0123456789abcdef");
+        document.cursor = caret(5);
+        let mut panes = Panes::default();
+        let pane = panes.focused_mut();
+        pane.tabs.push(1);
+        pane.active = Some(1);
+        panes.check_in(&mut documents);
+        panes.split(SplitDir::Horizontal);
+        panes.check_out(&mut documents);
+        documents.get_mut(&1).unwrap().cursor = caret(10);
+        (panes, documents)
+    }
+
+    fn view_cursor(panes: &Panes, pane: usize, doc: usize) -> usize {
+        panes.panes[&pane].views[&doc].cursor.unwrap().primary.index
+    }
+
+    /// 布局的形状：`|` 左右并排，`/` 上下排列
+    fn shape(node: &Node) -> String {
+        match node {
+            Node::Pane(id) => id.to_string(),
+            Node::Split {
+                dir, first, second, ..
+            } => {
+                let sep = if *dir == SplitDir::Horizontal {
+                    "|"
+                } else {
+                    "/"
+                };
+                format!("({}{}{})", shape(first), sep, shape(second))
+            }
+        }
+    }
+
+    #[test]
+    fn shift_index_moves_past_edits() {
+        let replace = Splice {
+            start: 2,
+            removed: 4,
+            inserted: 1,
+        };
+        assert_eq!(shift_index(1, &[insert(2, 3)]), 1);
+        // 插入点上的位置移到插入文本之后
+        assert_eq!(shift_index(2, &[insert(2, 3)]), 5);
+        assert_eq!(shift_index(2, &[replace]), 2);
+        assert_eq!(shift_index(4, &[replace]), 2);
+        assert_eq!(shift_index(6, &[replace]), 3);
+        // 依次应用，后一处修改的位置以前一处修改之后为准
+        assert_eq!(shift_index(6, &[replace, insert(0, 2)]), 5);
+    }
+
+    #[test]
+    fn sync_views_skips_the_view_that_made_the_edit() {
+        let (mut panes, mut documents) = split_document();
+        let document = documents.get_mut(&1).unwrap();
+
+        // 在编辑器视图之外修改有焦点的文档：文档字段中的光标由修改它的代码负责
+        panes.sync_views(None, 1, document, &[insert(0, 3)]);
+        assert_eq!(view_cursor(&panes, 0, 1), 8);
+        assert_eq!(document.cursor.unwrap().primary.index, 10);
+
+        // 在窗格 0 中输入：有焦点窗格的光标在文档字段里
+        panes.sync_views(Some(0), 1, document, &[insert(0, 3)]);
+        assert_eq!(view_cursor(&panes, 0, 1), 8);
+        assert_eq!(document.cursor.unwrap().primary.index, 13);
+    }
+
+    #[test]
+    fn closing_a_pane_collapses_its_split() {
+        let (mut panes, mut documents) = split_document();
+        panes.check_in(&mut documents);
+        panes.split(SplitDir::Vertical);
+        assert_eq!(shape(&panes.layout), "(0|(1/2))");
+        assert_eq!(panes.focused_id(), 2);
+
+        panes.close(2);
+        assert_eq!(shape(&panes.layout), "(0|1)");
+        assert_eq!(panes.focused_id(), 1);
+
+        let mut layout = Node::Split {
+            dir: SplitDir::Horizontal,
+            ratio: 0.5,
+            first: Box::new(Node::Pane(0)),
+            second: Box::new(Node::Split {
+                dir: SplitDir::Vertical,
+                ratio: 0.5,
+                first: Box::new(Node::Pane(1)),
+                second: Box::new(Node::Pane(2)),
+            }),
+        };
+        assert_eq!(layout.remove(0), Some(1));
+        assert_eq!(shape(&layout), "(1/2)");
+        assert_eq!(layout.remove(3), None);
+        assert_eq!(layout.remove(2), Some(1));
+        assert_eq!(shape(&layout), "1");
+        assert_eq!(layout.remove(1), None);
+    }
+
+    #[test]
+    fn neighbor_picks_the_closest_overlapping_pane() {
+        let mut panes = Panes {
+            rects: HashMap::from([
+                (0, Rect::from_min_max(pos2(0.0, 0.0), pos2(100.0, 100.0))),
+                (1, Rect::from_min_max(pos2(100.0, 0.0), pos2(200.0, 40.0))),
+                (2, Rect::from_min_max(pos2(100.0, 40.0), pos2(200.0, 100.0))),
+            ]),
+            ..Panes::default()
+        };
+        assert_eq!(panes.neighbor(Direction::Right), Some(2));
+        assert_eq!(panes.neighbor(Direction::Left), None);
+        panes.focused = 1;
+        assert_eq!(panes.neighbor(Direction::Down), Some(2));
+        assert_eq!(panes.neighbor(Direction::Left), Some(0));
+        assert_eq!(panes.neighbor(Direction::Up), None);
+    }
+
+    #[test]
+    fn move_tab_to_another_pane_moves_it_with_its_view() {
+        let (mut panes, mut documents) = split_document();
+        documents.insert(2, Document::new());
+        panes.check_in(&mut documents);
+        panes.panes.get_mut(&0).unwrap().tabs.push(2);
+
+        // 把窗格 1 中的文档 1 拖到窗格 0 的最前面：窗格 1 空了，关闭
+        panes.move_tab(1, 1, 0, 0);
+        panes.check_out(&mut documents);
+        assert_eq!(panes.count(), 1);
+        assert_eq!(panes.focused_id(), 0);
+        assert_eq!(panes.focused().tabs, vec![1, 2]);
+        assert_eq!(panes.focused().active, Some(1));
+        assert_eq!(documents[&1].cursor.unwrap().primary.index, 10);
+
+        // 同一个窗格中只调整顺序
+        panes.check_in(&mut documents);
+        panes.move_tab(1, 0, 0, 5);
+        panes.check_out(&mut documents);
+        assert_eq!(panes.focused().tabs, vec![2, 1]);
+        assert_eq!(panes.focused().active, Some(1));
+    }
+}
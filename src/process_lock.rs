@@ -0,0 +1,115 @@
+//! 进程锁：锁文件里写着持有者的进程号，表示会话、恢复快照等文件正被一个还在运行的进程使用。
+//!
+//! 进程崩溃后锁文件会留下来，但其中的进程已经不在了，其他进程可以接手。
+//! Windows 上持有者打开锁文件时不允许其他进程写入，进程退出后句柄关闭才能打开。
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// 持有期间锁文件一直打开着，释放时删除
+pub struct ProcessLock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl ProcessLock {
+    /// 取得 `path` 处的锁；已经被一个还在运行的进程持有时返回 `Ok(None)`
+    pub fn acquire(path: &Path) -> io::Result<Option<Self>> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // 第一次失败时锁文件可能是崩溃的进程留下的，删掉后再试一次
+        for _ in 0..2 {
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(windows)]
+            std::os::windows::fs::OpenOptionsExt::share_mode(&mut options, FILE_SHARE_READ);
+            match options.open(path) {
+                Ok(mut file) => {
+                    file.write_all(std::process::id().to_string().as_bytes())?;
+                    return Ok(Some(Self {
+                        path: path.to_path_buf(),
+                        _file: file,
+                    }));
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    if is_held(path) {
+                        return Ok(None);
+                    }
+                    match fs::remove_file(path) {
+                        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                        _ => {}
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for ProcessLock {
+    fn drop(&mut self) {
+        // 同时启动的另一个进程可能已经把文件换成了它自己的，那就不要删
+        let ours = fs::read_to_string(&self.path)
+            .is_ok_and(|pid| pid.trim() == std::process::id().to_string());
+        if ours {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(windows)]
+const FILE_SHARE_READ: u32 = 1;
+
+/// `path` 处的锁是否被一个还在运行的进程持有
+#[cfg(unix)]
+pub fn is_held(path: &Path) -> bool {
+    let Ok(text) = fs::read_to_string(path) else {
+        return false;
+    };
+    // 进程号 0 和负数对 kill 有特殊含义
+    let Some(pid) = text
+        .trim()
+        .parse::<libc::pid_t>()
+        .ok()
+        .filter(|&pid| pid > 0)
+    else {
+        return false;
+    };
+    // SAFETY: 信号 0 只检查进程是否存在，不会发送信号
+    let result = unsafe { libc::kill(pid, 0) };
+    // EPERM：进程存在，只是属于其他用户
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+pub fn is_held(path: &Path) -> bool {
+    path.exists() && OpenOptions::new().write(true).open(path).is_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn lock_is_exclusive_until_dropped_or_owner_is_gone() {
+        let dir = std::env::temp_dir().join(format!("editor-rs-lock-{}", std::process::id()));
+        let path = dir.join("test.lock");
+
+        let lock = ProcessLock::acquire(&path).unwrap();
+        assert!(lock.is_some());
+        assert!(is_held(&path));
+        assert!(ProcessLock::acquire(&path).unwrap().is_none());
+        drop(lock);
+        assert!(!path.exists());
+
+        // 持有者已经不在了
+        fs::write(&path, "2147483000").unwrap();
+        assert!(!is_held(&path));
+        assert!(ProcessLock::acquire(&path).unwrap().is_some());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,440 @@
+//! 崩溃恢复：定期把未保存的文档写到恢复目录，正常退出时删除。
+//!
+//! 每个编辑器进程在 `<数据目录>/editor-rs/recovery/` 下有自己的子目录，
+//! 旁边是同名的锁文件（见 [`ProcessLock`]）。启动时，锁的持有者已经不在的目录就是
+//! 上次崩溃（或者被强制结束）留下的，询问用户是否恢复。
+
+use crate::buffer::TextBuffer;
+use crate::diff::{DiffLine, diff_text, diff_view};
+use crate::document::{Document, write_atomic};
+use crate::encoding::TextEncoding;
+use crate::line_ending;
+use crate::process_lock::{self, ProcessLock};
+use crate::tabs::Tabs;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+// 内容变化后多久写一次快照
+const SNAPSHOT_DELAY: Duration = Duration::from_secs(5);
+
+/// 快照旁边记录的文档信息
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SnapshotMeta {
+    path: Option<PathBuf>,
+    name: Option<String>,
+}
+
+enum Job {
+    Write {
+        id: usize,
+        meta: SnapshotMeta,
+        buffer: TextBuffer,
+    },
+    Remove(usize),
+}
+
+/// 本进程的快照：记录每个文档上次写入快照时的版本
+#[derive(Default)]
+pub struct Recovery {
+    dir: Option<PathBuf>,
+    // 持有期间其他进程不会把这个目录当作残留
+    lock: Option<ProcessLock>,
+    sender: Option<Sender<Job>>,
+    written: HashMap<usize, u64>,
+    // 内容第一次和快照不一致的时间，等 SNAPSHOT_DELAY 后再写
+    pending_since: Option<Instant>,
+}
+
+impl Recovery {
+    /// 创建本进程的恢复目录并启动写快照的后台线程
+    pub fn start() -> Self {
+        match recovery_root() {
+            Some(root) => Self::start_in(&root),
+            None => Self::default(),
+        }
+    }
+
+    fn start_in(root: &Path) -> Self {
+        let mut recovery = Self::default();
+        // 进程号可能被重复使用，再加上启动时间
+        let started = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        let dir = root.join(format!("{}-{}", std::process::id(), started));
+        // 先取得锁再创建目录，其他进程不会看到一个还没有上锁的目录
+        let lock = match ProcessLock::acquire(&lock_path(&dir)) {
+            Ok(Some(lock)) => lock,
+            Ok(None) => {
+                log::warn!("恢复目录已经被占用：{}", dir.display());
+                return recovery;
+            }
+            Err(err) => {
+                log::warn!("无法创建恢复目录的锁：{}\n{}", dir.display(), err);
+                return recovery;
+            }
+        };
+        if let Err(err) = fs::create_dir_all(&dir) {
+            log::warn!("无法创建恢复目录：{}\n{}", dir.display(), err);
+            return recovery;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let thread_dir = dir.clone();
+        let spawned = thread::Builder::new()
+            .name("recovery".to_owned())
+            .spawn(move || {
+                for job in receiver {
+                    run_job(&thread_dir, job);
+                }
+            });
+        match spawned {
+            Ok(_) => {
+                recovery.dir = Some(dir);
+                recovery.lock = Some(lock);
+                recovery.sender = Some(sender);
+            }
+            Err(err) => log::warn!("无法启动恢复线程：{}", err),
+        }
+        recovery
+    }
+
+    /// 每帧调用：未保存的文档内容变化后过一会儿写一次快照，已经保存或关闭的删掉
+    pub fn update(&mut self, ctx: &egui::Context, tabs: &Tabs) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let mut stale = Vec::new();
+        for (id, document) in tabs.iter() {
+            let version = document.buffer.version();
+            if document.is_dirty() && self.written.get(&id) != Some(&version) {
+                stale.push(id);
+            }
+        }
+        // 已经不需要快照的文档
+        let obsolete: Vec<usize> = self
+            .written
+            .keys()
+            .copied()
+            .filter(|&id| !tabs.get(id).is_some_and(Document::is_dirty))
+            .collect();
+        for id in obsolete {
+            self.written.remove(&id);
+            let _ = sender.send(Job::Remove(id));
+        }
+
+        if stale.is_empty() {
+            self.pending_since = None;
+            return;
+        }
+        let since = *self.pending_since.get_or_insert_with(Instant::now);
+        let elapsed = since.elapsed();
+        if elapsed < SNAPSHOT_DELAY {
+            ctx.request_repaint_after(SNAPSHOT_DELAY - elapsed);
+            return;
+        }
+        self.pending_since = None;
+        for id in stale {
+            let Some(document) = tabs.get(id) else {
+                continue;
+            };
+            // Rope 的克隆只复制根节点，真正的写入在后台线程
+            let job = Job::Write {
+                id,
+                meta: SnapshotMeta {
+                    path: document.path.clone(),
+                    name: document.name.clone(),
+                },
+                buffer: document.buffer.clone(),
+            };
+            if sender.send(job).is_ok() {
+                self.written.insert(id, document.buffer.version());
+            }
+        }
+    }
+
+    /// 找出已经退出的其他进程留下的快照
+    pub fn find_leftovers(&self) -> Vec<RecoveredFile> {
+        match recovery_root() {
+            Some(root) => self.leftovers_in(&root),
+            None => Vec::new(),
+        }
+    }
+
+    fn leftovers_in(&self, root: &Path) -> Vec<RecoveredFile> {
+        let Ok(entries) = fs::read_dir(root) else {
+            return Vec::new();
+        };
+        let mut files = Vec::new();
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            if !dir.is_dir() || self.dir.as_ref() == Some(&dir) {
+                continue;
+            }
+            if process_lock::is_held(&lock_path(&dir)) {
+                continue;
+            }
+            let Ok(snapshots) = fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut found = false;
+            for snapshot in snapshots.flatten() {
+                let meta_path = snapshot.path();
+                if meta_path.extension().is_none_or(|ext| ext != "toml") {
+                    continue;
+                }
+                let text_path = meta_path.with_extension("txt");
+                let (Ok(meta), Ok(text)) = (
+                    fs::read_to_string(&meta_path),
+                    fs::read_to_string(&text_path),
+                ) else {
+                    continue;
+                };
+                let meta: SnapshotMeta = toml::from_str(&meta).unwrap_or_default();
+                let diff = meta.path.as_deref().and_then(|path| {
+                    let bytes = fs::read(path).ok()?;
+                    let disk = TextEncoding::detect(&bytes).decode(&bytes)?;
+                    Some(diff_text(&line_ending::normalize(&disk), &text))
+                });
+                files.push(RecoveredFile {
+                    path: meta.path,
+                    name: meta.name,
+                    text,
+                    diff,
+                    snapshot: text_path,
+                });
+                found = true;
+            }
+            if !found {
+                remove_dir(&dir);
+            }
+        }
+        files
+    }
+
+    /// 正常退出：删除本进程的快照
+    pub fn finish(&mut self) {
+        self.sender = None;
+        if let Some(dir) = self.dir.take() {
+            let _ = fs::remove_dir_all(dir);
+        }
+        self.lock = None;
+    }
+}
+
+fn run_job(dir: &Path, job: Job) {
+    match job {
+        Job::Write { id, meta, buffer } => {
+            let text = buffer.to_string();
+            let meta = toml::to_string(&meta).unwrap_or_default();
+            // 先写内容再写信息，读取时以信息文件为准
+            let result = write_atomic(&dir.join(format!("{}.txt", id)), text.as_bytes())
+                .and_then(|_| write_atomic(&dir.join(format!("{}.toml", id)), meta.as_bytes()));
+            if let Err(err) = result {
+                log::warn!("写入恢复快照失败：{}", err);
+            }
+        }
+        Job::Remove(id) => {
+            let _ = fs::remove_file(dir.join(format!("{}.toml", id)));
+            let _ = fs::remove_file(dir.join(format!("{}.txt", id)));
+        }
+    }
+}
+
+/// 恢复目录旁边的锁文件：`<目录>.lock`
+fn lock_path(dir: &Path) -> PathBuf {
+    dir.with_extension("lock")
+}
+
+/// 删除其他进程留下的恢复目录和它的锁文件
+fn remove_dir(dir: &Path) {
+    let _ = fs::remove_dir_all(dir);
+    let _ = fs::remove_file(lock_path(dir));
+}
+
+/// `<数据目录>/editor-rs/recovery/`
+fn recovery_root() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("editor-rs").join("recovery"))
+}
+
+/// 上次没有正常退出时留下的一份快照
+pub struct RecoveredFile {
+    pub path: Option<PathBuf>,
+    pub name: Option<String>,
+    pub text: String,
+    /// 和磁盘上的文件相比的差异；文件不存在时为 `None`
+    pub diff: Option<Vec<DiffLine>>,
+    snapshot: PathBuf,
+}
+
+impl RecoveredFile {
+    pub fn title(&self) -> String {
+        self.path
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .or_else(|| self.name.clone())
+            .unwrap_or_else(|| "未命名".to_owned())
+    }
+
+    /// 删除快照文件；目录空了时整个删掉
+    pub fn discard(&self) {
+        let _ = fs::remove_file(&self.snapshot);
+        let _ = fs::remove_file(self.snapshot.with_extension("toml"));
+        let Some(dir) = self.snapshot.parent() else {
+            return;
+        };
+        if fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none()) {
+            remove_dir(dir);
+        }
+    }
+}
+
+/// 恢复对话框中的选择
+pub enum RecoveryAction {
+    Recover(usize),
+    Discard(usize),
+    RecoverAll,
+    DiscardAll,
+}
+
+/// 「恢复未保存的工作」对话框，`files` 为空时不显示
+pub fn recovery_dialog(ctx: &egui::Context, files: &[RecoveredFile]) -> Option<RecoveryAction> {
+    if files.is_empty() {
+        return None;
+    }
+    let mut action = None;
+    egui::Window::new("Recover unsaved work")
+        .collapsible(false)
+        .resizable(true)
+        .default_width(640.0)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label("编辑器上次没有正常退出，以下文件有未保存的修改：");
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .max_height(480.0)
+                .show(ui, |ui| {
+                    for (index, file) in files.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.strong(file.title());
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    if ui.button("丢弃").clicked() {
+                                        action = Some(RecoveryAction::Discard(index));
+                                    }
+                                    if ui.button("恢复").clicked() {
+                                        action = Some(RecoveryAction::Recover(index));
+                                    }
+                                },
+                            );
+                        });
+                        egui::CollapsingHeader::new("差异")
+                            .id_salt(("recovery_diff", index))
+                            .show(ui, |ui| snapshot_diff(ui, file));
+                        ui.separator();
+                    }
+                });
+            ui.horizontal(|ui| {
+                if ui.button("全部恢复").clicked() {
+                    action = Some(RecoveryAction::RecoverAll);
+                }
+                if ui.button("全部丢弃").clicked() {
+                    action = Some(RecoveryAction::DiscardAll);
+                }
+            });
+        });
+    action
+}
+
+fn snapshot_diff(ui: &mut egui::Ui, file: &RecoveredFile) {
+    let Some(diff) = &file.diff else {
+        ui.weak(format!(
+            "磁盘上没有对应的文件，快照共 {} 行",
+            file.text.lines().count()
+        ));
+        return;
+    };
+    if diff.is_empty() {
+        ui.weak("与磁盘上的文件相同");
+        return;
+    }
+    diff_view(ui, diff);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("editor-rs-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn write_snapshot(dir: &Path, id: usize, name: &str, text: &str) {
+        let mut buffer = TextBuffer::default();
+        buffer.set_text(text);
+        let meta = SnapshotMeta {
+            path: None,
+            name: Some(name.to_owned()),
+        };
+        run_job(dir, Job::Write { id, meta, buffer });
+    }
+
+    #[test]
+    fn snapshots_are_written_and_removed() {
+        let dir = temp_root("snapshot");
+        write_snapshot(&dir, 3, "草稿", "你好\n");
+        assert_eq!(fs::read_to_string(dir.join("3.txt")).unwrap(), "你好\n");
+        let meta: SnapshotMeta =
+            toml::from_str(&fs::read_to_string(dir.join("3.toml")).unwrap()).unwrap();
+        assert_eq!(meta.name.as_deref(), Some("草稿"));
+
+        run_job(&dir, Job::Remove(3));
+        assert!(!dir.join("3.txt").exists());
+        assert!(!dir.join("3.toml").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn leftovers_come_only_from_processes_that_are_gone() {
+        let root = temp_root("leftovers");
+        let recovery = Recovery::start_in(&root);
+        let own = recovery.dir.clone().unwrap();
+        write_snapshot(&own, 1, "自己的", "mine");
+
+        // 另一个还在运行的进程（这里就是本进程）持有的目录
+        let running = root.join("running");
+        let _lock = ProcessLock::acquire(&lock_path(&running)).unwrap().unwrap();
+        fs::create_dir_all(&running).unwrap();
+        write_snapshot(&running, 1, "运行中", "running");
+
+        // 崩溃的进程留下的目录：锁文件中的进程已经不在了
+        let crashed = root.join("crashed");
+        fs::create_dir_all(&crashed).unwrap();
+        fs::write(lock_path(&crashed), "2147483000").unwrap();
+        write_snapshot(&crashed, 2, "崩溃", "lost");
+        // 没有快照的残留目录直接删掉
+        let empty = root.join("empty");
+        fs::create_dir_all(&empty).unwrap();
+
+        let files = recovery.leftovers_in(&root);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].title(), "崩溃");
+        assert_eq!(files[0].text, "lost");
+        assert!(files[0].diff.is_none());
+        assert!(!empty.exists());
+
+        files[0].discard();
+        assert!(!crashed.exists());
+        assert!(!lock_path(&crashed).exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,206 @@
+//! 会话：退出时记下打开的标签、光标和滚动位置、折叠的区域、侧边栏和窗口位置，下次启动时恢复。
+//!
+//! 会话保存在配置目录下的 `session.toml`；开启「热退出」时，未保存的内容
+//! 另外写到 `hot-exit/` 目录下，退出时不再询问是否保存。
+//!
+//! 同一时间只有持有 `session.lock` 的窗口读写会话和 `hot-exit/`，其他窗口不恢复也不保存。
+
+use crate::document::{Document, write_atomic};
+use crate::encoding::TextEncoding;
+use crate::line_ending::LineEnding;
+use crate::process_lock::ProcessLock;
+use eframe::egui::text::{CCursor, CCursorRange};
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const SESSION_FILE: &str = "session.toml";
+const HOT_EXIT_DIR: &str = "hot-exit";
+const LOCK_FILE: &str = "session.lock";
+
+/// 窗口的位置和大小（逻辑像素）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+    pub width: f32,
+    pub height: f32,
+    pub maximized: bool,
+}
+
+/// 一个标签页的状态
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TabState {
+    pub path: Option<PathBuf>,
+    pub name: Option<String>,
+    /// 光标和选区另一端的字符下标
+    pub cursor: usize,
+    pub anchor: usize,
+    pub top_line: usize,
+    /// 折叠起来的区域的标题行
+    pub folded: Vec<usize>,
+    pub encoding: String,
+    pub bom: bool,
+    pub line_ending: String,
+    /// 未保存的内容在 `hot-exit/` 下的文件名
+    pub unsaved: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub window: Option<WindowGeometry>,
+    pub explorer_open: bool,
+    pub workspace: Option<PathBuf>,
+    pub find_in_files_open: bool,
+    /// 命令面板中最近使用的命令 id
+    pub recent_commands: Vec<String>,
+    pub tabs: Vec<TabState>,
+    /// 活动标签在 `tabs` 中的下标
+    pub active: Option<usize>,
+}
+
+/// `<配置目录>/editor-rs/`
+fn session_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("editor-rs"))
+}
+
+/// 取得会话的所有权；另一个还在运行的窗口已经拥有时返回 `None`
+pub fn lock() -> Option<ProcessLock> {
+    let path = session_dir()?.join(LOCK_FILE);
+    ProcessLock::acquire(&path).unwrap_or_else(|err| {
+        log::warn!("无法创建会话锁：{}\n{}", path.display(), err);
+        None
+    })
+}
+
+impl Session {
+    /// 读取上次的会话；没有或者读取失败时返回 `None`
+    pub fn load() -> Option<Self> {
+        let path = session_dir()?.join(SESSION_FILE);
+        let text = fs::read_to_string(&path).ok()?;
+        match toml::from_str(&text) {
+            Ok(session) => Some(session),
+            Err(err) => {
+                log::warn!("会话文件有误：{}\n{}", path.display(), err);
+                None
+            }
+        }
+    }
+
+    /// 写入会话文件。`hot_exit` 为真时把未保存的文档内容也写下来，
+    /// 否则只记录已经保存过的文件
+    pub fn save<'a>(
+        mut self,
+        documents: impl IntoIterator<Item = (bool, &'a Document)>,
+        hot_exit: bool,
+    ) -> io::Result<()> {
+        let dir = session_dir().ok_or_else(|| io::Error::other("找不到配置目录"))?;
+        let hot_exit_dir = dir.join(HOT_EXIT_DIR);
+        // 上次留下的内容已经恢复过了，整个重写
+        match fs::remove_dir_all(&hot_exit_dir) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        fs::create_dir_all(&hot_exit_dir)?;
+
+        for (active, document) in documents {
+            // 没有路径的文档（例如标准输入）即使没改过，内容也只在内存里
+            let unsaved =
+                document.is_dirty() || (document.path.is_none() && !document.buffer.is_empty());
+            let mut tab = TabState::from_document(document);
+            if unsaved && hot_exit {
+                let file_name = format!("{}.txt", self.tabs.len());
+                write_atomic(
+                    &hot_exit_dir.join(&file_name),
+                    document.buffer.to_string().as_bytes(),
+                )?;
+                tab.unsaved = Some(file_name);
+            } else if document.path.is_none() {
+                continue;
+            }
+            if active {
+                self.active = Some(self.tabs.len());
+            }
+            self.tabs.push(tab);
+        }
+
+        let text = toml::to_string_pretty(&self).map_err(io::Error::other)?;
+        write_atomic(&dir.join(SESSION_FILE), text.as_bytes())
+    }
+}
+
+impl TabState {
+    fn from_document(document: &Document) -> Self {
+        let (cursor, anchor) = document
+            .cursor
+            .map_or((0, 0), |c| (c.primary.index, c.secondary.index));
+        Self {
+            path: document.path.clone(),
+            name: document.name.clone(),
+            cursor,
+            anchor,
+            top_line: document.top_line,
+            folded: document.folds.collapsed_lines(),
+            encoding: document.encoding.encoding.name().to_owned(),
+            bom: document.encoding.bom,
+            line_ending: document.line_ending.name().to_owned(),
+            unsaved: None,
+        }
+    }
+
+    /// 重新打开这个标签。文件已经不存在、也没有未保存的内容时返回 `None`
+    pub fn restore(&self) -> Option<Document> {
+        let unsaved = self.unsaved.as_ref().and_then(|name| {
+            let path = session_dir()?.join(HOT_EXIT_DIR).join(name);
+            match fs::read(&path) {
+                Ok(bytes) => Some(String::from_utf8_lossy(&bytes).into_owned()),
+                Err(err) => {
+                    log::warn!("无法读取未保存的内容：{}\n{}", path.display(), err);
+                    None
+                }
+            }
+        });
+        let mut document = match &unsaved {
+            Some(text) => {
+                Document::with_unsaved_text(self.path.as_deref(), text).unwrap_or_else(|err| {
+                    // 文件打不开时只保留路径，未保存的内容不能丢
+                    log::warn!("无法打开 {:?}：{}", self.path, err);
+                    let mut document = Document::new();
+                    if let Some(path) = &self.path {
+                        document.set_path(path);
+                    }
+                    document.replace_text(text);
+                    document
+                })
+            }
+            None => Document::open(self.path.as_deref()?).ok()?,
+        };
+        document.name = self.name.clone();
+        if unsaved.is_some() {
+            if let Some(encoding) = Encoding::for_label(self.encoding.as_bytes()) {
+                document.encoding = TextEncoding {
+                    encoding,
+                    bom: self.bom,
+                };
+            }
+            if let Some(ending) = LineEnding::ALL
+                .into_iter()
+                .find(|e| e.name() == self.line_ending)
+            {
+                document.line_ending = ending;
+            }
+        }
+        let len = document.buffer.len_chars();
+        document.cursor = Some(CCursorRange {
+            primary: CCursor::new(self.cursor.min(len)),
+            secondary: CCursor::new(self.anchor.min(len)),
+        });
+        document.scroll_top_request = Some(self.top_line);
+        document.folds.set_collapsed_lines(&self.folded);
+        Some(document)
+    }
+}
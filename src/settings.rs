@@ -0,0 +1,372 @@
+use crate::document::write_atomic;
+use crate::gutter::LineNumbers;
+use crate::indent::Indent;
+use eframe::egui;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// 内置字体的名字（`fonts/simsun.ttc`）
+pub const BUILTIN_FONT: &str = "simsun";
+/// egui 自带的字体，不含中文
+pub const EGUI_FONT: &str = "egui";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn preference(self) -> egui::ThemePreference {
+        match self {
+            Theme::System => egui::ThemePreference::System,
+            Theme::Light => egui::ThemePreference::Light,
+            Theme::Dark => egui::ThemePreference::Dark,
+        }
+    }
+
+    pub fn from_preference(preference: egui::ThemePreference) -> Self {
+        match preference {
+            egui::ThemePreference::System => Theme::System,
+            egui::ThemePreference::Light => Theme::Light,
+            egui::ThemePreference::Dark => Theme::Dark,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoSave {
+    Off,
+    /// 停止输入 `autosave_delay_ms` 毫秒后保存
+    AfterDelay,
+    /// 窗口失去焦点时保存
+    OnFocusChange,
+}
+
+/// 用户设置，保存在配置目录下的 `settings.toml` 中。缺少的项使用默认值。
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// `simsun`（内置）、`egui`（egui 自带）或者字体文件的路径
+    pub font_family: String,
+    pub font_size: f32,
+    pub tab_width: usize,
+    /// 没能从文件内容推测出缩进时，Tab 键插入空格还是制表符
+    pub insert_spaces: bool,
+    pub word_wrap: bool,
+    /// 在文本右侧显示缩略图
+    pub minimap: bool,
+    pub line_numbers: LineNumbers,
+    pub theme: Theme,
+    pub autosave: AutoSave,
+    pub autosave_delay_ms: u64,
+    /// 启动时恢复上次打开的标签和窗口位置（默认开启）
+    pub restore_session: bool,
+    /// 退出时不询问，未保存的内容留到下次启动（默认开启，需要同时开启 `restore_session`）
+    pub hot_exit: bool,
+    /// 没有上次的会话时的窗口大小
+    pub window_width: f32,
+    pub window_height: f32,
+    pub title_bar_height: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            font_family: BUILTIN_FONT.to_owned(),
+            font_size: 14.0,
+            tab_width: 4,
+            insert_spaces: true,
+            word_wrap: false,
+            minimap: true,
+            line_numbers: LineNumbers::Absolute,
+            theme: Theme::System,
+            autosave: AutoSave::Off,
+            autosave_delay_ms: 1000,
+            restore_session: true,
+            hot_exit: true,
+            window_width: 1280.0,
+            window_height: 1024.0,
+            title_bar_height: 32.0,
+        }
+    }
+}
+
+impl Settings {
+    /// 新文档的缩进方式
+    pub fn default_indent(&self) -> Indent {
+        if self.insert_spaces {
+            Indent::Spaces(self.tab_width.max(1))
+        } else {
+            Indent::Tabs
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(format!("读取设置失败：{}\n{}", path.display(), err)),
+        };
+        toml::from_str(&text).map_err(|err| format!("设置文件有误：{}\n{}", path.display(), err))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(self).map_err(io::Error::other)?;
+        write_atomic(path, text.as_bytes())
+    }
+}
+
+/// `<配置目录>/editor-rs/settings.toml`
+pub fn settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("editor-rs").join("settings.toml"))
+}
+
+/// 当前设置以及对设置文件的监听：文件被外部修改时自动重新读取。
+#[derive(Default)]
+pub struct SettingsStore {
+    pub settings: Settings,
+    /// 启动时读取设置文件出的错
+    pub load_error: Option<String>,
+    path: Option<PathBuf>,
+    watcher: Option<RecommendedWatcher>,
+    events: Option<Receiver<notify::Result<notify::Event>>>,
+}
+
+impl SettingsStore {
+    /// 读取设置文件；出错时使用默认设置，错误信息放在 `load_error` 中
+    pub fn load() -> Self {
+        let path = settings_path();
+        let (settings, load_error) = match path.as_deref().map(Settings::load) {
+            Some(Ok(settings)) => (settings, None),
+            Some(Err(err)) => (Settings::default(), Some(err)),
+            None => (Settings::default(), None),
+        };
+        Self {
+            settings,
+            load_error,
+            path,
+            ..Self::default()
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// 开始监听设置文件所在的目录（文件可能还不存在，或者被编辑器整个替换）
+    pub fn watch(&mut self, ctx: &egui::Context) {
+        let Some(dir) = self.path.as_ref().and_then(|p| p.parent()) else {
+            return;
+        };
+        if fs::create_dir_all(dir).is_err() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+            ctx.request_repaint();
+        });
+        match watcher {
+            Ok(mut watcher) => {
+                if let Err(err) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    log::warn!("无法监听设置文件：{}", err);
+                    return;
+                }
+                self.watcher = Some(watcher);
+                self.events = Some(receiver);
+            }
+            Err(err) => log::warn!("无法监听设置文件：{}", err),
+        }
+    }
+
+    /// 设置文件变化时重新读取。设置确实改变了返回 `Ok(true)`，文件有误时返回错误信息
+    pub fn poll(&mut self) -> Result<bool, String> {
+        let (Some(events), Some(path)) = (&self.events, &self.path) else {
+            return Ok(false);
+        };
+        let changed = events
+            .try_iter()
+            .flatten()
+            .any(|event| !event.kind.is_access() && event.paths.iter().any(|p| p == path));
+        if !changed {
+            return Ok(false);
+        }
+        let settings = Settings::load(path)?;
+        // 自己保存引起的变化不需要重新应用
+        if settings == self.settings {
+            return Ok(false);
+        }
+        self.settings = settings;
+        Ok(true)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Err("找不到配置目录".to_owned());
+        };
+        self.settings
+            .save(path)
+            .map_err(|err| format!("保存设置失败：{}\n{}", path.display(), err))
+    }
+}
+
+/// 设置页面。返回设置是否被修改
+pub fn settings_window(ctx: &egui::Context, open: &mut bool, store: &mut SettingsStore) -> bool {
+    let before = store.settings.clone();
+    let path = store.path().map(|p| p.display().to_string());
+    let settings = &mut store.settings;
+    egui::Window::new("Settings")
+        .open(open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("settings_grid")
+                .num_columns(2)
+                .spacing([24.0, 8.0])
+                .show(ui, |ui| {
+                    ui.label("字体");
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt("font_family")
+                            .selected_text(font_label(&settings.font_family))
+                            .show_ui(ui, |ui| {
+                                for family in [BUILTIN_FONT, EGUI_FONT] {
+                                    ui.selectable_value(
+                                        &mut settings.font_family,
+                                        family.to_owned(),
+                                        font_label(family),
+                                    );
+                                }
+                            });
+                        if ui.button("选择字体文件…").clicked() {
+                            if let Some(file) = rfd::FileDialog::new()
+                                .add_filter("字体", &["ttf", "otf", "ttc"])
+                                .pick_file()
+                            {
+                                settings.font_family = file.display().to_string();
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("字号");
+                    ui.add(egui::Slider::new(&mut settings.font_size, 8.0..=32.0).step_by(1.0));
+                    ui.end_row();
+
+                    ui.label("Tab 宽度");
+                    ui.add(egui::Slider::new(&mut settings.tab_width, 1..=8));
+                    ui.end_row();
+
+                    ui.label("缩进");
+                    ui.checkbox(&mut settings.insert_spaces, "用空格代替制表符");
+                    ui.end_row();
+
+                    ui.label("自动换行");
+                    ui.checkbox(&mut settings.word_wrap, "");
+                    ui.end_row();
+
+                    ui.label("缩略图");
+                    ui.checkbox(&mut settings.minimap, "");
+                    ui.end_row();
+
+                    ui.label("行号");
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut settings.line_numbers, LineNumbers::Absolute, "绝对");
+                        ui.selectable_value(&mut settings.line_numbers, LineNumbers::Relative, "相对");
+                        ui.selectable_value(&mut settings.line_numbers, LineNumbers::Off, "不显示");
+                    });
+                    ui.end_row();
+
+                    ui.label("主题");
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut settings.theme, Theme::System, "跟随系统");
+                        ui.selectable_value(&mut settings.theme, Theme::Light, "浅色");
+                        ui.selectable_value(&mut settings.theme, Theme::Dark, "深色");
+                    });
+                    ui.end_row();
+
+                    ui.label("自动保存");
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut settings.autosave, AutoSave::Off, "关闭");
+                        ui.selectable_value(&mut settings.autosave, AutoSave::AfterDelay, "延迟后");
+                        ui.selectable_value(
+                            &mut settings.autosave,
+                            AutoSave::OnFocusChange,
+                            "失去焦点时",
+                        );
+                    });
+                    ui.end_row();
+
+                    ui.label("自动保存延迟");
+                    ui.add_enabled(
+                        settings.autosave == AutoSave::AfterDelay,
+                        egui::Slider::new(&mut settings.autosave_delay_ms, 100..=10_000)
+                            .suffix(" ms")
+                            .logarithmic(true),
+                    );
+                    ui.end_row();
+
+                    ui.label("会话");
+                    ui.vertical(|ui| {
+                        ui.checkbox(&mut settings.restore_session, "启动时恢复上次打开的文件");
+                        ui.checkbox(&mut settings.hot_exit, "退出时保留未保存的修改，不再询问");
+                        // 删掉 settings.toml 中的这两项会回到默认的开启，退出时不再询问是否保存
+                        ui.weak("两项默认开启；设置文件中没有这两项时按开启处理");
+                    });
+                    ui.end_row();
+
+                    ui.label("标题栏高度");
+                    ui.add(egui::Slider::new(
+                        &mut settings.title_bar_height,
+                        24.0..=48.0,
+                    ));
+                    ui.end_row();
+
+                    ui.label("窗口大小");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut settings.window_width).range(400.0..=8192.0),
+                        );
+                        ui.label("×");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.window_height).range(300.0..=8192.0),
+                        );
+                    })
+                    .response
+                    .on_hover_text("没有上次的窗口位置时使用，下次启动时生效");
+                    ui.end_row();
+                });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("恢复默认").clicked() {
+                    *settings = Settings::default();
+                }
+                if let Some(path) = &path {
+                    ui.weak(path.as_str());
+                }
+            });
+        });
+    store.settings != before
+}
+
+fn font_label(family: &str) -> String {
+    match family {
+        BUILTIN_FONT => "宋体（内置）".to_owned(),
+        EGUI_FONT => "egui 默认".to_owned(),
+        path => Path::new(path)
+            .file_name()
+            .map_or_else(|| path.to_owned(), |n| n.to_string_lossy().into_owned()),
+    }
+}
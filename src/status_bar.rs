@@ -0,0 +1,175 @@
+use crate::buffer::LineCol;
+use crate::document::Document;
+use crate::encoding::{EncodingAction, encoding_button};
+use crate::highlight::Language;
+use crate::indent::Indent;
+use crate::line_ending::{LineEnding, line_ending_button};
+use eframe::egui;
+
+/// 在状态栏上点击某一项后要执行的操作
+pub enum StatusAction {
+    GoTo(LineCol),
+    Encoding(EncodingAction),
+    LineEnding(LineEnding),
+    Indent(Indent),
+    /// 把已有的行首缩进转换成指定方式
+    ConvertIndent(Indent),
+    Language(Language),
+    Save,
+}
+
+/// 底部状态栏：光标位置、选区大小、缩进、换行符、编码、语言和保存状态。
+#[derive(Default)]
+pub struct StatusBar {
+    // 「跳转到行」输入框的内容
+    goto_input: String,
+    /// 组合键已经按下的第一个键，显示在状态栏上
+    pub pending_keys: Option<String>,
+}
+
+impl StatusBar {
+    pub fn show(&mut self, ctx: &egui::Context, document: &Document) -> Option<StatusAction> {
+        let mut action = None;
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if document.is_dirty() {
+                    if ui
+                        .button("● 未保存")
+                        .on_hover_text("保存 (Ctrl+S)")
+                        .clicked()
+                    {
+                        action = Some(StatusAction::Save);
+                    }
+                } else {
+                    ui.weak("已保存");
+                }
+                if let Some(keys) = &self.pending_keys {
+                    ui.separator();
+                    ui.weak(format!("已按下 {}，等待下一个键…", keys));
+                }
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    // 从右往左排列
+                    if let Some(language) = language_menu(ui, document.highlighter.language()) {
+                        action = Some(StatusAction::Language(language));
+                    }
+                    let can_reopen = document.path.is_some() && !document.is_dirty();
+                    if let Some(choice) = encoding_button(ui, document.encoding, can_reopen) {
+                        action = Some(StatusAction::Encoding(choice));
+                    }
+                    let mixed = document.mixed_line_endings;
+                    if let Some(ending) = line_ending_button(ui, document.line_ending, mixed) {
+                        action = Some(StatusAction::LineEnding(ending));
+                    }
+                    if let Some(choice) = indent_menu(ui, document.indent) {
+                        action = Some(choice);
+                    }
+                    selection_label(ui, document);
+                    if let Some(position) = self.position_menu(ui, document) {
+                        action = Some(StatusAction::GoTo(position));
+                    }
+                });
+            });
+        });
+        action
+    }
+
+    /// 光标位置，点开后可以输入「行:列」跳转
+    fn position_menu(&mut self, ui: &mut egui::Ui, document: &Document) -> Option<LineCol> {
+        let cursor = document.cursor.map_or(0, |c| c.primary.index);
+        let position = document.buffer.char_to_line_col(cursor);
+        let mut goto = None;
+        ui.menu_button(
+            format!("Ln {}, Col {}", position.line + 1, position.col + 1),
+            |ui| {
+                ui.label(format!("跳转到行（1 - {}）：", document.buffer.len_lines()));
+                let edit = ui.add(
+                    egui::TextEdit::singleline(&mut self.goto_input)
+                        .hint_text("行[:列]")
+                        .desired_width(120.0),
+                );
+                edit.request_focus();
+                if edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    goto = parse_line_col(&self.goto_input);
+                    self.goto_input.clear();
+                    ui.close_menu();
+                }
+            },
+        );
+        goto
+    }
+}
+
+/// 选区的字符数和行数，没有选中内容时不显示
+fn selection_label(ui: &mut egui::Ui, document: &Document) {
+    let Some(cursor) = document.cursor else {
+        return;
+    };
+    let (a, b) = (cursor.primary.index, cursor.secondary.index);
+    if a == b {
+        return;
+    }
+    let (start, end) = (a.min(b), a.max(b));
+    let lines = document.buffer.char_to_line(end) - document.buffer.char_to_line(start) + 1;
+    ui.label(format!("已选择 {} 个字符，{} 行", end - start, lines));
+}
+
+fn indent_menu(ui: &mut egui::Ui, current: Indent) -> Option<StatusAction> {
+    let mut action = None;
+    ui.menu_button(current.to_string(), |ui| {
+        ui.label("Indent Using");
+        for indent in Indent::CHOICES {
+            if ui
+                .selectable_label(indent == current, indent.to_string())
+                .clicked()
+            {
+                action = Some(StatusAction::Indent(indent));
+                ui.close_menu();
+            }
+        }
+        ui.separator();
+        if ui.button("Convert Indentation to Spaces").clicked() {
+            let spaces = match current {
+                Indent::Spaces(n) => Indent::Spaces(n),
+                Indent::Tabs => Indent::Spaces(current.width()),
+            };
+            action = Some(StatusAction::ConvertIndent(spaces));
+            ui.close_menu();
+        }
+        if ui.button("Convert Indentation to Tabs").clicked() {
+            action = Some(StatusAction::ConvertIndent(Indent::Tabs));
+            ui.close_menu();
+        }
+    });
+    action
+}
+
+fn language_menu(ui: &mut egui::Ui, current: Language) -> Option<Language> {
+    let mut chosen = None;
+    ui.menu_button(current.name(), |ui| {
+        for language in Language::ALL {
+            if ui
+                .selectable_label(language == current, language.name())
+                .clicked()
+            {
+                chosen = Some(language);
+                ui.close_menu();
+            }
+        }
+    });
+    chosen
+}
+
+/// 解析「行」或「行:列」（从 1 开始），返回从 0 开始的位置
+pub fn parse_line_col(input: &str) -> Option<LineCol> {
+    let mut parts = input.trim().splitn(2, [':', ',']);
+    let line: usize = parts.next()?.trim().parse().ok()?;
+    let col: usize = match parts.next() {
+        Some(col) => col.trim().parse().ok()?,
+        None => 1,
+    };
+    Some(LineCol {
+        line: line.saturating_sub(1),
+        col: col.saturating_sub(1),
+    })
+}
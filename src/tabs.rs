@@ -0,0 +1,386 @@
+use crate::document::Document;
+use crate::panes::{Direction, Panes, SplitDir};
+use eframe::egui::{self, Sense, Stroke};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 标签栏上的用户操作，由 `EditorApp` 统一处理（关闭前可能需要确认保存）。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TabAction {
+    Activate(usize),
+    Close(usize),
+    CloseOthers(usize),
+    CloseToTheRight(usize),
+    /// 把窗格 `from` 中的标签 `id` 拖到窗格 `pane` 的 `to` 位置
+    Move {
+        id: usize,
+        from: usize,
+        pane: usize,
+        to: usize,
+    },
+    New,
+}
+
+/// 拖动中的标签和它所在的窗格
+struct DraggedTab {
+    pane: usize,
+    id: usize,
+}
+
+/// 多文档管理：每个标签页拥有自己的文档（缓冲区、路径、撤销历史等）。
+///
+/// 文档可以显示在多个分屏窗格中；标签的激活、排序、切换都作用于有焦点的窗格。
+pub struct Tabs {
+    tabs: HashMap<usize, Document>,
+    /// 所有文档按打开顺序排列（保存会话、全部关闭时使用）
+    order: Vec<usize>,
+    panes: Panes,
+    next_id: usize,
+}
+
+impl Default for Tabs {
+    fn default() -> Self {
+        let mut tabs = Self {
+            tabs: HashMap::new(),
+            order: Vec::new(),
+            panes: Panes::default(),
+            next_id: 0,
+        };
+        tabs.new_tab(Document::new());
+        tabs
+    }
+}
+
+impl Tabs {
+    /// 在有焦点的窗格中打开新标签
+    pub fn new_tab(&mut self, document: Document) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.tabs.insert(id, document);
+        self.order.push(id);
+        self.panes.check_in(&mut self.tabs);
+        let pane = self.panes.focused_mut();
+        pane.tabs.push(id);
+        pane.active = Some(id);
+        self.panes.check_out(&mut self.tabs);
+        id
+    }
+
+    /// 关闭文档，所有窗格中的这个标签都去掉
+    pub fn close_tab(&mut self, id: usize) -> Option<Document> {
+        self.panes.check_in(&mut self.tabs);
+        let document = self.tabs.remove(&id);
+        if document.is_some() {
+            self.order.retain(|&x| x != id);
+            self.panes.remove_document(id);
+        }
+        self.panes.check_out(&mut self.tabs);
+        document
+    }
+
+    /// 文档还显示在其他窗格中时，只从有焦点的窗格去掉这个标签，返回 `true`；
+    /// 否则需要真正关闭文档
+    pub fn detach(&mut self, id: usize) -> bool {
+        let focused = self.panes.focused_id();
+        if !self.panes.shown_elsewhere(id, focused) || !self.pane_tabs().contains(&id) {
+            return false;
+        }
+        self.panes.check_in(&mut self.tabs);
+        self.panes.remove_from(focused, id);
+        self.panes.check_out(&mut self.tabs);
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// 所有文档的 id，按打开顺序
+    pub fn ids(&self) -> Vec<usize> {
+        self.order.clone()
+    }
+
+    /// 有焦点的窗格标签栏上的文档
+    pub fn pane_tabs(&self) -> Vec<usize> {
+        self.panes.focused().tabs.clone()
+    }
+
+    /// 有焦点的窗格中 `id` 右侧所有标签的 id
+    pub fn ids_right_of(&self, id: usize) -> Vec<usize> {
+        match self.index_of(id) {
+            Some(index) => self.panes.focused().tabs[index + 1..].to_vec(),
+            None => Vec::new(),
+        }
+    }
+
+    /// 标签在有焦点的窗格标签栏上的位置
+    pub fn index_of(&self, id: usize) -> Option<usize> {
+        self.panes.focused().tabs.iter().position(|&x| x == id)
+    }
+
+    pub fn active_id(&self) -> Option<usize> {
+        self.panes.focused().active
+    }
+
+    /// 在有焦点的窗格中激活文档，不在这个窗格的标签栏上时加到当前标签后面
+    pub fn set_active(&mut self, id: usize) {
+        if !self.tabs.contains_key(&id) {
+            return;
+        }
+        self.panes.check_in(&mut self.tabs);
+        let pane = self.panes.focused_mut();
+        if !pane.tabs.contains(&id) {
+            let index = pane
+                .active
+                .and_then(|active| pane.tabs.iter().position(|&x| x == active))
+                .map_or(pane.tabs.len(), |i| i + 1);
+            pane.tabs.insert(index, id);
+        }
+        pane.active = Some(id);
+        self.panes.check_out(&mut self.tabs);
+    }
+
+    pub fn active(&self) -> Option<&Document> {
+        self.active_id().and_then(|id| self.tabs.get(&id))
+    }
+
+    pub fn active_mut(&mut self) -> Option<&mut Document> {
+        self.active_id().and_then(|id| self.tabs.get_mut(&id))
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Document> {
+        self.tabs.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Document> {
+        self.tabs.get_mut(&id)
+    }
+
+    /// 按标签顺序遍历
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Document)> {
+        self.order.iter().map(|id| (*id, &self.tabs[id]))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut Document)> {
+        self.tabs.iter_mut().map(|(id, doc)| (*id, doc))
+    }
+
+    /// 已经打开了这个文件的标签
+    pub fn find_by_path(&self, path: &Path) -> Option<usize> {
+        self.iter()
+            .find(|(_, doc)| doc.path.as_deref().is_some_and(|p| same_file(p, path)))
+            .map(|(id, _)| id)
+    }
+
+    /// 文件或文件夹被重命名 / 移动后，更新其中已打开文档的路径
+    pub fn rename_path(&mut self, from: &Path, to: &Path) {
+        for document in self.tabs.values_mut() {
+            let Some(path) = &document.path else {
+                continue;
+            };
+            if let Ok(rest) = path.strip_prefix(from) {
+                let path = if rest.as_os_str().is_empty() {
+                    to.to_path_buf()
+                } else {
+                    to.join(rest)
+                };
+                document.set_path(&path);
+            }
+        }
+    }
+
+    /// 把窗格 `from` 中的标签 `id` 移动到窗格 `pane` 的 `to` 位置；
+    /// 拖到其他窗格时从原来的窗格移走，在新窗格中激活
+    pub fn move_tab(&mut self, id: usize, from: usize, pane: usize, to: usize) {
+        if !self.tabs.contains_key(&id) {
+            return;
+        }
+        self.panes.check_in(&mut self.tabs);
+        self.panes.move_tab(id, from, pane, to);
+        self.panes.check_out(&mut self.tabs);
+    }
+
+    pub fn next_tab(&mut self, forward: bool) {
+        let tabs = &self.panes.focused().tabs;
+        let (Some(active), len) = (self.active_id().and_then(|id| self.index_of(id)), tabs.len()) else {
+            return;
+        };
+        let index = if forward {
+            (active + 1) % len
+        } else {
+            (active + len - 1) % len
+        };
+        self.set_active(tabs[index]);
+    }
+
+    pub fn pane_count(&self) -> usize {
+        self.panes.count()
+    }
+
+    pub fn focused_pane(&self) -> usize {
+        self.panes.focused_id()
+    }
+
+    /// 窗格中正在显示的文档
+    pub fn pane_active(&self, pane: usize) -> Option<usize> {
+        self.panes.get(pane).and_then(|p| p.active)
+    }
+
+    pub fn focus_pane(&mut self, pane: usize) {
+        if pane == self.panes.focused_id() {
+            return;
+        }
+        self.panes.check_in(&mut self.tabs);
+        self.panes.set_focused(pane);
+        self.panes.check_out(&mut self.tabs);
+    }
+
+    /// 焦点移到 `direction` 方向上相邻的窗格，没有时返回 `false`
+    pub fn focus_direction(&mut self, direction: Direction) -> bool {
+        match self.panes.neighbor(direction) {
+            Some(pane) => {
+                self.focus_pane(pane);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 拆分有焦点的窗格，新窗格显示同一个文档
+    pub fn split(&mut self, dir: SplitDir) {
+        self.panes.check_in(&mut self.tabs);
+        self.panes.split(dir);
+        self.panes.check_out(&mut self.tabs);
+    }
+
+    /// 按布局划分编辑区，返回每个窗格的位置
+    pub fn layout(&mut self, ui: &mut egui::Ui, rect: egui::Rect) -> Vec<(usize, egui::Rect)> {
+        self.panes.layout(ui, rect)
+    }
+
+    /// 用窗格 `pane` 自己的光标和滚动位置显示文档 `id`。
+    /// `f` 中的修改会同步到其他窗格中这个文档的光标
+    pub fn show_in_pane<R>(
+        &mut self,
+        pane: usize,
+        id: usize,
+        f: impl FnOnce(&mut Document) -> R,
+    ) -> Option<R> {
+        let document = self.tabs.get_mut(&id)?;
+        // 先同步在编辑器视图之外的修改
+        let splices = document.buffer.take_splices();
+        self.panes.sync_views(None, id, document, &splices);
+        let result = self.panes.with_view(pane, id, document, f);
+        let splices = document.buffer.take_splices();
+        self.panes.sync_views(Some(pane), id, document, &splices);
+        Some(result)
+    }
+
+    /// 把在编辑器视图之外的修改（撤销、替换、重新加载等）同步到各窗格的光标
+    pub fn sync_edits(&mut self) {
+        for (&id, document) in &mut self.tabs {
+            let splices = document.buffer.take_splices();
+            self.panes.sync_views(None, id, document, &splices);
+        }
+    }
+
+    /// 窗格 `pane` 的标签栏：点击切换、拖动排序（也可以拖到其他窗格）、中键关闭、右键菜单
+    pub fn tab_bar(&self, ui: &mut egui::Ui, pane_id: usize) -> Vec<TabAction> {
+        let mut actions = Vec::new();
+        let Some(pane) = self.panes.get(pane_id) else {
+            return actions;
+        };
+        egui::ScrollArea::horizontal()
+            .id_salt(("tab_bar_scroll", pane_id))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for (index, &id) in pane.tabs.iter().enumerate() {
+                        let document = &self.tabs[&id];
+                        let title = if document.is_dirty() {
+                            format!("● {}", document.title())
+                        } else {
+                            document.title()
+                        };
+                        let response = ui
+                            .add(
+                                egui::Button::new(title)
+                                    .selected(pane.active == Some(id))
+                                    .sense(Sense::click_and_drag()),
+                            )
+                            .on_hover_text(match &document.path {
+                                Some(path) => path.display().to_string(),
+                                None => document.title(),
+                            });
+
+                        if response.clicked() {
+                            actions.push(TabAction::Activate(id));
+                        }
+                        if response.middle_clicked() {
+                            actions.push(TabAction::Close(id));
+                        }
+
+                        // 拖动排序
+                        if response.drag_started() {
+                            response.dnd_set_drag_payload(DraggedTab { pane: pane_id, id });
+                        }
+                        if let Some(dragged) = response.dnd_hover_payload::<DraggedTab>() {
+                            if dragged.id != id {
+                                let x = response.rect.left() - 1.0;
+                                ui.painter().vline(
+                                    x,
+                                    response.rect.y_range(),
+                                    Stroke::new(2.0, ui.visuals().selection.stroke.color),
+                                );
+                            }
+                        }
+                        if let Some(dragged) = response.dnd_release_payload::<DraggedTab>() {
+                            if dragged.id != id {
+                                actions.push(TabAction::Move {
+                                    id: dragged.id,
+                                    from: dragged.pane,
+                                    pane: pane_id,
+                                    to: index,
+                                });
+                            }
+                        }
+
+                        response.context_menu(|ui| {
+                            if ui.button("Close").clicked() {
+                                actions.push(TabAction::Close(id));
+                                ui.close_menu();
+                            }
+                            if ui.button("Close Others").clicked() {
+                                actions.push(TabAction::CloseOthers(id));
+                                ui.close_menu();
+                            }
+                            if ui.button("Close to the Right").clicked() {
+                                actions.push(TabAction::CloseToTheRight(id));
+                                ui.close_menu();
+                            }
+                        });
+
+                        if ui.small_button("×").on_hover_text("关闭标签").clicked() {
+                            actions.push(TabAction::Close(id));
+                        }
+                        ui.add_space(4.0);
+                    }
+
+                    if ui.button("+").on_hover_text("新建标签").clicked() {
+                        actions.push(TabAction::New);
+                    }
+                });
+            });
+        actions
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
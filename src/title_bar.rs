@@ -3,7 +3,8 @@ use eframe::egui::{TopBottomPanel, ViewportCommand};
 
 #[derive(Clone, Copy, Default)]
 pub struct TitleBarPanel {
-    title: &'static str
+    title: &'static str,
+    height: f32,
 }
 
 impl TitleBarPanel {
@@ -105,7 +106,7 @@ impl TitleBarPanel {
     pub fn title_bar(self, ctx: &egui::Context) {
         let title_frame = self.custom_title_bar_frame(ctx);
 
-        TopBottomPanel::top("title_bar_panel").frame(title_frame).exact_height(32.0).show(ctx, |ui| {
+        TopBottomPanel::top("title_bar_panel").frame(title_frame).exact_height(self.height).show(ctx, |ui| {
             let rect = ui.max_rect();
             self.title_bar_ui(ui, rect);
         });
@@ -128,6 +129,10 @@ impl TitleBarPanel {
 
 impl TitleBarPanel {
     pub fn new(title: &'static str) -> Self {
-        TitleBarPanel { title }
+        TitleBarPanel { title, height: 32.0 }
+    }
+
+    pub fn set_height(&mut self, height: f32) {
+        self.height = height;
     }
 }